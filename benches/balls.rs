@@ -9,10 +9,10 @@ extern crate test;
 use test::Bencher;
 
 use std::sync::Arc;
-use std::cell::RefCell;
+use mech_core::sync::RefCell;
 use std::fmt;
 use std::ptr;
-use std::rc::Rc;
+use mech_core::sync::Rc;
 use hashbrown::{HashMap, HashSet};
 
 use rayon::prelude::*;
@@ -0,0 +1,116 @@
+#![allow(warnings)]
+#![feature(test)]
+
+extern crate test;
+
+use test::Bencher;
+
+use mech_core::sync::RefCell;
+use mech_core::sync::Rc;
+use mech_core::*;
+
+// Loads n blocks into a fresh core. Block i is triggered by #x{i} and writes
+// #y{i}, and every fourth block also reads the output of the block before it,
+// so the schedule has some depth as well as width. Loading time should grow
+// linearly with n.
+fn load_blocks(n: usize) {
+  let mut core = Core::new();
+  let mut blocks = vec![];
+  for i in 0..n {
+    let x = TableId::Global(hash_str(&format!("x{}",i)));
+    let y = TableId::Global(hash_str(&format!("y{}",i)));
+    let mut block = Block::new();
+    block.add_tfm(Transformation::NewTable{table_id: TableId::Local(hash_str(&format!("block{}",i))), rows: 1, columns: 1});
+    block.triggers.insert((x,RegisterIndex::All,RegisterIndex::All));
+    block.input.insert((x,RegisterIndex::All,RegisterIndex::All));
+    if i % 4 != 0 {
+      let prev = TableId::Global(hash_str(&format!("y{}",i-1)));
+      block.triggers.insert((prev,RegisterIndex::All,RegisterIndex::All));
+      block.input.insert((prev,RegisterIndex::All,RegisterIndex::All));
+    }
+    block.output.insert((y,RegisterIndex::All,RegisterIndex::All));
+    blocks.push(block);
+  }
+  core.load_blocks(&blocks);
+}
+
+#[bench]
+fn load_blocks_1e2(b: &mut Bencher) {
+  b.iter(|| load_blocks(1e2 as usize));
+}
+
+#[bench]
+fn load_blocks_1e3(b: &mut Bencher) {
+  b.iter(|| load_blocks(1e3 as usize));
+}
+
+#[bench]
+fn load_blocks_1e4(b: &mut Bencher) {
+  b.iter(|| load_blocks(1e4 as usize));
+}
+
+// Loads a chain of n blocks, where block i is triggered by the output of the
+// block before it. Every trigger upstream of a block reaches it, so this is
+// the worst case for keeping the solve orders up to date as blocks are added.
+// Loaded in reverse, every block loaded before a new one is downstream of it,
+// which is the worst case for checking whether the new block closes a cycle.
+// Each new block's output is also propagated down the whole chain below it,
+// so that part grows with the square of n however the schedule is kept.
+fn load_chain(n: usize, reversed: bool) {
+  let mut core = Core::new();
+  let mut blocks = vec![];
+  for i in 0..n {
+    let x = TableId::Global(hash_str(&format!("y{}",i)));
+    let y = TableId::Global(hash_str(&format!("y{}",i+1)));
+    let mut block = Block::new();
+    block.add_tfm(Transformation::NewTable{table_id: TableId::Local(hash_str(&format!("block{}",i))), rows: 1, columns: 1});
+    block.triggers.insert((x,RegisterIndex::All,RegisterIndex::All));
+    block.input.insert((x,RegisterIndex::All,RegisterIndex::All));
+    block.output.insert((y,RegisterIndex::All,RegisterIndex::All));
+    blocks.push(block);
+  }
+  if reversed {
+    blocks.reverse();
+  }
+  core.load_blocks(&blocks);
+}
+
+#[bench]
+fn load_chain_1e2(b: &mut Bencher) {
+  b.iter(|| load_chain(1e2 as usize, false));
+}
+
+#[bench]
+fn load_chain_5e2(b: &mut Bencher) {
+  b.iter(|| load_chain(5e2 as usize, false));
+}
+
+#[bench]
+fn load_chain_1e3(b: &mut Bencher) {
+  b.iter(|| load_chain(1e3 as usize, false));
+}
+
+#[bench]
+fn load_chain_2e3(b: &mut Bencher) {
+  b.iter(|| load_chain(2e3 as usize, false));
+}
+
+#[bench]
+fn load_reversed_chain_1e2(b: &mut Bencher) {
+  b.iter(|| load_chain(1e2 as usize, true));
+}
+
+#[bench]
+fn load_reversed_chain_5e2(b: &mut Bencher) {
+  b.iter(|| load_chain(5e2 as usize, true));
+}
+
+#[bench]
+fn load_reversed_chain_1e3(b: &mut Bencher) {
+  b.iter(|| load_chain(1e3 as usize, true));
+}
+
+#[bench]
+fn load_reversed_chain_2e3(b: &mut Bencher) {
+  b.iter(|| load_chain(2e3 as usize, true));
+}
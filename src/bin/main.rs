@@ -15,10 +15,10 @@
 // matrix library in std
 
 use std::sync::Arc;
-use std::cell::RefCell;
+use mech_core::sync::RefCell;
 use std::fmt;
 use std::ptr;
-use std::rc::Rc;
+use mech_core::sync::Rc;
 use hashbrown::{HashMap, HashSet};
 use std::time::{Instant};
 
@@ -16,13 +16,14 @@ use crate::function::{
   math::*,
   math_update::*,
 };
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::sync::RefCell;
+use crate::sync::Rc;
 use hashbrown::{HashMap, HashSet};
 use std::fmt;
 use serde::Serialize;
 use std::mem::transmute;
 use std::convert::TryInto;
+use std::time::{Duration, Instant};
 
 lazy_static! {
   pub static ref cF32L: u64 = hash_str("f32-literal");
@@ -53,17 +54,21 @@ lazy_static! {
 
 #[derive(Clone)]
 pub struct Plan{
-  plan: Vec<Rc<dyn MechFunction>>
+  plan: Vec<Rc<dyn MechFunction>>,
+  pub deferred: bool,   // Steps pushed onto a deferred plan aren't solved until the block runs
 }
 
 impl Plan {
   pub fn new () -> Plan {
     Plan {
       plan: Vec::new(),
+      deferred: false,
     }
   }
   pub fn push<S: MechFunction + 'static>(&mut self, mut fxn: S) {
-    fxn.solve();
+    if !self.deferred {
+      fxn.solve();
+    }
     self.plan.push(Rc::new(fxn));
   }
 
@@ -73,6 +78,20 @@ impl Plan {
     }
   }
 
+  // Solves the plan and returns how long each step took.
+  pub fn solve_profiled(&self) -> Vec<Duration> {
+    self.plan.iter().map(|fxn| {
+      let start = Instant::now();
+      fxn.solve();
+      start.elapsed()
+    }).collect()
+  }
+
+  // The name of the function compiled for each step.
+  pub fn kernel_names(&self) -> Vec<String> {
+    self.plan.iter().map(|fxn| fxn.name().to_string()).collect()
+  }
+
 }
 
 impl fmt::Debug for Plan {
@@ -101,6 +120,15 @@ pub enum BlockState {
   Disabled,     // The block is disabled will not execute if it otherwise would
 }
 
+// A summary of a block's state, for inspecting a running core.
+#[derive(Clone, Debug)]
+pub struct BlockStatus {
+  pub id: BlockId,
+  pub state: BlockState,
+  pub unsatisfied_transformation: Option<Transformation>,
+  pub error: Option<MechError>,
+}
+
 // ## Block
 
 #[derive(Clone)]
@@ -121,9 +149,18 @@ pub struct Block {
   pub required_functions: HashSet<u64>,
   pub strings: StringDictionary,
   pub triggers: HashSet<(TableId,RegisterIndex,RegisterIndex)>,
+  // The registers of the block's whenevers. A block with any only runs when
+  // one of them changes, whatever else it reads.
+  pub whenever_triggers: HashSet<(TableId,RegisterIndex,RegisterIndex)>,
   pub input: HashSet<(TableId,RegisterIndex,RegisterIndex)>,
   pub output: HashSet<(TableId,RegisterIndex,RegisterIndex)>,
   pub dynamic_tables: HashSet<(TableId,RegisterIndex,RegisterIndex)>,
+  pub temporal: Vec<Temporal>,
+  // The rows, columns and column kinds of each global table the block reads,
+  // as they were when the block was compiled.
+  pub input_shapes: HashMap<u64,(usize,usize,Vec<ValueKind>)>,
+  // The error from the block's most recent solve, if it failed.
+  pub last_error: Option<MechError>,
 }
 
 impl Block {
@@ -145,9 +182,13 @@ impl Block {
       transformations: Vec::new(),
       strings: Rc::new(RefCell::new(HashMap::new())),
       triggers: HashSet::new(),
+      whenever_triggers: HashSet::new(),
       input: HashSet::new(),
       output: HashSet::new(),
       dynamic_tables: HashSet::new(),
+      temporal: Vec::new(),
+      input_shapes: HashMap::new(),
+      last_error: None,
     }
   }
 
@@ -160,19 +201,19 @@ impl Block {
             Some(TableId::Global(id)) => match self.global_database.borrow().get_table_by_id(id) {
               Some(table) => Ok(table.clone()),
               None => {
-                let table_name: String = self.strings.borrow().get(table_id.unwrap()).unwrap().to_string();
+                let table_name = self.table_name(table_id);
                 Err(MechError{msg: table_name, id: 2101, kind: MechErrorKind::MissingTable(*table_id)})
               }
             }
             Some(TableId::Local(id)) => match self.tables.get_table_by_id(id) {
               Some(table) => Ok(table.clone()),
               None => {
-                let table_name: String = self.strings.borrow().get(table_id.unwrap()).unwrap().to_string();
+                let table_name = self.table_name(table_id);
                 Err(MechError{msg: table_name, id: 2102, kind: MechErrorKind::MissingTable(*table_id)})
               }
             }
             None => {
-              let table_name: String = self.strings.borrow().get(table_id.unwrap()).unwrap().to_string();
+              let table_name = self.table_name(table_id);
               Err(MechError{msg: table_name, id: 2103, kind: MechErrorKind::MissingTable(*table_id)})
             }
           }
@@ -184,7 +225,7 @@ impl Block {
           match self.pending_global_tables.get(id) {
             Some(table) => Ok(table.clone()),
             None => {
-              let table_name: String = self.strings.borrow().get(table_id.unwrap()).unwrap().to_string();
+              let table_name = self.table_name(table_id);
               Err(MechError{msg: table_name, id: 2104, kind: MechErrorKind::MissingTable(*table_id)})
             }
           }
@@ -193,6 +234,13 @@ impl Block {
     }
   }
 
+  fn table_name(&self, table_id: &TableId) -> String {
+    match self.strings.borrow().get(table_id.unwrap()) {
+      Some(name) => name.to_string(),
+      None => humanize(table_id.unwrap()),
+    }
+  }
+
   pub fn gen_id(&mut self) -> BlockId {
     self.id = hash_str(&format!("{:?}",self.transformations));
     self.id
@@ -201,19 +249,227 @@ impl Block {
   pub fn id(&self) -> BlockId {
     self.id
   }
+
+  // Every transformation the block was given, including the one it's stuck on
+  // and any queued behind it.
+  pub fn all_transformations(&self) -> Vec<Transformation> {
+    let mut tfms = self.transformations.clone();
+    if let Some((_,tfm)) = &self.unsatisfied_transformation {
+      tfms.push(tfm.clone());
+    }
+    tfms.append(&mut self.pending_transformations.clone());
+    tfms
+  }
   
-  // Clear everything and recompile it all
+  // Clear everything and recompile it all. Nothing is solved while the block
+  // is recompiled, since it's solved when the change that reshaped its inputs
+  // is propagated, and some kernels (appends, for one) don't give the same
+  // result when solved twice.
   pub fn recompile(&mut self) -> Result<(),MechError> {
     let tfms = self.transformations.clone();
+    // Timers carry over, so recompiling a block doesn't restart its waits.
+    let temporal = self.temporal.clone();
+    let deferred = self.plan.deferred;
     self.transformations.clear();
+    self.temporal.clear();
     self.plan = Plan::new();
+    self.plan.deferred = true;
     self.tables.clear();
     for tfm in tfms {
       self.compile_tfm(tfm)?;
     }
+    self.temporal = temporal;
+    self.plan.deferred = deferred;
+    self.input_shapes = self.read_input_shapes();
     Ok(())
   }
 
+  fn read_input_shapes(&self) -> HashMap<u64,(usize,usize,Vec<ValueKind>)> {
+    let database_brrw = self.global_database.borrow();
+    self.input.iter().filter_map(|(table_id,_,_)| {
+      let table = database_brrw.get_table_by_id(table_id.unwrap())?;
+      let table_brrw = table.borrow();
+      Some((*table_id.unwrap(),(table_brrw.rows,table_brrw.cols,table_brrw.col_kinds.clone())))
+    }).collect()
+  }
+
+  // Whether a table the block reads has changed shape since the block was
+  // compiled.
+  pub fn inputs_reshaped(&self) -> bool {
+    self.read_input_shapes() != self.input_shapes
+  }
+
+  // Starts the timers of the block's waits, measured from now. Timers that are
+  // already running are left alone.
+  pub fn start_timers(&mut self, now: u64) {
+    for temporal in &mut self.temporal {
+      if let Temporal::Wait{duration, deadline: deadline @ None, ..} = temporal {
+        *deadline = Some(now + *duration);
+      }
+    }
+  }
+
+  // Marks the waits whose timers have run out as elapsed, and returns whether
+  // any of them were.
+  pub fn release_waits(&mut self, now: u64) -> bool {
+    let mut released = false;
+    for temporal in &mut self.temporal {
+      if let Temporal::Wait{deadline: Some(deadline), elapsed: elapsed @ false, ..} = temporal {
+        if now >= *deadline {
+          *elapsed = true;
+          released = true;
+        }
+      }
+    }
+    released
+  }
+
+  // A suspended block isn't solved, even when it's triggered. That's the case
+  // while it's waiting on a timer or a condition, or once the condition it
+  // repeats until holds. Entry actions are always suspended, and are solved
+  // directly by their state machine.
+  pub fn is_suspended(&self) -> bool {
+    self.temporal.iter().any(|temporal| match temporal {
+      Temporal::Wait{elapsed,..} => !elapsed,
+      Temporal::WaitFor{table_id, indices} => !self.condition_holds(table_id, indices),
+      Temporal::Until{table_id, indices} => self.condition_holds(table_id, indices),
+      Temporal::OnEnter{..} => true,
+      Temporal::Rising{rising,..} => !rising,
+    })
+  }
+
+  // Reads the conditions of the block's edge-triggered whenevers, and notes
+  // which of them went from false to true since they were last read.
+  pub fn sample_edges(&mut self) {
+    let mut samples = vec![];
+    for temporal in &self.temporal {
+      if let Temporal::Rising{table_id, indices, ..} = temporal {
+        samples.push(self.condition_holds(table_id, indices));
+      }
+    }
+    let rising_edges = self.temporal.iter_mut().filter_map(|temporal| match temporal {
+      Temporal::Rising{last, rising, ..} => Some((last, rising)),
+      _ => None,
+    });
+    for ((last,rising),holds) in rising_edges.zip(samples) {
+      *rising = holds && !*last;
+      *last = holds;
+    }
+  }
+
+  // Rows selected by alias are looked up when the block is compiled, and
+  // replaced with the index of the row, so functions only ever see numerical
+  // rows. Only the first index is resolved, since that's the only one whose
+  // table is known before the block runs. Selecting a row of a table with more
+  // than one column selects the whole row.
+  fn resolve_row_aliases(&self, table_id: &TableId, indices: &Vec<(TableIndex, TableIndex)>) -> Result<Vec<(TableIndex, TableIndex)>,MechError> {
+    let mut indices = indices.clone();
+    if let Some((TableIndex::Alias(alias), col)) = indices.first() {
+      let table = self.get_table(table_id)?;
+      let table_brrw = table.borrow();
+      let ix = match table_brrw.row_map.get_index(alias) {
+        Ok(ix) => ix + 1,
+        Err(_) => {return Err(MechError{msg: humanize(alias), id: 2129, kind: MechErrorKind::MissingRowAlias(*table_id,*alias)});}
+      };
+      let col = match col {
+        TableIndex::None if table_brrw.cols > 1 => TableIndex::All,
+        col => col.clone(),
+      };
+      indices[0] = (TableIndex::Index(ix), col);
+    }
+    Ok(indices)
+  }
+
+  // Several columns selected by name, like #balls.(x,y), are swapped for a
+  // sub-table of the selected columns, so functions see an ordinary table.
+  // The sub-table shares its columns with the table they were selected from,
+  // so reading it costs nothing, and functions that write to it write to the
+  // original table. Selecting a single column by name is the same as using
+  // its alias.
+  fn select_columns(&mut self, table_id: &TableId, indices: &Vec<(TableIndex, TableIndex)>) -> Result<(TableId,Vec<(TableIndex, TableIndex)>),MechError> {
+    match indices.as_slice() {
+      [(row, TableIndex::Aliases(aliases))] if aliases.len() == 1 => {
+        Ok((*table_id,vec![(row.clone(),TableIndex::Alias(aliases[0]))]))
+      }
+      [(row, TableIndex::Aliases(aliases))] => {
+        let table = self.get_table(table_id)?;
+        let view_id = hash_str(&format!("{:?}{:?}", table_id, aliases));
+        let view = table.borrow().sub_table(view_id,aliases)?;
+        match self.tables.get_table_by_id(&view_id) {
+          Some(existing) => {*existing.borrow_mut() = view;}
+          None => {self.tables.insert_table(view)?;}
+        }
+        let row = match row {
+          TableIndex::None => TableIndex::All,
+          row => row.clone(),
+        };
+        Ok((TableId::Local(view_id),vec![(row,TableIndex::All)]))
+      }
+      _ => Ok((*table_id,indices.clone())),
+    }
+  }
+
+  // The register a whenever fires on. A row alias or index narrows it to that
+  // row, and a column alias or index narrows it to that column. Indices are
+  // given by alias when the row or column has one, to match the registers
+  // that setting it reports.
+  fn whenever_register(&self, table_id: &TableId, indices: &Vec<(TableIndex, TableIndex)>) -> (TableId,RegisterIndex,RegisterIndex) {
+    let mut row = RegisterIndex::All;
+    let mut col = RegisterIndex::All;
+    for (row_ix,col_ix) in indices {
+      match row_ix {
+        TableIndex::Alias(alias) => row = RegisterIndex::Alias(*alias),
+        TableIndex::Index(ix) => {
+          row = match self.get_table(table_id) {
+            Ok(table) => table.borrow().row_register(row_ix),
+            Err(_) => RegisterIndex::Index(*ix),
+          };
+        }
+        _ => (),
+      }
+      match col_ix {
+        TableIndex::Alias(alias) => col = RegisterIndex::Alias(*alias),
+        TableIndex::Index(_) => {
+          col = match self.get_table(table_id) {
+            Ok(table) => table.borrow().column_register(col_ix),
+            Err(_) => RegisterIndex::Index(col_ix.unwrap()),
+          };
+        }
+        _ => (),
+      }
+    }
+    (*table_id,row,col)
+  }
+
+  // Triggers the block on a register, unless it's only triggered by its
+  // whenevers.
+  fn add_trigger(&mut self, register: (TableId,RegisterIndex,RegisterIndex)) {
+    if self.whenever_triggers.is_empty() {
+      self.triggers.insert(register);
+    }
+  }
+
+  pub fn repeats(&self) -> bool {
+    self.temporal.iter().any(|temporal| match temporal {
+      Temporal::Until{..} => true,
+      _ => false,
+    })
+  }
+
+  // A condition holds when every value it selects is true.
+  pub fn condition_holds(&self, table_id: &TableId, indices: &Vec<(TableIndex, TableIndex)>) -> bool {
+    let table = match self.get_table(table_id) {
+      Ok(table) => table,
+      Err(_) => {return false;}
+    };
+    let table_brrw = table.borrow();
+    let values: Vec<Value> = match indices.as_slice() {
+      [] | [(TableIndex::All, TableIndex::All)] => (0..table_brrw.len()).filter_map(|ix| table_brrw.get_linear(ix).ok()).collect(),
+      _ => indices.iter().filter_map(|(row,col)| table_brrw.get(row,col).ok()).collect(),
+    };
+    values.len() > 0 && values.iter().all(|value| *value == Value::Bool(true))
+  }
+
   pub fn ready(&mut self) -> Result<(),MechError> {
     match self.state {
       // If the state is ready, we are good.
@@ -246,6 +502,7 @@ impl Block {
             for (_,table_ref) in self.pending_global_tables.drain() {
               self.global_database.borrow_mut().insert_table_ref(table_ref);
             }
+            self.input_shapes = self.read_input_shapes();
             Ok(())
           }
         }
@@ -590,32 +847,49 @@ impl Block {
       Transformation::TableDefine{table_id, indices, out} => {
         if let TableId::Global(_) = table_id { 
           self.input.insert((*table_id,RegisterIndex::All,RegisterIndex::All));
-          self.triggers.insert((*table_id,RegisterIndex::All,RegisterIndex::All));
+          self.add_trigger(trigger_register(table_id,indices));
         }
       }
       Transformation::ColumnAlias{table_id, column_ix, column_alias} => {
         if let TableId::Global(_) = table_id { 
-          self.triggers.insert((*table_id,RegisterIndex::All,RegisterIndex::Alias(*column_alias)));
+          self.add_trigger((*table_id,RegisterIndex::All,RegisterIndex::Alias(*column_alias)));
           self.input.insert((*table_id,RegisterIndex::All,RegisterIndex::Alias(*column_alias)));
           self.output.insert((*table_id,RegisterIndex::All,RegisterIndex::Alias(*column_alias)));
         }
       }
+      Transformation::RowAlias{table_id, row_ix, row_alias} => {
+        if let TableId::Global(_) = table_id { 
+          self.add_trigger((*table_id,RegisterIndex::Alias(*row_alias),RegisterIndex::All));
+          self.input.insert((*table_id,RegisterIndex::Alias(*row_alias),RegisterIndex::All));
+          self.output.insert((*table_id,RegisterIndex::Alias(*row_alias),RegisterIndex::All));
+        }
+      }
       Transformation::Function{name, ref arguments, out} => {
         self.required_functions.insert(*name);
         for (_,table_id,indices) in arguments {
           if let TableId::Global(_) = table_id {
-            let register = (*table_id,RegisterIndex::All,RegisterIndex::All);
-            self.input.insert(register);
-            self.triggers.insert(register);
+            self.input.insert((*table_id,RegisterIndex::All,RegisterIndex::All));
+            self.add_trigger(trigger_register(table_id,indices));
           }
         }
         if let (TableId::Global(table_id),_,_) = out {
           self.output.insert((TableId::Global(*table_id),RegisterIndex::All,RegisterIndex::All));
         }
       }
-      Transformation::Whenever{table_id, indices} => {
-        self.triggers.clear();
-        self.triggers.insert((*table_id,RegisterIndex::All,RegisterIndex::All));
+      Transformation::WheneverRising{table_id, indices} |
+      Transformation::WaitFor{table_id, indices} |
+      Transformation::Until{table_id, indices} => {
+        if let TableId::Global(_) = table_id { 
+          self.input.insert((*table_id,RegisterIndex::All,RegisterIndex::All));
+          self.add_trigger(trigger_register(table_id,indices));
+        }
+      }
+      Transformation::StateTransition{table_id, guard, ..} => {
+        for table_id in [table_id, guard] {
+          if let TableId::Global(_) = table_id { 
+            self.input.insert((*table_id,RegisterIndex::All,RegisterIndex::All));
+          }
+        }
       }
       _ => (),
     }
@@ -693,7 +967,7 @@ impl Block {
       }
       Transformation::ColumnAlias{table_id, column_ix, column_alias} => {
         if let TableId::Global(_) = table_id { 
-          self.triggers.insert((*table_id,RegisterIndex::All,RegisterIndex::Alias(*column_alias)));
+          self.add_trigger((*table_id,RegisterIndex::All,RegisterIndex::Alias(*column_alias)));
           self.input.insert((*table_id,RegisterIndex::All,RegisterIndex::Alias(*column_alias)));
           self.output.insert((*table_id,RegisterIndex::All,RegisterIndex::Alias(*column_alias)));
         }
@@ -704,13 +978,29 @@ impl Block {
         }
         table.set_col_alias(*column_ix,*column_alias);
       },
+      Transformation::RowAlias{table_id, row_ix, row_alias} => {
+        if let TableId::Global(_) = table_id { 
+          self.add_trigger((*table_id,RegisterIndex::Alias(*row_alias),RegisterIndex::All));
+          self.input.insert((*table_id,RegisterIndex::Alias(*row_alias),RegisterIndex::All));
+          self.output.insert((*table_id,RegisterIndex::Alias(*row_alias),RegisterIndex::All));
+        }
+        let table = self.get_table(table_id)?;
+        let mut table_brrw = table.borrow_mut();
+        if *row_ix + 1 > table_brrw.rows {
+          let cols = table_brrw.cols;
+          table_brrw.resize(*row_ix + 1,cols)?;
+        }
+        table_brrw.set_row_alias(*row_ix,*row_alias)?;
+      },
       Transformation::TableDefine{table_id, indices, out} => {
         if let TableId::Global(id) = table_id { 
           self.input.insert((*table_id,RegisterIndex::All,RegisterIndex::All));
-          self.triggers.insert((*table_id,RegisterIndex::All,RegisterIndex::All));
+          self.add_trigger(trigger_register(table_id,indices));
         }
         // Compile a Table Define function
-        let arguments =  vec![(0,table_id.clone(),indices.clone())];
+        let indices = self.resolve_row_aliases(table_id,indices)?;
+        let (table_id,indices) = self.select_columns(table_id,&indices)?;
+        let arguments =  vec![(0,table_id,indices)];
         let out =  (*out, TableIndex::All, TableIndex::All);
         let fxns = self.functions.clone();
         match &fxns {
@@ -888,10 +1178,70 @@ impl Block {
         table_brrw.set_raw(0,0,value.clone())?;
       }
       Transformation::Whenever{table_id, indices} => {
-        self.triggers.clear();
-        self.triggers.insert((*table_id,RegisterIndex::All,RegisterIndex::All));
+        let register = self.whenever_register(table_id,indices);
+        self.whenever_triggers.insert(register);
+        self.triggers = self.whenever_triggers.clone();
+      }
+      Transformation::WheneverRising{table_id, indices} => {
+        self.get_table(table_id)?;
+        self.plan.deferred = true;
+        let last = self.condition_holds(table_id, indices);
+        self.temporal.push(Temporal::Rising{table_id: *table_id, indices: indices.clone(), last, rising: false});
+      }
+      Transformation::Wait{duration} => {
+        self.plan.deferred = true;
+        self.temporal.push(Temporal::Wait{duration: *duration, deadline: None, elapsed: false});
+      }
+      Transformation::WaitFor{table_id, indices} => {
+        self.get_table(table_id)?;
+        self.plan.deferred = true;
+        self.temporal.push(Temporal::WaitFor{table_id: *table_id, indices: indices.clone()});
+      }
+      Transformation::Until{table_id, indices} => {
+        self.get_table(table_id)?;
+        self.temporal.push(Temporal::Until{table_id: *table_id, indices: indices.clone()});
+      }
+      Transformation::StateMachine{table_id, initial} => {
+        let table = self.get_table(table_id)?;
+        let mut table_brrw = table.borrow_mut();
+        // Recompiling the block leaves a running machine in its current state.
+        if table_brrw.is_empty() {
+          let name = match self.strings.borrow().get(initial) {
+            Some(name) => name.to_string(),
+            None => humanize(initial),
+          };
+          table_brrw.resize(1,1)?;
+          table_brrw.set_col_kind(0,ValueKind::String)?;
+          table_brrw.set_raw(0,0,Value::String(MechString::from_string(name)))?;
+        }
+      }
+      Transformation::StateTransition{table_id, guard, ..} => {
+        self.get_table(table_id)?;
+        self.get_table(guard)?;
+      }
+      Transformation::OnEnter{table_id, state} => {
+        self.get_table(table_id)?;
+        self.plan.deferred = true;
+        self.temporal.push(Temporal::OnEnter{table_id: *table_id, state: *state});
       }
       Transformation::Function{name, ref arguments, out} => {        
+        let mut resolved_arguments = vec![];
+        for (arg_name,table_id,indices) in arguments {
+          let indices = self.resolve_row_aliases(table_id,indices)?;
+          let (table_id,indices) = self.select_columns(table_id,&indices)?;
+          resolved_arguments.push((*arg_name,table_id,indices));
+        }
+        let arguments = &resolved_arguments;
+        let out = match out {
+          (table_id,row @ TableIndex::Alias(_),col) |
+          (table_id,row,col @ TableIndex::Aliases(_)) => {
+            let resolved = self.resolve_row_aliases(table_id,&vec![(row.clone(),col.clone())])?;
+            let (table_id,resolved) = self.select_columns(table_id,&resolved)?;
+            let (row,col) = resolved[0].clone();
+            (table_id,row,col)
+          }
+          out => out.clone(),
+        };
         // A list of all the functions that are
         // loaded onto this core.
         let fxns = self.functions.clone();
@@ -945,6 +1295,16 @@ impl Block {
     }
   }
 
+  pub fn solve_profiled(&self) -> Result<BlockTiming,MechError> {
+    if self.state == BlockState::Ready {
+      let start = Instant::now();
+      let steps = self.plan.solve_profiled();
+      Ok(BlockTiming{block_id: self.id, elapsed: start.elapsed(), steps})
+    } else {
+      Err(MechError{msg: "".to_string(), id: 2127, kind: MechErrorKind::GenericError("Block not ready".to_string())})
+    }
+  }
+
 }
 
 impl fmt::Debug for Block {
@@ -1018,6 +1378,17 @@ impl fmt::Debug for Block {
   }
 }
 
+// A block that reads from a single named column or row only needs to run when
+// that column or row changes. Any other selection triggers on the whole table.
+fn trigger_register(table_id: &TableId, indices: &Vec<(TableIndex, TableIndex)>) -> (TableId,RegisterIndex,RegisterIndex) {
+  match indices.as_slice() {
+    [(TableIndex::Alias(row_alias), TableIndex::Alias(alias))] => (*table_id,RegisterIndex::Alias(*row_alias),RegisterIndex::Alias(*alias)),
+    [(TableIndex::Alias(row_alias), _)] => (*table_id,RegisterIndex::Alias(*row_alias),RegisterIndex::All),
+    [(_, TableIndex::Alias(alias))] => (*table_id,RegisterIndex::All,RegisterIndex::Alias(*alias)),
+    _ => (*table_id,RegisterIndex::All,RegisterIndex::All),
+  }
+}
+
 // ### RegisterIndex
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
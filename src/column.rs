@@ -1,8 +1,8 @@
 use std::sync::Arc;
-use std::cell::RefCell;
+use crate::sync::{RefCell, Ref, RefMut};
 use std::fmt;
 use std::ptr;
-use std::rc::Rc;
+use crate::sync::Rc;
 use hashbrown::{HashMap, HashSet};
 
 use std::collections::VecDeque;
@@ -121,6 +121,65 @@ impl Column {
     Ok(())
   }
 
+  // Inserts count rows before row ix, in place, so compiled blocks holding the
+  // column see the new rows. An empty column has no rows to insert into.
+  pub fn insert_rows(&self, ix: usize, count: usize) -> std::result::Result<(),MechError> {
+    match self {
+      Column::U8(col) => col.insert_rows(ix,count,U8(0)),
+      Column::U16(col) => col.insert_rows(ix,count,U16(0)),
+      Column::U32(col) => col.insert_rows(ix,count,U32(0)),
+      Column::U64(col) => col.insert_rows(ix,count,U64(0)),
+      Column::U128(col) => col.insert_rows(ix,count,U128(0)),
+      Column::I8(col) => col.insert_rows(ix,count,I8(0)),
+      Column::I16(col) => col.insert_rows(ix,count,I16(0)),
+      Column::I32(col) => col.insert_rows(ix,count,I32(0)),
+      Column::I64(col) => col.insert_rows(ix,count,I64(0)),
+      Column::I128(col) => col.insert_rows(ix,count,I128(0)),
+      Column::f32(col) => col.insert_rows(ix,count,0.0),
+      Column::Length(col) | Column::Time(col) | Column::Speed(col) |
+      Column::Angle(col) |
+      Column::F32(col) => col.insert_rows(ix,count,F32(0.0)),
+      Column::F64(col) => col.insert_rows(ix,count,F64(0.0)),
+      Column::Ref(col) => col.insert_rows(ix,count,TableId::Local(0)),
+      Column::Index(col) => col.insert_rows(ix,count,0),
+      Column::Any(col) => col.insert_rows(ix,count,Value::Empty),
+      Column::Bool(col) => col.insert_rows(ix,count,false),
+      Column::String(col) => col.insert_rows(ix,count,MechString::new()),
+      Column::Empty => (),
+      Column::Reference(_) => {return Err(MechError{msg: "".to_string(), id: 9431, kind: MechErrorKind::None});}
+    }
+    Ok(())
+  }
+
+  // Keeps only the rows whose entry in keep is true, in place.
+  pub fn retain_rows(&self, keep: &Vec<bool>) -> std::result::Result<(),MechError> {
+    match self {
+      Column::U8(col) => col.retain_rows(keep)?,
+      Column::U16(col) => col.retain_rows(keep)?,
+      Column::U32(col) => col.retain_rows(keep)?,
+      Column::U64(col) => col.retain_rows(keep)?,
+      Column::U128(col) => col.retain_rows(keep)?,
+      Column::I8(col) => col.retain_rows(keep)?,
+      Column::I16(col) => col.retain_rows(keep)?,
+      Column::I32(col) => col.retain_rows(keep)?,
+      Column::I64(col) => col.retain_rows(keep)?,
+      Column::I128(col) => col.retain_rows(keep)?,
+      Column::f32(col) => col.retain_rows(keep)?,
+      Column::Length(col) | Column::Time(col) | Column::Speed(col) |
+      Column::Angle(col) |
+      Column::F32(col) => col.retain_rows(keep)?,
+      Column::F64(col) => col.retain_rows(keep)?,
+      Column::Ref(col) => col.retain_rows(keep)?,
+      Column::Index(col) => col.retain_rows(keep)?,
+      Column::Any(col) => col.retain_rows(keep)?,
+      Column::Bool(col) => col.retain_rows(keep)?,
+      Column::String(col) => col.retain_rows(keep)?,
+      Column::Empty => (),
+      Column::Reference(_) => {return Err(MechError{msg: "".to_string(), id: 9432, kind: MechErrorKind::None});}
+    }
+    Ok(())
+  }
+
   unwrap_column!(unwrap_u8,U8);
   unwrap_column!(unwrap_u16,U16);
   unwrap_column!(unwrap_u32,U32);
@@ -202,15 +261,37 @@ impl<T: Clone> ColumnV<T> {
     c_brrw[row] = value;
   }
 
-  pub fn borrow(&self) -> std::cell::Ref<Vec<T>> {
+  pub fn borrow(&self) -> Ref<Vec<T>> {
     let ColumnV(col) = self;
     col.borrow()
   }
 
-  pub fn borrow_mut(&self) -> std::cell::RefMut<Vec<T>> {
+  pub fn borrow_mut(&self) -> RefMut<Vec<T>> {
     let ColumnV(col) = self;
     col.borrow_mut()
   }
+
+  pub fn insert_rows(&self, ix: usize, count: usize, value: T) {
+    let ColumnV(col) = self;
+    col.borrow_mut().splice(ix..ix, std::iter::repeat(value).take(count));
+  }
+
+  // Keeps only the rows whose entry in keep is true. The mask has an entry for
+  // every row of the column.
+  pub fn retain_rows(&self, keep: &Vec<bool>) -> std::result::Result<(),MechError> {
+    let ColumnV(col) = self;
+    let mut col_brrw = col.borrow_mut();
+    if keep.len() != col_brrw.len() {
+      return Err(MechError{msg: "".to_string(), id: 9433, kind: MechErrorKind::DimensionMismatch(vec![(keep.len(),1),(col_brrw.len(),1)])});
+    }
+    let mut row = 0;
+    col_brrw.retain(|_| {
+      let kept = keep[row];
+      row += 1;
+      kept
+    });
+    Ok(())
+  }
   
 }
 
@@ -23,12 +23,14 @@ use crate::function::{
 };
 
 use hashbrown::{HashMap, HashSet};
-use std::rc::Rc;
-use std::cell::RefCell;
+use crate::sync::Rc;
+use crate::sync::RefCell;
 
 
 pub type BlockRef = Rc<RefCell<Block>>;
 
+pub const MAX_ITERATIONS: usize = 1000;
+
 pub struct Functions{
   pub functions: HashMap<u64,Box<dyn MechFunctionCompiler>>,
 }
@@ -76,6 +78,21 @@ pub struct Core {
   pub defined_tables: HashSet<(TableId,RegisterIndex,RegisterIndex)>,
   pub schedule: Schedule,
   pub dictionary: StringDictionary,
+  pub max_iterations: usize,
+  pub history: History,
+  pub machines: HashMap<u64,Box<dyn Machine>>,
+  pub machine_subscriptions: HashMap<u64,Vec<u64>>,
+  pub time: Box<dyn TimeSource>,
+  pub journal: Option<Journal>,
+  pub storage: Option<Storage>,
+  write_log: Option<WriteLog>,   // Tables written by the transaction or tick in progress, as they were before
+}
+
+// Fails to compile if anything a Core holds can't be shared between threads.
+#[cfg(feature = "sync")]
+fn _assert_core_is_send_sync() {
+  fn _assert<T: Send + Sync>() {}
+  _assert::<Core>();
 }
 
 impl Core {
@@ -156,6 +173,14 @@ impl Core {
       output: HashSet::new(),
       defined_tables: HashSet::new(),
       dictionary: dictionary,
+      max_iterations: MAX_ITERATIONS,
+      history: History::new(),
+      machines: HashMap::new(),
+      machine_subscriptions: HashMap::new(),
+      time: Box::new(SystemClock::new()),
+      journal: None,
+      storage: None,
+      write_log: None,
     }
   }
 
@@ -176,105 +201,941 @@ impl Core {
     self.input.difference(&self.defined_tables).cloned().collect()
   }
 
-  pub fn process_transaction(&mut self, txn: &Transaction) -> Result<(Vec<BlockRef>,HashSet<(TableId,RegisterIndex,RegisterIndex)>),MechError> {
+  // Describes the global tables the loaded blocks need from the host, and the
+  // ones they produce.
+  pub fn interface(&self) -> ProgramInterface {
+    let global_ids = |registers: &HashSet<(TableId,RegisterIndex,RegisterIndex)>| -> HashSet<u64> {
+      registers.iter().filter_map(|(table_id,_,_)| match table_id {
+        TableId::Global(id) => Some(*id),
+        _ => None,
+      }).collect()
+    };
+    let defined = global_ids(&self.defined_tables);
+    let inputs = global_ids(&self.input).difference(&defined).cloned().collect();
+    ProgramInterface {
+      inputs: self.describe_tables(&inputs),
+      outputs: self.describe_tables(&global_ids(&self.output)),
+    }
+  }
+
+  fn describe_tables(&self, table_ids: &HashSet<u64>) -> Vec<TableInterface> {
+    let database_brrw = self.database.borrow();
+    let mut described = HashSet::new();
+    let mut tables = vec![];
+    for table_id in table_ids {
+      let table_interface = match database_brrw.get_table_by_id(table_id) {
+        Some(table) => {
+          let table_brrw = table.borrow();
+          let name = self.get_name(table_brrw.id).or(table_brrw.name());
+          TableInterface::from_table(&table_brrw, name, &self.dictionary)
+        }
+        None => TableInterface::missing(*table_id, self.get_name(*table_id)),
+      };
+      if described.insert(table_interface.id) {
+        tables.push(table_interface);
+      }
+    }
+    tables.sort_by(|a,b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+    tables
+  }
+
+  // Transactions are all-or-nothing. If any change is rejected, every change
+  // before it is rolled back, no blocks are run, and the error says which
+  // change in the transaction was at fault. A transaction that fails after its
+  // changes are applied, while its blocks run or while it's being stored, is
+  // rolled back too, along with everything its blocks wrote. Machines are only
+  // told about a transaction once it's committed, so a machine that fails
+  // doesn't fail the transaction. Their errors are returned alongside it, with
+  // the id of each machine.
+  pub fn process_transaction(&mut self, txn: &Transaction) -> Result<(Vec<BlockRef>,HashSet<(TableId,RegisterIndex,RegisterIndex)>,Vec<(u64,MechError)>),MechError> {
+    self.commit_transaction(txn, None)
+  }
+
+  // Processes a transaction and returns what changed as a result, including
+  // what the blocks it triggered wrote. The diff is a transaction itself, so it
+  // can be forwarded to another core as is. Cells are sent as a Set, and a
+  // table that was created or reshaped is sent whole.
+  pub fn process_transaction_diff(&mut self, txn: &Transaction) -> Result<(Transaction,Vec<(u64,MechError)>),MechError> {
+    let mut diff = vec![];
+    let (_,_,machine_errors) = self.commit_transaction(txn, Some(&mut diff))?;
+    Ok((diff,machine_errors))
+  }
+
+  fn commit_transaction(&mut self, txn: &Transaction, diff: Option<&mut Transaction>) -> Result<(Vec<BlockRef>,HashSet<(TableId,RegisterIndex,RegisterIndex)>,Vec<(u64,MechError)>),MechError> {
     let mut changed_registers = HashSet::new();
     let mut block_refs = Vec::new();
-    for change in txn {
+    let mut rollback = Vec::new();
+    let mut restructured = (HashSet::new(),HashSet::new());
+    self.schedule.profiler.clear();
+    self.journal_event(JournalEvent::Transaction(txn.clone()))?;
+    self.check_kinds(txn)?;
+    // Blocks undo what they wrote from the write log, and the changes undo
+    // themselves from the rollback log. Diffs and the history need the tables
+    // the changes touch as they were before too, so those are captured up front.
+    let mut write_log = WriteLog::new();
+    if diff.is_some() || self.history.enabled() {
+      let database_brrw = self.database.borrow();
+      for change in txn {
+        write_log.capture(&database_brrw, change.table_id());
+      }
+    }
+    self.write_log = Some(write_log);
+    let result = match self.run_transaction(txn, &mut rollback, &mut restructured, &mut changed_registers, &mut block_refs) {
+      Ok(output_registers) => {
+        let mut written: HashSet<u64> = txn.iter().map(|change| change.table_id()).collect();
+        written.extend(self.write_log.iter().flat_map(|write_log| write_log.tables().iter().cloned()));
+        self.sync_storage(&written).map(|_| output_registers)
+      }
+      Err(x) => Err(x),
+    };
+    let mut output_registers = match result {
+      Ok(output_registers) => output_registers,
+      Err(mut x) => {
+        // The error that failed the transaction is the one returned. If it
+        // can't be rolled back as well, that's added to the message.
+        if let Err(rollback_error) = self.abort_transaction(rollback, &restructured) {
+          x.msg = format!("{} (rolling back the transaction failed: {:?})", x.msg, rollback_error);
+        }
+        return Err(x);
+      }
+    };
+    let before = match self.write_log.take() {
+      Some(write_log) => write_log.into_snapshots(),
+      None => vec![],
+    };
+    if let Some(diff) = diff {
+      let database_brrw = self.database.borrow();
+      diff.extend(before.iter().flat_map(|snapshot| snapshot.changes(&database_brrw)));
+    }
+    if self.history.enabled() {
+      self.history.push(HistoryEvent::Transaction(txn.clone()), before, &self.database.borrow());
+    }
+    self.compact_storage_when_due()?;
+    if self.schedule.profiler.enabled {
+      output_registers.extend(self.update_profile()?);
+    }
+    let changed_tables: HashSet<u64> = changed_registers.union(&output_registers).filter_map(|(table_id,_,_)| match table_id {
+      TableId::Global(id) => Some(*id),
+      _ => None,
+    }).collect();
+    let machine_errors = self.notify_machines(&changed_tables);
+    Ok((block_refs,changed_registers,machine_errors))
+  }
+
+  // Applies the changes of a transaction and runs the blocks they trigger until
+  // a steady state is reached. What each change overwrote is recorded in the
+  // rollback log, and the tables it removed or reshaped in restructured.
+  fn run_transaction(&mut self, txn: &Transaction, rollback: &mut Vec<Rollback>, restructured: &mut (HashSet<u64>,HashSet<u64>), changed_registers: &mut HashSet<(TableId,RegisterIndex,RegisterIndex)>, block_refs: &mut Vec<BlockRef>) -> Result<HashSet<(TableId,RegisterIndex,RegisterIndex)>,MechError> {
+    for (change_ix, change) in txn.iter().enumerate() {
+      if let Err(x) = self.apply_change(change, rollback, changed_registers) {
+        return Err(MechError{msg: format!("{:?}", change), id: 1009, kind: MechErrorKind::InvalidChange(change_ix, Box::new(x))});
+      }
+    }
+    *restructured = self.restructured_tables(txn);
+    let (removed_tables, reshaped_tables) = restructured.clone();
+    self.unload_readers(&removed_tables)?;
+    for table_id in reshaped_tables {
+      self.recompile_readers(table_id)?;
+    }
+    let changed_tables: HashSet<TableId> = changed_registers.iter().map(|(table_id,_,_)| *table_id).collect();
+    for changed_table_id in changed_tables {
+      let mut cured_block_refs = self.remove_error(changed_table_id)?;
+      block_refs.append(&mut cured_block_refs);
+    }
+    let mut output_registers = self.propagate(changed_registers)?;
+    output_registers.extend(self.step_state_machines()?);
+    Ok(output_registers)
+  }
+
+  // Puts the database back the way it was before a failed transaction. The
+  // write log undoes what the blocks wrote, which was captured after the
+  // changes were applied, so it goes first, and then the rollback log undoes
+  // the changes. Blocks that were recompiled or unloaded because a table was
+  // reshaped or removed are compiled against the restored table, which can
+  // write their outputs, so what they write is logged and undone as well.
+  fn abort_transaction(&mut self, rollback: Vec<Rollback>, restructured: &(HashSet<u64>,HashSet<u64>)) -> Result<(),MechError> {
+    {
+      let mut database_brrw = self.database.borrow_mut();
+      if let Some(write_log) = self.write_log.take() {
+        write_log.restore(&mut database_brrw);
+      }
+      database_brrw.rollback(rollback)?;
+    }
+    let (removed_tables, reshaped_tables) = restructured;
+    if removed_tables.len() + reshaped_tables.len() == 0 {
+      return Ok(());
+    }
+    self.write_log = Some(WriteLog::new());
+    let result = self.restore_readers(removed_tables, reshaped_tables);
+    if let Some(write_log) = self.write_log.take() {
+      write_log.restore(&mut self.database.borrow_mut());
+    }
+    result
+  }
+
+  fn restore_readers(&mut self, removed_tables: &HashSet<u64>, reshaped_tables: &HashSet<u64>) -> Result<(),MechError> {
+    for table_id in removed_tables {
+      self.remove_error(TableId::Global(*table_id))?;
+    }
+    for table_id in reshaped_tables {
+      self.recompile_readers(*table_id)?;
+    }
+    Ok(())
+  }
+
+  // Checks every value a transaction sets against the kind of its column before
+  // anything is applied. Tables that the transaction creates, or whose columns
+  // it changes, are left to be checked as the transaction is applied, since
+  // their columns aren't known until then.
+  fn check_kinds(&self, txn: &Transaction) -> Result<(),MechError> {
+    let database_brrw = self.database.borrow();
+    let mut reshaped = HashSet::new();
+    for (change_ix, change) in txn.iter().enumerate() {
       match change {
-        Change::Set((table_id, adds)) => {
-          match self.database.borrow().get_table_by_id(table_id) {
-            Some(table) => {
-              let table_brrw = table.borrow();
-              for (row,col,val) in adds {
-                match table_brrw.set(row, col, val.clone()) {
-                  Ok(()) => {
-                    // TODO This is inserting a {:,:} register instead of the one passed in, and that needs to be fixed.
-                    changed_registers.insert((TableId::Global(*table_id),RegisterIndex::All,RegisterIndex::All));
-                  },
-                  Err(x) => { return Err(MechError{msg: "".to_string(), id: 1000, kind: MechErrorKind::GenericError(format!("{:?}", x))});},
+        Change::Set((table_id, values)) if !reshaped.contains(table_id) => {
+          let table = match database_brrw.get_table_by_id(table_id) {
+            Some(table) => table,
+            None => continue,
+          };
+          let table_brrw = table.borrow();
+          for (_,col,value) in values {
+            let col_ix = match col {
+              TableIndex::Index(ix) if *ix > 0 => ix - 1,
+              TableIndex::Alias(alias) => match table_brrw.col_map.get_index(alias) {
+                Ok(ix) => ix,
+                Err(_) => continue,
+              },
+              TableIndex::All | TableIndex::None => 0,
+              _ => continue,
+            };
+            if let Err(x) = table_brrw.coerce(col_ix, value.clone()) {
+              return Err(MechError{msg: format!("{:?}", change), id: 1016, kind: MechErrorKind::InvalidChange(change_ix, Box::new(x))});
+            }
+          }
+        }
+        Change::Append{table_id, rows} if !reshaped.contains(table_id) => {
+          if let Some(table) = database_brrw.get_table_by_id(table_id) {
+            let table_brrw = table.borrow();
+            for row in rows {
+              for (col_ix,value) in row.iter().enumerate().take(table_brrw.cols) {
+                if let Err(x) = table_brrw.coerce(col_ix, value.clone()) {
+                  return Err(MechError{msg: format!("{:?}", change), id: 1022, kind: MechErrorKind::InvalidChange(change_ix, Box::new(x))});
                 }
               }
             }
-            None => {return Err(MechError{msg: "".to_string(), id: 1001, kind: MechErrorKind::MissingTable(TableId::Global(*table_id))});},
           }
+          reshaped.insert(*table_id);
+        }
+        Change::NewTable{table_id, ..} |
+        Change::ColumnAlias{table_id, ..} |
+        Change::RowAlias{table_id, ..} |
+        Change::ColumnKind{table_id, ..} |
+        Change::RemoveTable{table_id} |
+        Change::InsertRows{table_id, ..} |
+        Change::RemoveRows{table_id, ..} |
+        Change::Resize{table_id, ..} |
+        Change::Append{table_id, ..} => {
+          reshaped.insert(*table_id);
         }
-        Change::NewTable{table_id, rows, columns} => {
-          let table = Table::new(*table_id,rows.clone(),*columns);
-          self.database.borrow_mut().insert_table(table)?;
-        }
-        Change::ColumnAlias{table_id, column_ix, column_alias} => {
-          match self.database.borrow_mut().get_table_by_id(table_id) {
-            Some(table) => {
-              let mut table_brrw = table.borrow_mut();   
-              let rows = table_brrw.rows;
-              if *column_ix + 1 > table_brrw.cols {
-                table_brrw.resize(rows, column_ix + 1);
-              }    
-              table_brrw.set_col_alias(*column_ix,*column_alias);     
+        _ => (),
+      }
+    }
+    Ok(())
+  }
+
+  // Machines subscribe to tables by id or alias. Registering a machine with an
+  // id that's already registered replaces the old one.
+  pub fn register_machine(&mut self, machine: Box<dyn Machine>, tables: &Vec<u64>) -> Result<(),MechError> {
+    let machine_id = machine.id();
+    self.journal_event(JournalEvent::Machine(machine_id, tables.clone()))?;
+    for table_id in tables {
+      let subscribers = self.machine_subscriptions.entry(*table_id).or_insert(vec![]);
+      if !subscribers.contains(&machine_id) {
+        subscribers.push(machine_id);
+      }
+    }
+    self.machines.insert(machine_id, machine);
+    Ok(())
+  }
+
+  // Every subscribed machine is called even if an earlier one fails, and the
+  // failures are returned together.
+  fn notify_machines(&mut self, changed_tables: &HashSet<u64>) -> Vec<(u64,MechError)> {
+    let mut errors = vec![];
+    if self.machines.len() == 0 {
+      return errors;
+    }
+    let database_brrw = self.database.borrow();
+    for (table_id, machine_ids) in self.machine_subscriptions.iter() {
+      let table = match database_brrw.get_table_by_id(table_id) {
+        Some(table) if changed_tables.contains(&table.borrow().id) => table.clone(),
+        _ => continue,
+      };
+      let table_brrw = table.borrow();
+      for machine_id in machine_ids {
+        if let Some(machine) = self.machines.get_mut(machine_id) {
+          if let Err(x) = machine.on_change(&table_brrw) {
+            errors.push((*machine_id, x));
+          }
+        }
+      }
+    }
+    errors
+  }
+
+  // Undo, redo and rewind move through the history of transactions and ticks,
+  // restoring the recorded table contents without running any blocks. They
+  // return the step the core is at afterwards. The history is only kept when
+  // history.capacity is non-zero.
+  pub fn undo(&mut self) -> Result<usize,MechError> {
+    self.check_not_recording()?;
+    let from = self.history.step;
+    let step = self.history.undo(&mut self.database.borrow_mut())?;
+    self.sync_history(from, step)?;
+    Ok(step)
+  }
+
+  pub fn redo(&mut self) -> Result<usize,MechError> {
+    self.check_not_recording()?;
+    let from = self.history.step;
+    let step = self.history.redo(&mut self.database.borrow_mut())?;
+    self.sync_history(from, step)?;
+    Ok(step)
+  }
+
+  pub fn rewind_to(&mut self, step: usize) -> Result<usize,MechError> {
+    self.check_not_recording()?;
+    let from = self.history.step;
+    let step = self.history.rewind_to(step, &mut self.database.borrow_mut())?;
+    self.sync_history(from, step)?;
+    Ok(step)
+  }
+
+  // Moving through the history rewrites the tables of the steps in between,
+  // and persistent ones among them are stored as they are now.
+  fn sync_history(&mut self, from: usize, to: usize) -> Result<(),MechError> {
+    let table_ids = self.history.tables_between(from.min(to), from.max(to));
+    self.sync_storage(&table_ids)?;
+    self.compact_storage_when_due()
+  }
+
+  // The history can reach back to before a journal was started, so a replayed
+  // core can't undo the same way. Moving through it is refused while recording.
+  fn check_not_recording(&self) -> Result<(),MechError> {
+    match self.journal {
+      Some(_) => Err(MechError{msg: "".to_string(), id: 1026, kind: MechErrorKind::GenericError("History can't be used while recording a journal".to_string())}),
+      None => Ok(()),
+    }
+  }
+
+  // Blocks are saved as the transformations they were compiled from, so blocks
+  // built directly from a plan aren't included.
+  pub fn snapshot(&self) -> Result<Vec<u8>,MechError> {
+    let database_brrw = self.database.borrow();
+    let tables = database_brrw.tables.values().map(|table| table.borrow().to_changes()).collect();
+    let table_aliases = database_brrw.table_alias_to_id.iter().map(|(alias,table_id)| (*alias,*table_id)).collect();
+    let dictionary = self.dictionary.borrow().iter().map(|(id,string)| (*id,string.clone())).collect();
+    let mut blocks = vec![];
+    let mut saved = HashSet::new();
+    let unsatisfied = self.errors.values().flat_map(|block_refs| block_refs.iter());
+    for block_ref in self.blocks.values().chain(self.unsatisfied_blocks.values()).chain(unsatisfied) {
+      if !saved.insert(Rc::as_ptr(block_ref)) {
+        continue;
+      }
+      let tfms = block_ref.borrow().all_transformations();
+      if tfms.len() > 0 {
+        blocks.push(tfms);
+      }
+    }
+    let snapshot = CoreSnapshot{tables, table_aliases, dictionary, blocks, max_iterations: self.max_iterations};
+    snapshot.to_bytes()
+  }
+
+  // Rebuilds a core from a snapshot. Tables that aren't defined by a block are
+  // created first so the blocks find their inputs when they load. Once the
+  // blocks are loaded, every table is overwritten with its saved contents, so
+  // the new core picks up exactly where the old one was.
+  pub fn restore(bytes: &[u8]) -> Result<Core,MechError> {
+    let snapshot = CoreSnapshot::from_bytes(bytes)?;
+    let mut core = Core::new();
+    core.max_iterations = snapshot.max_iterations;
+    {
+      let mut dictionary_brrw = core.dictionary.borrow_mut();
+      for (id,string) in &snapshot.dictionary {
+        dictionary_brrw.insert(*id,string.clone());
+      }
+    }
+    let mut block_tables = HashSet::new();
+    for tfms in &snapshot.blocks {
+      for tfm in tfms {
+        if let Transformation::NewTable{table_id: TableId::Global(table_id), ..} = tfm {
+          block_tables.insert(*table_id);
+        }
+      }
+    }
+    for changes in &snapshot.tables {
+      if let Some(Change::NewTable{table_id, ..}) = changes.first() {
+        if !block_tables.contains(table_id) {
+          core.process_transaction(changes)?;
+        }
+      }
+    }
+    let blocks = snapshot.blocks.iter().map(|tfms| core.rebuild_block(tfms)).collect();
+    core.load_blocks(&blocks);
+    let database = core.database.clone();
+    let mut database_brrw = database.borrow_mut();
+    for (alias,table_id) in &snapshot.table_aliases {
+      database_brrw.table_alias_to_id.entry(*alias).or_insert(*table_id);
+    }
+    for changes in &snapshot.tables {
+      for change in changes {
+        match change {
+          Change::NewTable{table_id, rows, columns} => {
+            if let Some(table) = database_brrw.get_table_by_id(table_id) {
+              let mut table_brrw = table.borrow_mut();
+              if table_brrw.rows != *rows || table_brrw.cols != *columns {
+                table_brrw.resize(*rows,*columns)?;
+              }
             }
-            x => {return Err(MechError{msg: "".to_string(), id: 1002, kind: MechErrorKind::GenericError(format!("{:?}", x))});},
           }
+          Change::Set((table_id, values)) => {
+            if let Some(table) = database_brrw.get_table_by_id(table_id) {
+              let table_brrw = table.borrow();
+              for (row,col,value) in values {
+                table_brrw.set(row,col,value.clone())?;
+              }
+            }
+          }
+          _ => (),
         }
-        Change::ColumnKind{table_id, column_ix, column_kind} => {
-          match self.database.borrow_mut().get_table_by_id(table_id) {
-            Some(table) => {
-              let mut table_brrw = table.borrow_mut();   
-              let rows = table_brrw.rows;
-              if *column_ix + 1 > table_brrw.cols {
-                table_brrw.resize(rows, column_ix + 1);
-              }    
-              table_brrw.set_col_kind(*column_ix,column_kind.clone());     
+      }
+    }
+    drop(database_brrw);
+    Ok(core)
+  }
+
+  // Starts recording a journal of everything the core receives to the file at
+  // path, replacing any journal already being recorded. A core that already
+  // has tables or blocks starts the journal with a snapshot of itself, and then
+  // its disabled blocks, which snapshots don't keep. Waits that are already
+  // running start over when the snapshot is replayed.
+  pub fn record(&mut self, path: &str) -> Result<(),MechError> {
+    let mut journal = Journal::create(path)?;
+    if self.database.borrow().tables.len() > 0 || self.blocks.len() > 0 || self.errors.len() > 0 {
+      let now = self.time.now();
+      journal.record(now, JournalEvent::Snapshot(self.snapshot()?))?;
+      journal.max_iterations = self.max_iterations;
+      let mut disabled: Vec<BlockId> = self.blocks.iter().filter(|(_,block_ref)| block_ref.borrow().state == BlockState::Disabled).map(|(block_id,_)| *block_id).collect();
+      disabled.sort();
+      for block_id in disabled {
+        journal.record(now, JournalEvent::DisableBlock(block_id))?;
+      }
+    }
+    self.journal = Some(journal);
+    Ok(())
+  }
+
+  pub fn stop_recording(&mut self) {
+    self.journal = None;
+  }
+
+  // The iteration limit is a field, so it's compared against the journal's
+  // copy before each entry and recorded first if it has changed.
+  fn journal_event(&mut self, event: JournalEvent) -> Result<(),MechError> {
+    let now = self.time.now();
+    match &mut self.journal {
+      Some(journal) => {
+        if journal.max_iterations != self.max_iterations {
+          journal.record(now, JournalEvent::MaxIterations(self.max_iterations))?;
+          journal.max_iterations = self.max_iterations;
+        }
+        journal.record(now, event)
+      }
+      None => Ok(()),
+    }
+  }
+
+  // Opens a core backed by the storage directory at path, recovering the
+  // persistent tables it holds. From then on, every change to a persistent
+  // table is written to the storage when the transaction, tick or block load
+  // that made it finishes. Blocks, machines and tables that aren't persistent
+  // aren't stored, so the host loads them again after opening.
+  pub fn open(path: &str) -> Result<Core,MechError> {
+    let (mut storage, records) = Storage::open(path)?;
+    let mut core = Core::new();
+    for record in records {
+      match record {
+        StorageRecord::Persist(table_id) => {
+          storage.persistent.insert(table_id);
+        }
+        StorageRecord::Tables(tables) => {
+          let mut txn = vec![];
+          for mut changes in tables {
+            if let Some(Change::NewTable{table_id, ..}) = changes.first() {
+              core.database.borrow_mut().tables.remove(table_id);
             }
-            x => {return Err(MechError{msg: "".to_string(), id: 1003, kind: MechErrorKind::GenericError(format!("{:?}", x))});},
+            txn.append(&mut changes);
           }
+          core.process_transaction(&txn)?;
         }
       }
     }
+    storage.track(&core.database.borrow());
+    core.storage = Some(storage);
+    Ok(core)
+  }
 
-    for (changed_table_id,_,_) in &changed_registers {
-      let mut cured_block_refs = self.remove_error(*changed_table_id)?;
-      block_refs.append(&mut cured_block_refs);
+  // Marks a table as persistent, so it's kept in the core's storage. The
+  // table is named by its id or an alias, and doesn't have to exist yet.
+  pub fn persist(&mut self, table_id: u64) -> Result<(),MechError> {
+    let database_brrw = self.database.borrow();
+    let table_id = match database_brrw.get_table_by_id(&table_id) {
+      Some(table) => table.borrow().id,
+      None => table_id,
+    };
+    match &mut self.storage {
+      Some(storage) => storage.persist(table_id, &database_brrw),
+      None => Err(MechError{msg: "".to_string(), id: 1025, kind: MechErrorKind::GenericError("Core was not opened with storage".to_string())}),
     }
-    for register in &changed_registers {
-      self.step(register);
+  }
+
+  // Writes a new storage snapshot of the persistent tables and starts an
+  // empty log, which keeps recovery short.
+  pub fn compact_storage(&mut self) -> Result<(),MechError> {
+    match &mut self.storage {
+      Some(storage) => storage.compact(&self.database.borrow()),
+      None => Ok(()),
     }
-    Ok((block_refs,changed_registers))
   }
 
-  pub fn remove_error(&mut self, table_id: TableId) -> Result<Vec<BlockRef>,MechError> {
-    let mut block_refs = vec![];
-    match &self.errors.remove(&MechErrorKind::MissingTable(table_id)) {
-      Some(ref ublocks) => {
-        let mut mb = ublocks.clone();
-        block_refs.append(&mut mb);
+  fn sync_storage(&mut self, table_ids: &HashSet<u64>) -> Result<(),MechError> {
+    match &mut self.storage {
+      Some(storage) => storage.sync(&self.database.borrow(), table_ids),
+      None => Ok(()),
+    }
+  }
+
+  // Outside of transactions and ticks nothing else logs what's written, so
+  // while there's storage a log of just the tables written is kept, and those
+  // are synced when it's finished.
+  fn start_storage_log(&mut self) {
+    self.write_log = self.storage.as_ref().map(|_| WriteLog::tables_only());
+  }
+
+  fn finish_storage_log(&mut self) -> Result<(),MechError> {
+    if let Some(write_log) = self.write_log.take() {
+      self.sync_storage(write_log.tables())?;
+    }
+    self.compact_storage_when_due()
+  }
+
+  // Compaction only rewrites what's already in the log, so it's done once the
+  // transaction that filled the log has been committed.
+  fn compact_storage_when_due(&mut self) -> Result<(),MechError> {
+    match &mut self.storage {
+      Some(storage) if storage.compaction_due() => storage.compact(&self.database.borrow()),
+      _ => Ok(()),
+    }
+  }
+
+  // Rebuilds a core from a recorded journal. Blocks built directly from a plan
+  // aren't recorded, and neither are user functions, so the replayed core won't
+  // have them. Machines are recorded by id and the tables they watch, but can't
+  // be rebuilt from that. They only read tables, so the replayed tables come
+  // out the same without them.
+  pub fn replay(path: &str) -> Result<Core,MechError> {
+    Core::replay_to(path, u64::MAX)
+  }
+
+  // Replays the entries of a journal that come before step. Entries are applied
+  // the same way they were when recorded, so a transaction that was rejected
+  // then is rejected again, and the replay carries on past it.
+  pub fn replay_to(path: &str, step: u64) -> Result<Core,MechError> {
+    let entries = Journal::read(path)?;
+    let mut core = Core::new();
+    let clock = ManualClock::new();
+    core.set_time_source(Box::new(clock.clone()));
+    for entry in entries.iter().take_while(|entry| entry.step < step) {
+      clock.set(entry.time);
+      match &entry.event {
+        JournalEvent::Transaction(txn) => {
+          let _ = core.process_transaction(txn);
+        }
+        JournalEvent::Blocks(blocks) => {
+          let blocks = blocks.iter().map(|tfms| core.rebuild_block(tfms)).collect();
+          core.load_blocks(&blocks);
+        }
+        JournalEvent::Tick => {
+          let _ = core.tick();
+        }
+        JournalEvent::RemoveBlock(block_id) => {
+          let _ = core.remove_block(*block_id);
+        }
+        JournalEvent::DisableBlock(block_id) => {
+          let _ = core.disable_block(*block_id);
+        }
+        JournalEvent::EnableBlock(block_id) => {
+          let _ = core.enable_block(*block_id);
+        }
+        JournalEvent::MaxIterations(max_iterations) => {
+          core.max_iterations = *max_iterations;
+        }
+        JournalEvent::Machine(..) => (),
+        JournalEvent::Snapshot(bytes) => {
+          core = Core::restore(bytes)?;
+          core.set_time_source(Box::new(clock.clone()));
+        }
+        // The table is built by a transaction on a core of its own, so no
+        // blocks run, the same as when it was inserted.
+        JournalEvent::InsertTable(changes) => {
+          let mut scratch = Core::new();
+          if scratch.process_transaction(changes).is_ok() {
+            if let Some(Change::NewTable{table_id, ..}) = changes.first() {
+              if let Some(table) = scratch.database.borrow_mut().tables.remove(table_id) {
+                let _ = core.database.borrow_mut().insert_table_ref(table);
+              }
+            }
+          }
+        }
       }
-      None => (),
     }
-    match self.errors.remove(&MechErrorKind::PendingTable(table_id)) {
-      Some(ref ublocks) => {
-        let mut mb = ublocks.clone();
-        block_refs.append(&mut mb);
+    Ok(core)
+  }
+
+  // Runs blocks in rounds until a steady state is reached, which is when a round
+  // finishes without changing anything that feeds back into blocks that already
+  // ran. If that takes more than max_iterations rounds, an error is returned.
+  // The database is left as the last round wrote it. Returns the output
+  // registers of every block that ran.
+  fn propagate(&mut self, registers: &HashSet<(TableId,RegisterIndex,RegisterIndex)>) -> Result<HashSet<(TableId,RegisterIndex,RegisterIndex)>,MechError> {
+    let mut registers = registers.clone();
+    let mut output_registers = HashSet::new();
+    let mut iterations = 0;
+    while registers.len() > 0 {
+      if iterations == self.max_iterations {
+        let tables = registers.iter().map(|(table_id,_,_)| match self.get_name(*table_id.unwrap()) {
+          Some(name) => name,
+          None => humanize(table_id.unwrap()),
+        }).collect::<Vec<String>>().join(", ");
+        return Err(MechError{msg: tables, id: 1010, kind: MechErrorKind::IterationLimitReached(self.max_iterations)});
       }
-      None => (),
+      for register in &registers {
+        for trigger in self.schedule.triggered_registers(register) {
+          output_registers.extend(self.schedule.trigger_output(&trigger).iter().cloned());
+        }
+      }
+      let order = self.schedule.solve_order(&registers);
+      if let Some(write_log) = &mut self.write_log {
+        let database_brrw = self.database.borrow();
+        for block_ref in &order {
+          write_log.capture_outputs(&database_brrw, &block_ref.borrow());
+        }
+      }
+      registers = self.schedule.run_order(&order)?;
+      iterations += 1;
     }
-    self.load_block_refs(block_refs.clone());
-    self.schedule_blocks();
-    let mut graph_output = vec![];
-    match self.schedule.trigger_to_output.get(&(table_id,RegisterIndex::All,RegisterIndex::All)) {
-      Some(output) => {
-        for (table_id,_,_) in output {
-          graph_output.push(table_id.clone());
+    Ok(output_registers)
+  }
+
+  fn apply_change(&mut self, change: &Change, rollback: &mut Vec<Rollback>, changed_registers: &mut HashSet<(TableId,RegisterIndex,RegisterIndex)>) -> Result<(),MechError> {
+    match change {
+      Change::Set((table_id, adds)) => {
+        match self.database.borrow().get_table_by_id(table_id) {
+          Some(table) => {
+            let table_brrw = table.borrow();
+            for (row,col,val) in adds {
+              let old_value = table_brrw.get(row, col)?;
+              match table_brrw.set(row, col, val.clone()) {
+                Ok(()) => {
+                  rollback.push(Rollback::Set{table: table.clone(), row: row.clone(), col: col.clone(), value: old_value});
+                  changed_registers.insert((TableId::Global(table_brrw.id),table_brrw.row_register(row),table_brrw.column_register(col)));
+                },
+                Err(x @ MechError{kind: MechErrorKind::ColumnKindMismatch(..), ..}) => {return Err(x);}
+                Err(x) => { return Err(MechError{msg: "".to_string(), id: 1000, kind: MechErrorKind::GenericError(format!("{:?}", x))});},
+              }
+            }
+          }
+          None => {return Err(MechError{msg: "".to_string(), id: 1001, kind: MechErrorKind::MissingTable(TableId::Global(*table_id))});},
         }
       }
-      None => (),
+      Change::NewTable{table_id, rows, columns} => {
+        let table = Table::new(*table_id,rows.clone(),*columns);
+        self.database.borrow_mut().insert_table(table)?;
+        rollback.push(Rollback::NewTable(*table_id));
+      }
+      Change::ColumnAlias{table_id, column_ix, column_alias} => {
+        let database_brrw = self.database.borrow();
+        match database_brrw.get_table_by_id(table_id) {
+          Some(table) => {
+            rollback.push(Rollback::reshape(&database_brrw, table, table.borrow().rows, column_ix + 1));
+            let mut table_brrw = table.borrow_mut();   
+            let rows = table_brrw.rows;
+            if *column_ix + 1 > table_brrw.cols {
+              table_brrw.resize(rows, column_ix + 1)?;
+            }    
+            table_brrw.set_col_alias(*column_ix,*column_alias);     
+          }
+          x => {return Err(MechError{msg: "".to_string(), id: 1002, kind: MechErrorKind::GenericError(format!("{:?}", x))});},
+        }
+      }
+      // Naming a row counts as a change to it, so blocks that read the row by
+      // its new name are solved.
+      Change::RowAlias{table_id, row_ix, row_alias} => {
+        let database_brrw = self.database.borrow();
+        match database_brrw.get_table_by_id(table_id) {
+          Some(table) => {
+            rollback.push(Rollback::reshape(&database_brrw, table, row_ix + 1, table.borrow().cols));
+            let mut table_brrw = table.borrow_mut();
+            let cols = table_brrw.cols;
+            if *row_ix + 1 > table_brrw.rows {
+              table_brrw.resize(row_ix + 1, cols)?;
+            }
+            table_brrw.set_row_alias(*row_ix,*row_alias)?;
+            changed_registers.insert((TableId::Global(table_brrw.id),RegisterIndex::Alias(*row_alias),RegisterIndex::All));
+          }
+          None => {return Err(MechError{msg: "".to_string(), id: 1017, kind: MechErrorKind::MissingTable(TableId::Global(*table_id))});},
+        }
+      }
+      Change::ColumnKind{table_id, column_ix, column_kind} => {
+        let database_brrw = self.database.borrow();
+        match database_brrw.get_table_by_id(table_id) {
+          Some(table) => {
+            rollback.push(Rollback::reshape(&database_brrw, table, table.borrow().rows, column_ix + 1));
+            let mut table_brrw = table.borrow_mut();   
+            let rows = table_brrw.rows;
+            if *column_ix + 1 > table_brrw.cols {
+              table_brrw.resize(rows, column_ix + 1)?;
+            }    
+            table_brrw.set_col_kind(*column_ix,column_kind.clone());     
+          }
+          x => {return Err(MechError{msg: "".to_string(), id: 1003, kind: MechErrorKind::GenericError(format!("{:?}", x))});},
+        }
+      }
+      // Aliases of a removed table are kept, so they name the table again if
+      // it's created anew.
+      Change::RemoveTable{table_id} => {
+        let mut database_brrw = self.database.borrow_mut();
+        let id = match database_brrw.get_table_by_id(table_id) {
+          Some(table) => table.borrow().id,
+          None => {return Err(MechError{msg: "".to_string(), id: 1018, kind: MechErrorKind::MissingTable(TableId::Global(*table_id))});},
+        };
+        rollback.push(Rollback::Table(TableSnapshot::capture(&database_brrw, id)));
+        rollback.push(Rollback::DynamicTables(database_brrw.dynamic_tables.clone()));
+        database_brrw.tables.remove(&id);
+        database_brrw.dynamic_tables.retain(|(dynamic_table_id,_,_)| *dynamic_table_id != TableId::Global(id));
+      }
+      Change::InsertRows{table_id, row_ix, rows} => {
+        let database_brrw = self.database.borrow();
+        match database_brrw.get_table_by_id(table_id) {
+          Some(table) => {
+            let id = table.borrow().id;
+            rollback.push(Rollback::Table(TableSnapshot::capture(&database_brrw, id)));
+            table.borrow_mut().insert_rows(*row_ix,*rows)?;
+            changed_registers.insert((TableId::Global(id),RegisterIndex::All,RegisterIndex::All));
+          }
+          None => {return Err(MechError{msg: "".to_string(), id: 1019, kind: MechErrorKind::MissingTable(TableId::Global(*table_id))});},
+        }
+      }
+      Change::RemoveRows{table_id, rows} => {
+        let database_brrw = self.database.borrow();
+        match database_brrw.get_table_by_id(table_id) {
+          Some(table) => {
+            let id = table.borrow().id;
+            rollback.push(Rollback::Table(TableSnapshot::capture(&database_brrw, id)));
+            let mut table_brrw = table.borrow_mut();
+            let mask = rows.to_mask(table_brrw.rows)?;
+            table_brrw.remove_rows(&mask)?;
+            changed_registers.insert((TableId::Global(id),RegisterIndex::All,RegisterIndex::All));
+          }
+          None => {return Err(MechError{msg: "".to_string(), id: 1020, kind: MechErrorKind::MissingTable(TableId::Global(*table_id))});},
+        }
+      }
+      Change::Resize{table_id, rows, columns} => {
+        let database_brrw = self.database.borrow();
+        match database_brrw.get_table_by_id(table_id) {
+          Some(table) => {
+            let id = table.borrow().id;
+            rollback.push(Rollback::Table(TableSnapshot::capture(&database_brrw, id)));
+            table.borrow_mut().resize(*rows,*columns)?;
+            changed_registers.insert((TableId::Global(id),RegisterIndex::All,RegisterIndex::All));
+          }
+          None => {return Err(MechError{msg: "".to_string(), id: 1021, kind: MechErrorKind::MissingTable(TableId::Global(*table_id))});},
+        }
+      }
+      Change::Append{table_id, rows} => {
+        let database_brrw = self.database.borrow();
+        match database_brrw.get_table_by_id(table_id) {
+          Some(table) => {
+            let id = table.borrow().id;
+            rollback.push(Rollback::Table(TableSnapshot::capture(&database_brrw, id)));
+            match table.borrow_mut().append_rows(rows) {
+              Ok(()) => (),
+              Err(x @ MechError{kind: MechErrorKind::ColumnKindMismatch(..), ..}) => {return Err(x);}
+              Err(x) => {return Err(MechError{msg: "".to_string(), id: 1023, kind: MechErrorKind::GenericError(format!("{:?}", x))});},
+            }
+            changed_registers.insert((TableId::Global(id),RegisterIndex::All,RegisterIndex::All));
+          }
+          None => {return Err(MechError{msg: "".to_string(), id: 1024, kind: MechErrorKind::MissingTable(TableId::Global(*table_id))});},
+        }
+      }
+    }
+    Ok(())
+  }
+
+  // The tables a transaction removed, and the tables whose rows it inserted or
+  // removed or whose shape it changed. A table that's removed and then created
+  // again in the same transaction counts as reshaped.
+  fn restructured_tables(&self, txn: &Transaction) -> (HashSet<u64>,HashSet<u64>) {
+    let database_brrw = self.database.borrow();
+    let mut removed_tables = HashSet::new();
+    let mut reshaped_tables = HashSet::new();
+    for change in txn {
+      match change {
+        Change::RemoveTable{table_id} => {
+          let id = match database_brrw.table_alias_to_id.get(table_id) {
+            Some(alias_table_id) => *alias_table_id.unwrap(),
+            None => *table_id,
+          };
+          match database_brrw.get_table_by_id(&id) {
+            Some(_) => reshaped_tables.insert(id),
+            None => removed_tables.insert(id),
+          };
+        }
+        Change::InsertRows{table_id, ..} |
+        Change::RemoveRows{table_id, ..} |
+        Change::Resize{table_id, ..} |
+        Change::Append{table_id, ..} => {
+          if let Some(table) = database_brrw.get_table_by_id(table_id) {
+            reshaped_tables.insert(table.borrow().id);
+          }
+        }
+        _ => (),
+      }
+    }
+    (removed_tables, reshaped_tables)
+  }
+
+  // Blocks that read a removed table are unloaded, together with any tables
+  // that only they defined, and loaded again from their transformations. They
+  // then wait for a table with the same id to be created.
+  fn unload_readers(&mut self, table_ids: &HashSet<u64>) -> Result<(),MechError> {
+    if table_ids.len() == 0 {
+      return Ok(());
+    }
+    let readers: Vec<BlockId> = self.blocks.iter().filter(|(_,block_ref)| {
+      block_ref.borrow().input.iter().any(|(table_id,_,_)| match table_id {
+        TableId::Global(id) => table_ids.contains(id),
+        _ => false,
+      })
+    }).map(|(block_id,_)| *block_id).collect();
+    for block_id in readers {
+      self.reload_block(block_id)?;
+    }
+    Ok(())
+  }
+
+  // Blocks are compiled against the shape of the tables they read, so the blocks
+  // reached from a reshaped table are recompiled if a table they read has
+  // changed shape. They're taken in the order they're solved, so a block's
+  // inputs have been resized by the blocks upstream of it by the time it's
+  // checked. A block that can't be compiled against the new shape (because
+  // the table is now empty, say) is loaded again from its transformations,
+  // and waits until it can be.
+  fn recompile_readers(&mut self, table_id: u64) -> Result<(),MechError> {
+    let register = (TableId::Global(table_id),RegisterIndex::All,RegisterIndex::All);
+    let mut order: Vec<BlockRef> = vec![];
+    for trigger in self.schedule.triggered_registers(&register) {
+      order.extend(self.schedule.trigger_order(&trigger).iter().cloned());
+    }
+    for block_ref in self.blocks.values() {
+      if block_ref.borrow().input.iter().any(|(input_table_id,_,_)| *input_table_id == register.0) {
+        order.push(block_ref.clone());
+      }
+    }
+    let mut recompiled = HashSet::new();
+    let mut failed = vec![];
+    for block_ref in order {
+      if !recompiled.insert(Rc::as_ptr(&block_ref)) {
+        continue;
+      }
+      let mut block_brrw = block_ref.borrow_mut();
+      if !block_brrw.inputs_reshaped() {
+        continue;
+      }
+      if let Some(write_log) = &mut self.write_log {
+        write_log.capture_outputs(&self.database.borrow(), &block_brrw);
+      }
+      if let Err(_) = block_brrw.recompile() {
+        failed.push(block_brrw.id);
+      }
     }
-    for table_id in graph_output {
-      self.remove_error(table_id);
+    for block_id in failed {
+      self.reload_block(block_id)?;
+    }
+    Ok(())
+  }
+
+  fn reload_block(&mut self, block_id: BlockId) -> Result<(),MechError> {
+    let tfms = match self.blocks.get(&block_id) {
+      Some(block_ref) => block_ref.borrow().all_transformations(),
+      None => {return Ok(());}
+    };
+    self.unload_block(block_id)?;
+    let block = self.rebuild_block(&tfms);
+    self.load_block(Rc::new(RefCell::new(block)));
+    Ok(())
+  }
+
+  pub fn remove_error(&mut self, table_id: TableId) -> Result<Vec<BlockRef>,MechError> {
+    let mut block_refs = vec![];
+    let mut visited = HashSet::new();
+    let mut tables = vec![table_id];
+    // Tables downstream of a cured table may be cured as well. Each table is
+    // only visited once, so blocks that feed back into each other terminate.
+    while let Some(table_id) = tables.pop() {
+      if !visited.insert(table_id) {
+        continue;
+      }
+      let mut cured_block_refs = vec![];
+      match &self.errors.remove(&MechErrorKind::MissingTable(table_id)) {
+        Some(ref ublocks) => {
+          let mut mb = ublocks.clone();
+          cured_block_refs.append(&mut mb);
+        }
+        None => (),
+      }
+      match self.errors.remove(&MechErrorKind::PendingTable(table_id)) {
+        Some(ref ublocks) => {
+          let mut mb = ublocks.clone();
+          cured_block_refs.append(&mut mb);
+        }
+        None => (),
+      }
+      // Blocks waiting on a row alias are tried again whenever their table
+      // changes, since the change may have named the row.
+      let row_alias_errors: Vec<MechErrorKind> = self.errors.keys().filter(|kind| match kind {
+        MechErrorKind::MissingRowAlias(alias_table_id,_) => *alias_table_id == table_id,
+        _ => false,
+      }).cloned().collect();
+      for kind in row_alias_errors {
+        if let Some(mut ublocks) = self.errors.remove(&kind) {
+          cured_block_refs.append(&mut ublocks);
+        }
+      }
+      self.load_block_refs(cured_block_refs.clone());
+      self.schedule_blocks();
+      block_refs.append(&mut cured_block_refs);
+      if let Some(triggers) = self.schedule.table_to_triggers.get(&table_id).cloned() {
+        for trigger in triggers {
+          tables.extend(self.schedule.trigger_output(&trigger).iter().map(|(output_table_id,_,_)| *output_table_id));
+        }
+      }
     }
     Ok(block_refs)
   }
 
   pub fn insert_table(&mut self, table: Table) -> Result<Rc<RefCell<Table>>,MechError> {
+    self.journal_event(JournalEvent::InsertTable(table.to_changes()))?;
     self.database.borrow_mut().insert_table(table)
   }
 
@@ -351,6 +1212,377 @@ impl Core {
     Ok(())
   }
 
+  // Builds a new, unloaded block from a list of transformations.
+  fn rebuild_block(&self, tfms: &Vec<Transformation>) -> Block {
+    let mut block = Block::new();
+    block.functions = Some(self.functions.clone());
+    block.user_functions = Some(self.user_functions.clone());
+    block.global_database = self.database.clone();
+    for tfm in tfms {
+      block.add_tfm(tfm.clone());
+    }
+    block
+  }
+
+  // Removes a loaded block. Tables it defined are dropped unless another block
+  // defines them as well. Blocks that read a dropped table are removed in turn
+  // and loaded again from their transformations, so they wait for the table to
+  // be defined again, for instance by a replacement block. A block that's still
+  // waiting to be satisfied hasn't defined anything, so it's just forgotten.
+  pub fn remove_block(&mut self, block_id: BlockId) -> Result<(),MechError> {
+    self.journal_event(JournalEvent::RemoveBlock(block_id))?;
+    self.start_storage_log();
+    self.unload_block(block_id)?;
+    self.finish_storage_log()
+  }
+
+  // Blocks are also unloaded while a transaction is applied, when they need to
+  // be reloaded, and those removals aren't journaled since the transaction is.
+  fn unload_block(&mut self, block_id: BlockId) -> Result<(),MechError> {
+    if !self.blocks.contains_key(&block_id) {
+      if self.remove_unsatisfied_block(block_id) {
+        self.rebuild_registers();
+        return Ok(());
+      }
+      return Err(MechError{msg: humanize(&block_id), id: 1013, kind: MechErrorKind::MissingBlock(block_id)});
+    }
+    let mut removed_blocks = vec![];
+    let mut dropped_tables = HashSet::new();
+    let mut block_ids = vec![block_id];
+    while let Some(block_id) = block_ids.pop() {
+      let block_ref = match self.blocks.remove(&block_id) {
+        Some(block_ref) => block_ref,
+        None => continue,
+      };
+      self.schedule.remove_block(block_id);
+      self.rebuild_registers();
+      let mut newly_dropped = vec![];
+      for register in block_ref.borrow().defined_tables.iter() {
+        if let (TableId::Global(table_id),_,_) = register {
+          if !self.defined_tables.contains(register) && dropped_tables.insert(*table_id) {
+            self.database.borrow_mut().tables.remove(table_id);
+            newly_dropped.push(*table_id);
+          }
+        }
+      }
+      for (dependent_id,dependent) in self.blocks.iter() {
+        let reads_dropped_table = dependent.borrow().input.iter().any(|(table_id,_,_)| match table_id {
+          TableId::Global(table_id) => newly_dropped.contains(table_id),
+          _ => false,
+        });
+        if reads_dropped_table {
+          block_ids.push(*dependent_id);
+        }
+      }
+      removed_blocks.push(block_ref);
+    }
+    for block_ref in removed_blocks.iter().skip(1) {
+      let block = self.rebuild_block(&block_ref.borrow().all_transformations());
+      self.load_block(Rc::new(RefCell::new(block)));
+    }
+    self.rebuild_registers();
+    self.schedule_blocks()?;
+    Ok(())
+  }
+
+  // Drops a block from the errors it's waiting on and from the schedule's
+  // unscheduled blocks. Returns whether the block was waiting at all.
+  fn remove_unsatisfied_block(&mut self, block_id: BlockId) -> bool {
+    let found = self.remove_waiting_blocks(|block_ref| block_ref.borrow().id == block_id);
+    self.schedule.remove_block(block_id);
+    found
+  }
+
+  fn remove_waiting_blocks(&mut self, is_block: impl Fn(&BlockRef) -> bool) -> bool {
+    let mut found = false;
+    for block_refs in self.errors.values_mut() {
+      let waiting = block_refs.len();
+      block_refs.retain(|block_ref| !is_block(block_ref));
+      found |= block_refs.len() != waiting;
+    }
+    self.errors.retain(|_,block_refs| block_refs.len() > 0);
+    for block_refs in self.full_errors.values_mut() {
+      block_refs.retain(|block_ref| !is_block(block_ref));
+    }
+    self.full_errors.retain(|_,block_refs| block_refs.len() > 0);
+    self.unsatisfied_blocks.retain(|_,block_ref| !is_block(block_ref));
+    found
+  }
+
+  fn waiting_block(&self, block_id: BlockId) -> Option<BlockRef> {
+    self.errors.values().flat_map(|block_refs| block_refs.iter()).find(|block_ref| block_ref.borrow().id == block_id).cloned()
+  }
+
+  // Swaps a block, loaded or waiting to be satisfied, for a new one. Blocks
+  // that read tables defined by the old block pick up the new block's tables.
+  pub fn replace_block(&mut self, block_id: BlockId, block: Block) -> Result<(Vec<BlockId>,Vec<MechError>),MechError> {
+    self.remove_block(block_id)?;
+    Ok(self.load_blocks(&vec![block]))
+  }
+
+  // A disabled block stays loaded and scheduled, but isn't solved until it's
+  // enabled again. A block still waiting for its inputs isn't loaded when they
+  // turn up until it's enabled.
+  pub fn disable_block(&mut self, block_id: BlockId) -> Result<(),MechError> {
+    self.journal_event(JournalEvent::DisableBlock(block_id))?;
+    match self.blocks.get(&block_id).cloned().or_else(|| self.waiting_block(block_id)) {
+      Some(block_ref) => {
+        block_ref.borrow_mut().state = BlockState::Disabled;
+        Ok(())
+      }
+      None => Err(MechError{msg: humanize(&block_id), id: 1014, kind: MechErrorKind::MissingBlock(block_id)}),
+    }
+  }
+
+  // Enabling a block solves it straight away, so its output catches up with
+  // anything that changed while it was disabled.
+  pub fn enable_block(&mut self, block_id: BlockId) -> Result<(),MechError> {
+    self.journal_event(JournalEvent::EnableBlock(block_id))?;
+    self.start_storage_log();
+    let output = match (self.blocks.get(&block_id).cloned(), self.waiting_block(block_id)) {
+      (Some(block_ref),_) => {
+        let mut block_brrw = block_ref.borrow_mut();
+        if block_brrw.state != BlockState::Disabled {
+          return Ok(());
+        }
+        block_brrw.state = BlockState::Ready;
+        if block_brrw.is_suspended() {
+          return Ok(());
+        }
+        self.schedule.profiler.clear();
+        if let Some(write_log) = &mut self.write_log {
+          write_log.capture_outputs(&self.database.borrow(), &block_brrw);
+        }
+        self.schedule.profiler.solve(&mut block_brrw)?;
+        block_brrw.output.clone()
+      }
+      // A disabled block isn't loaded when its inputs turn up, so it's tried
+      // again now. If they're still missing it goes back to waiting.
+      (None,Some(block_ref)) => {
+        if block_ref.borrow().state != BlockState::Disabled {
+          return Ok(());
+        }
+        block_ref.borrow_mut().state = BlockState::Unsatisfied;
+        self.remove_waiting_blocks(|waiting| Rc::ptr_eq(waiting, &block_ref));
+        self.schedule.profiler.clear();
+        let (_,_,output) = self.load_block(block_ref);
+        self.schedule_blocks()?;
+        output
+      }
+      (None,None) => {return Err(MechError{msg: humanize(&block_id), id: 1015, kind: MechErrorKind::MissingBlock(block_id)});}
+    };
+    self.propagate(&output)?;
+    self.finish_storage_log()?;
+    if self.schedule.profiler.enabled {
+      self.update_profile()?;
+    }
+    Ok(())
+  }
+
+  // Replaces the clock that waits are measured against. Timers that are
+  // already running keep their deadlines, so this is best done before any
+  // blocks are loaded.
+  pub fn set_time_source(&mut self, time: Box<dyn TimeSource>) {
+    self.time = time;
+  }
+
+  // Advances the core to the time source's current time. Blocks whose waits
+  // have elapsed are solved for the first time, and blocks that repeat until a
+  // condition holds are solved again. Their output is then propagated, and the
+  // registers that changed are returned, along with the errors of any machines
+  // that failed to handle them.
+  pub fn tick(&mut self) -> Result<(HashSet<(TableId,RegisterIndex,RegisterIndex)>,Vec<(u64,MechError)>),MechError> {
+    self.journal_event(JournalEvent::Tick)?;
+    self.schedule.profiler.clear();
+    if self.history.enabled() {
+      self.write_log = Some(WriteLog::new());
+    } else {
+      self.start_storage_log();
+    }
+    let result = self.run_tick();
+    let write_log = self.write_log.take();
+    let mut output_registers = result?;
+    if let Some(write_log) = write_log {
+      self.sync_storage(write_log.tables())?;
+      // Ticks that didn't solve anything aren't worth a step of their own.
+      if self.history.enabled() && output_registers.len() > 0 {
+        self.history.push(HistoryEvent::Tick, write_log.into_snapshots(), &self.database.borrow());
+      }
+    }
+    self.compact_storage_when_due()?;
+    if self.schedule.profiler.enabled {
+      output_registers.extend(self.update_profile()?);
+    }
+    let changed_tables: HashSet<u64> = output_registers.iter().filter_map(|(table_id,_,_)| match table_id {
+      TableId::Global(id) => Some(*id),
+      _ => None,
+    }).collect();
+    let machine_errors = self.notify_machines(&changed_tables);
+    Ok((output_registers,machine_errors))
+  }
+
+  fn run_tick(&mut self) -> Result<HashSet<(TableId,RegisterIndex,RegisterIndex)>,MechError> {
+    let now = self.time.now();
+    let mut block_ids: Vec<BlockId> = self.blocks.keys().cloned().collect();
+    block_ids.sort();
+    let mut registers = HashSet::new();
+    for block_id in block_ids {
+      let mut block_brrw = self.blocks[&block_id].borrow_mut();
+      if block_brrw.temporal.len() == 0 || block_brrw.state != BlockState::Ready {
+        continue;
+      }
+      let released = block_brrw.release_waits(now);
+      if (released || block_brrw.repeats()) && !block_brrw.is_suspended() {
+        if let Some(write_log) = &mut self.write_log {
+          write_log.capture_outputs(&self.database.borrow(), &block_brrw);
+        }
+        self.schedule.profiler.solve(&mut block_brrw)?;
+        registers.extend(block_brrw.output.iter().cloned());
+      }
+    }
+    let mut output_registers = self.propagate(&registers)?;
+    output_registers.extend(registers);
+    output_registers.extend(self.step_state_machines()?);
+    Ok(output_registers)
+  }
+
+  // The state of every block, loaded or waiting to be satisfied.
+  pub fn block_states(&self) -> Vec<BlockStatus> {
+    let unsatisfied = self.errors.values().flat_map(|block_refs| block_refs.iter());
+    self.blocks.values().chain(unsatisfied).map(|block_ref| {
+      let block_brrw = block_ref.borrow();
+      let (error,unsatisfied_transformation) = match &block_brrw.unsatisfied_transformation {
+        Some((error,tfm)) => (Some(error.clone()),Some(tfm.clone())),
+        None => (block_brrw.last_error.clone(),None),
+      };
+      BlockStatus{id: block_brrw.id, state: block_brrw.state.clone(), unsatisfied_transformation, error}
+    }).collect()
+  }
+
+  // Turns on the profiler and adds the #mech/profile table, which is rewritten
+  // with the profiler's numbers at the end of every transaction and tick.
+  pub fn enable_profiler(&mut self) -> Result<(),MechError> {
+    if self.database.borrow().get_table_by_id(&*MECH_PROFILE).is_none() {
+      let columns = [
+        (*MECH_PROFILE_BLOCK,"block",ValueKind::U64),
+        (*MECH_PROFILE_STEP,"step",ValueKind::U64),
+        (*MECH_PROFILE_KERNEL,"kernel",ValueKind::String),
+        (*MECH_PROFILE_CALLS,"calls",ValueKind::U64),
+        (*MECH_PROFILE_TOTAL,"total",ValueKind::F32),
+        (*MECH_PROFILE_MAX,"max",ValueKind::F32),
+      ];
+      let mut txn = vec![Change::NewTable{table_id: *MECH_PROFILE, rows: 0, columns: columns.len()}];
+      {
+        let mut dictionary = self.dictionary.borrow_mut();
+        dictionary.insert(*MECH_PROFILE,MechString::from_str("mech/profile"));
+        for (column_ix,(column_alias,name,column_kind)) in columns.iter().enumerate() {
+          dictionary.insert(*column_alias,MechString::from_str(name));
+          txn.push(Change::ColumnAlias{table_id: *MECH_PROFILE, column_ix, column_alias: *column_alias});
+          txn.push(Change::ColumnKind{table_id: *MECH_PROFILE, column_ix, column_kind: column_kind.clone()});
+        }
+      }
+      self.process_transaction(&txn)?;
+    }
+    self.schedule.profiler.enabled = true;
+    Ok(())
+  }
+
+  // The #mech/profile table is left with the numbers from the last transaction
+  // that was profiled.
+  pub fn disable_profiler(&mut self) {
+    self.schedule.profiler.enabled = false;
+    self.schedule.profiler.clear();
+  }
+
+  pub fn profile(&self) -> &Profiler {
+    &self.schedule.profiler
+  }
+
+  // Takes at most one transition in every state machine, in order of their
+  // table ids, then propagates the new states and the output of the entry
+  // actions that ran.
+  fn step_state_machines(&mut self) -> Result<HashSet<(TableId,RegisterIndex,RegisterIndex)>,MechError> {
+    if self.schedule.state_machines.len() == 0 {
+      return Ok(HashSet::new());
+    }
+    let mut table_ids: Vec<u64> = self.schedule.state_machines.keys().cloned().collect();
+    table_ids.sort();
+    let mut registers = HashSet::new();
+    for table_id in table_ids {
+      let state = match self.schedule.state_machines[&table_id].next_state()? {
+        Some(state) => state,
+        None => continue,
+      };
+      if let Some(write_log) = &mut self.write_log {
+        let database_brrw = self.database.borrow();
+        write_log.capture(&database_brrw, table_id);
+        for block_ref in self.schedule.state_machines[&table_id].entry_actions.get(&state).into_iter().flatten() {
+          write_log.capture_outputs(&database_brrw, &block_ref.borrow());
+        }
+      }
+      registers.extend(self.schedule.enter_state(table_id, state)?);
+    }
+    let mut output_registers = self.propagate(&registers)?;
+    output_registers.extend(registers);
+    Ok(output_registers)
+  }
+
+  // Blocks that depend on #mech/profile are run once it has been written.
+  // Their own timings are recorded in the profiler, but the table has already
+  // been written by then, so they don't show up in it.
+  fn update_profile(&mut self) -> Result<HashSet<(TableId,RegisterIndex,RegisterIndex)>,MechError> {
+    let table = match self.database.borrow().get_table_by_id(&*MECH_PROFILE) {
+      Some(table) => table.clone(),
+      None => {return Ok(HashSet::new());}
+    };
+    let mut rows = vec![];
+    for profile in self.schedule.profiler.slowest_blocks() {
+      let kernels = match self.blocks.get(&profile.block_id) {
+        Some(block_ref) => block_ref.borrow().plan.kernel_names(),
+        None => vec![],
+      };
+      rows.push((profile.block_id,0,"".to_string(),profile.block.clone()));
+      for (ix,step) in profile.steps.iter().enumerate() {
+        rows.push((profile.block_id,ix+1,kernels.get(ix).cloned().unwrap_or_default(),step.clone()));
+      }
+    }
+    {
+      let mut table_brrw = table.borrow_mut();
+      let cols = table_brrw.cols;
+      table_brrw.resize(rows.len(),cols)?;
+      for (row,(block_id,step,kernel,entry)) in rows.into_iter().enumerate() {
+        table_brrw.set_raw(row,0,Value::U64(U64::new(block_id)))?;
+        table_brrw.set_raw(row,1,Value::U64(U64::new(step as u64)))?;
+        table_brrw.set_raw(row,2,Value::String(MechString::from_string(kernel)))?;
+        table_brrw.set_raw(row,3,Value::U64(U64::new(entry.calls as u64)))?;
+        table_brrw.set_raw(row,4,Value::F32(F32::new(entry.total.as_secs_f32() * 1000.0)))?;
+        table_brrw.set_raw(row,5,Value::F32(F32::new(entry.max.as_secs_f32() * 1000.0)))?;
+      }
+    }
+    let mut registers = HashSet::new();
+    registers.insert((TableId::Global(*MECH_PROFILE),RegisterIndex::All,RegisterIndex::All));
+    let mut output_registers = self.propagate(&registers)?;
+    output_registers.extend(registers);
+    Ok(output_registers)
+  }
+
+  fn rebuild_registers(&mut self) {
+    self.input.clear();
+    self.output.clear();
+    self.defined_tables.clear();
+    for block_ref in self.blocks.values() {
+      let block_brrw = block_ref.borrow();
+      self.input.extend(block_brrw.input.iter().cloned());
+      self.output.extend(block_brrw.output.iter().cloned());
+      self.defined_tables.extend(block_brrw.defined_tables.iter().cloned());
+    }
+    for block_ref in self.errors.values().flat_map(|block_refs| block_refs.iter()) {
+      let block_brrw = block_ref.borrow();
+      self.input.extend(block_brrw.input.iter().cloned());
+      self.output.extend(block_brrw.output.iter().cloned());
+    }
+  }
+
   pub fn load_block_refs(&mut self, mut blocks: Vec<BlockRef>) -> (Vec<BlockId>,Vec<MechError>) {
     let mut block_ids = vec![];
     let mut block_errors = vec![];
@@ -361,7 +1593,9 @@ impl Core {
       for register in new_block_output.iter() {
         self.step(register);
       }
-      self.schedule_blocks();
+      if let Err(x) = self.schedule_blocks() {
+        block_errors.push(x);
+      }
       //self.recompile_dynamic_tables();
     }
     (block_ids,block_errors)
@@ -370,6 +1604,11 @@ impl Core {
   pub fn load_blocks(&mut self, mut blocks: &Vec<Block>) -> (Vec<BlockId>,Vec<MechError>) {
     let mut block_ids = vec![];
     let mut block_errors = vec![];
+    let tfms = blocks.iter().map(|block| block.all_transformations()).filter(|tfms| tfms.len() > 0).collect();
+    if let Err(x) = self.journal_event(JournalEvent::Blocks(tfms)) {
+      block_errors.push(x);
+    }
+    self.start_storage_log();
     for block in blocks {
       let (mut new_block_ids, mut new_block_errors, mut new_block_output) = self.load_block(Rc::new(RefCell::new(block.clone())));
       block_ids.append(&mut new_block_ids);
@@ -377,9 +1616,17 @@ impl Core {
       for register in new_block_output.iter() {
         self.step(register);
       }
-      self.schedule_blocks();
+      if let Err(x) = self.schedule_blocks() {
+        block_errors.push(x);
+      }
       //self.recompile_dynamic_tables();
     }
+    if let Err(x) = self.step_state_machines() {
+      block_errors.push(x);
+    }
+    if let Err(x) = self.finish_storage_log() {
+      block_errors.push(x);
+    }
     (block_ids,block_errors)
   }
 
@@ -412,19 +1659,23 @@ impl Core {
       for fxn_id in block_brrw.required_functions.iter() {
         self.required_functions.insert(*fxn_id);
       }
+      if let Some(write_log) = &mut self.write_log {
+        write_log.capture_transformations(&self.database.borrow(), &block_brrw.all_transformations());
+      }
       // try to satisfy the block
       match block_brrw.ready() {
         Ok(()) => {
           
           let id = block_brrw.gen_id();
+          block_brrw.start_timers(self.time.now());
 
           // Merge input and output
-          self.input = self.input.union(&mut block_brrw.input).cloned().collect();
-          self.output = self.output.union(&mut block_brrw.output).cloned().collect();
-          self.defined_tables = self.defined_tables.union(&mut block_brrw.defined_tables).cloned().collect();
+          self.input.extend(block_brrw.input.iter().cloned());
+          self.output.extend(block_brrw.output.iter().cloned());
+          self.defined_tables.extend(block_brrw.defined_tables.iter().cloned());
           {
             let mut database_brrw = self.database.borrow_mut();
-            database_brrw.dynamic_tables = database_brrw.dynamic_tables.union(&mut block_brrw.dynamic_tables).cloned().collect();
+            database_brrw.dynamic_tables.extend(block_brrw.dynamic_tables.iter().cloned());
           }
           self.schedule.add_block(block_ref.clone());
           self.blocks.insert(id,block_ref_c.clone());
@@ -443,8 +1694,8 @@ impl Core {
         }
         Err(x) => {
           // Merge input and output
-          self.input = self.input.union(&mut block_brrw.input).cloned().collect();
-          self.output = self.output.union(&mut block_brrw.output).cloned().collect();        
+          self.input.extend(block_brrw.input.iter().cloned());
+          self.output.extend(block_brrw.output.iter().cloned());
           let (mech_error,_) = block_brrw.unsatisfied_transformation.as_ref().unwrap();
           let blocks_with_errors = self.full_errors.entry(mech_error.clone()).or_insert(Vec::new());
           blocks_with_errors.push(block_ref_c.clone());
@@ -527,12 +1778,36 @@ impl Core {
     }
   }
 
+  // A block that closes a cycle isn't scheduled. It's unloaded and waits with
+  // the cycle's error until it's removed or replaced.
   pub fn schedule_blocks(&mut self) -> Result<(),MechError> {
-    self.schedule.schedule_blocks()
+    let result = self.schedule.schedule_blocks();
+    if let Err(ref error) = result {
+      for block_ref in self.schedule.cyclic_blocks.drain(..) {
+        let block_id = block_ref.borrow().id;
+        self.blocks.remove(&block_id);
+        block_ref.borrow_mut().state = BlockState::Error;
+        self.errors.entry(error.kind.clone()).or_insert(Vec::new()).push(block_ref.clone());
+        self.full_errors.entry(error.clone()).or_insert(Vec::new()).push(block_ref);
+      }
+    }
+    result
   }
 
   pub fn step(&mut self, register: &(TableId,RegisterIndex,RegisterIndex)) -> Result<(),MechError> {
-    self.schedule.run_schedule(register)
+    if self.schedule.triggered_registers(register).len() == 0 {
+      return Err(MechError{msg: "".to_string(), id: 1011, kind: MechErrorKind::GenericError(format!("No schedule assocaited with {:?}", register))});
+    }
+    let mut registers = HashSet::new();
+    registers.insert(*register);
+    self.propagate(&registers)?;
+    Ok(())
+  }
+}
+
+impl Drop for Core {
+  fn drop(&mut self) {
+    self.schedule.detach_nodes();
   }
 }
 
@@ -1,7 +1,7 @@
 use crate::*;
 use hashbrown::{HashMap, HashSet};
-use std::rc::Rc;
-use std::cell::RefCell;
+use crate::sync::Rc;
+use crate::sync::RefCell;
 use std::fmt;
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -9,7 +9,13 @@ pub enum Change {
   Set((u64, Vec<(TableIndex, TableIndex, Value)>)),
   NewTable{table_id: u64, rows: usize, columns: usize},
   ColumnAlias{table_id: u64, column_ix: usize, column_alias: u64},
+  RowAlias{table_id: u64, row_ix: usize, row_alias: u64},
   ColumnKind{table_id: u64, column_ix: usize, column_kind: ValueKind},
+  RemoveTable{table_id: u64},
+  InsertRows{table_id: u64, row_ix: usize, rows: usize},
+  RemoveRows{table_id: u64, rows: RowSelection},
+  Resize{table_id: u64, rows: usize, columns: usize},
+  Append{table_id: u64, rows: Vec<Vec<Value>>},
 }
 
 impl fmt::Debug for Change {
@@ -19,14 +25,110 @@ impl fmt::Debug for Change {
       Change::Set((table_id,args)) => write!(f,"Set({},{:#?})",humanize(table_id),args)?,
       Change::NewTable{table_id,rows,columns} => write!(f,"NewTable({},{:?},{:?})",humanize(table_id),rows,columns)?,
       Change::ColumnAlias{table_id,column_ix,column_alias} => write!(f,"ColumnAlias({},{:?},{})",humanize(table_id),column_ix,humanize(column_alias))?,
+      Change::RowAlias{table_id,row_ix,row_alias} => write!(f,"RowAlias({},{:?},{})",humanize(table_id),row_ix,humanize(row_alias))?,
       Change::ColumnKind{table_id,column_ix,column_kind} => write!(f,"ColumnKind({},{:?},{:?})",humanize(table_id),column_ix,column_kind)?,
+      Change::RemoveTable{table_id} => write!(f,"RemoveTable({})",humanize(table_id))?,
+      Change::InsertRows{table_id,row_ix,rows} => write!(f,"InsertRows({},{:?},{:?})",humanize(table_id),row_ix,rows)?,
+      Change::RemoveRows{table_id,rows} => write!(f,"RemoveRows({},{:?})",humanize(table_id),rows)?,
+      Change::Resize{table_id,rows,columns} => write!(f,"Resize({},{:?},{:?})",humanize(table_id),rows,columns)?,
+      Change::Append{table_id,rows} => write!(f,"Append({},{:#?})",humanize(table_id),rows)?,
     }
     Ok(())
   }
 }
 
+impl Change {
+
+  pub fn table_id(&self) -> u64 {
+    match self {
+      Change::Set((table_id, _)) |
+      Change::NewTable{table_id, ..} |
+      Change::ColumnAlias{table_id, ..} |
+      Change::RowAlias{table_id, ..} |
+      Change::ColumnKind{table_id, ..} |
+      Change::RemoveTable{table_id} |
+      Change::InsertRows{table_id, ..} |
+      Change::RemoveRows{table_id, ..} |
+      Change::Resize{table_id, ..} |
+      Change::Append{table_id, ..} => *table_id,
+    }
+  }
+
+}
+
 pub type Transaction = Vec<Change>;
 
+// The rows a change removes, either as a list of row indices (counting from
+// zero) or as a mask with an entry for every row of the table.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RowSelection {
+  Indices(Vec<usize>),
+  Mask(Vec<bool>),
+}
+
+impl RowSelection {
+
+  pub fn to_mask(&self, rows: usize) -> Result<Vec<bool>,MechError> {
+    match self {
+      RowSelection::Indices(ixes) => {
+        let mut mask = vec![false; rows];
+        for ix in ixes {
+          if *ix >= rows {
+            return Err(MechError{msg: "".to_string(), id: 1727, kind: MechErrorKind::GenericError(format!("Row {} is out of range for a table with {} rows", ix, rows))});
+          }
+          mask[*ix] = true;
+        }
+        Ok(mask)
+      }
+      RowSelection::Mask(mask) => Ok(mask.clone()),
+    }
+  }
+
+}
+
+// Records what a change overwrote, so a transaction that fails part way
+// through can be undone without leaving the database half updated.
+pub enum Rollback {
+  Set{table: TableRef, row: TableIndex, col: TableIndex, value: Value},
+  NewTable(u64),
+  Schema{table: TableRef, rows: usize, cols: usize, col_kinds: Vec<ValueKind>, col_map: AliasMap, row_map: AliasMap, data: Vec<Column>},
+  // Changes that move or drop rows edit the columns in place, so the whole
+  // table is saved, values included.
+  Table(TableSnapshot),
+  DynamicTables(HashSet<(TableId,RegisterIndex,RegisterIndex)>),
+}
+
+impl Rollback {
+
+  pub fn schema(table: &TableRef) -> Rollback {
+    let table_brrw = table.borrow();
+    Rollback::Schema{
+      table: table.clone(),
+      rows: table_brrw.rows,
+      cols: table_brrw.cols,
+      col_kinds: table_brrw.col_kinds.clone(),
+      col_map: table_brrw.col_map.clone(),
+      row_map: table_brrw.row_map.clone(),
+      data: table_brrw.data.clone(),
+    }
+  }
+
+  // For a change that may grow the table to at least rows by cols. Growing
+  // resizes the columns in place, which the handles kept by a schema rollback
+  // would see, so the whole table is saved in that case.
+  pub fn reshape(database: &Database, table: &TableRef, rows: usize, cols: usize) -> Rollback {
+    let (id, grows) = {
+      let table_brrw = table.borrow();
+      (table_brrw.id, rows > table_brrw.rows || cols > table_brrw.cols)
+    };
+    match grows {
+      true => Rollback::Table(TableSnapshot::capture(database, id)),
+      false => Rollback::schema(table),
+    }
+  }
+
+}
+
 #[derive(Clone)]
 pub struct Database {
   pub dynamic_tables: HashSet<(TableId,RegisterIndex,RegisterIndex)>,
@@ -99,6 +201,37 @@ impl Database {
     }
   }
 
+  // Undo a partially applied transaction. Entries are applied in reverse
+  // order so that later changes are peeled off before earlier ones.
+  pub fn rollback(&mut self, mut log: Vec<Rollback>) -> Result<(),MechError> {
+    while let Some(entry) = log.pop() {
+      match entry {
+        Rollback::Set{table, row, col, value} => {
+          table.borrow().set(&row, &col, value)?;
+        }
+        Rollback::NewTable(table_id) => {
+          self.tables.remove(&table_id);
+        }
+        Rollback::Schema{table, rows, cols, col_kinds, col_map, row_map, data} => {
+          let mut table_brrw = table.borrow_mut();
+          table_brrw.rows = rows;
+          table_brrw.cols = cols;
+          table_brrw.col_kinds = col_kinds;
+          table_brrw.col_map = col_map;
+          table_brrw.row_map = row_map;
+          table_brrw.data = data;
+        }
+        Rollback::Table(snapshot) => {
+          snapshot.restore(self);
+        }
+        Rollback::DynamicTables(dynamic_tables) => {
+          self.dynamic_tables = dynamic_tables;
+        }
+      }
+    }
+    Ok(())
+  }
+
   pub fn get_table(&self, table_name: &str) -> Option<&Rc<RefCell<Table>>> {
     let alias = hash_str(table_name);
     match self.table_alias_to_id.get(&alias) {
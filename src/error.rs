@@ -33,10 +33,12 @@ pub enum MechErrorKind {
   PendingTable(TableId),                             // TableId of pending table                          
   DimensionMismatch(Vec<(Rows,Cols)>),      // Argument dimensions are mismatched ((row,col),(row,col))
   //MissingColumn((TableId,TableIndex)),             // The identified table is missing a needed column
-  //ColumnKindMismatch(Vec<ValueKind>),              // Excepted kind versus given kind
+  ColumnKindMismatch(TableId,usize,Option<u64>,ValueKind,ValueKind), // (table, column index, column alias, expected kind, given kind)
   //SubscriptOutOfBounds(((Rows,Cols),(Rows,Cols))), // (target) vs (actual) index
   LinearSubscriptOutOfBounds((Rows,Rows)),           // (target) vs (actual) index
   DuplicateAlias(u64),                               // Alias ID
+  MissingRowAlias(TableId,u64),                      // (table, row alias)
+  MissingColumnAlias(TableId,u64),                   // (table, column alias)
   //DomainMismatch(u64, u64),                        // domain IDs (target vs actual)
   MissingFunction(u64),                              // ID of missing function
   //TransformationPending(Transformation),           // Block is unsatisfied so the transformation is not added
@@ -53,6 +55,19 @@ pub enum MechErrorKind {
   UnhandledTableShape(TableShape),
   TooManyInputArguments(usize,usize),                // (given,expected)
   ParserError(nodes::ParserNode, ParserErrorReport, String),
+  InvalidChange(usize, Box<MechError>),              // (index of the rejected change in its transaction, reason)
+  IterationLimitReached(usize),                      // Number of rounds run without reaching a steady state
+  CyclicDependency(Vec<BlockId>),                    // Blocks that trigger each other in a loop, starting and ending with the same block
+  MissingStep(usize),                                // Step that isn't available in the transaction history
+  InvalidSnapshot,
+  SnapshotVersionMismatch(u32,u32),                  // (found,expected)
+  InvalidMessage,
+  ProtocolVersionMismatch(u32,u32),                  // (peer,ours)
+  MessageTooLarge(usize,usize),                      // (length,maximum)
+  InvalidJournal,
+  JournalVersionMismatch(u32,u32),                   // (found,expected)
+  InvalidStorage,
+  StorageVersionMismatch(u32,u32),                   // (found,expected)
   None,
 }
 
@@ -1,6 +1,6 @@
 use crate::*;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::sync::RefCell;
+use crate::sync::Rc;
 use std::fmt::*;
 use num_traits::*;
 #[cfg(feature = "parallel")]
@@ -109,8 +109,8 @@ macro_rules! compare_infix_vv {
       pub out: ColumnV<bool>
     }
     impl<T,U> MechFunction for $func_name<T,U> 
-    where T: Clone + Debug + PartialEq + PartialOrd + Into<U>,
-          U: Clone + Debug + PartialEq + PartialOrd + Into<T>,
+    where T: Clone + Debug + PartialEq + PartialOrd + Into<U> + Send + Sync,
+          U: Clone + Debug + PartialEq + PartialOrd + Into<T> + Send + Sync,
     {
       fn solve(&self) {
         let (lhs,lsix,leix) = &self.lhs;
@@ -170,8 +170,8 @@ macro_rules! compare_infix_vs {
       pub out: ColumnV<bool>
     }
     impl<T,U> MechFunction for $func_name<T,U> 
-    where T: Clone + Debug + PartialEq + PartialOrd + Into<U>,
-          U: Clone + Debug + PartialEq + PartialOrd + Into<T>,
+    where T: Clone + Debug + PartialEq + PartialOrd + Into<U> + Send + Sync,
+          U: Clone + Debug + PartialEq + PartialOrd + Into<T> + Send + Sync,
     {
       fn solve(&self) {
         let (lhs,lsix,leix) = &self.lhs;
@@ -228,8 +228,8 @@ macro_rules! compare_infix_sv {
       pub out: ColumnV<bool>
     }
     impl<T,U> MechFunction for $func_name<T,U> 
-    where T: Clone + Debug + PartialEq + PartialOrd + Into<U>,
-          U: Clone + Debug + PartialEq + PartialOrd + Into<T>,
+    where T: Clone + Debug + PartialEq + PartialOrd + Into<U> + Send + Sync,
+          U: Clone + Debug + PartialEq + PartialOrd + Into<T> + Send + Sync,
     {
       fn solve(&self) {
         let (lhs,lsix,leix) = &self.lhs;
@@ -286,8 +286,8 @@ macro_rules! compare_infix_dd {
       pub out: OutTable
     }
     impl<T,U> MechFunction for $func_name<T,U> 
-    where T: Clone + Debug + PartialEq + PartialOrd + Into<U>,
-          U: Clone + Debug + PartialEq + PartialOrd + Into<T>,
+    where T: Clone + Debug + PartialEq + PartialOrd + Into<U> + Send + Sync,
+          U: Clone + Debug + PartialEq + PartialOrd + Into<T> + Send + Sync,
     {
       fn solve(&self) {
         let lhs = &self.lhs.borrow();
@@ -324,8 +324,8 @@ macro_rules! compare_infix_ds {
       pub out: OutTable
     }
     impl<T,U> MechFunction for $func_name<T,U> 
-    where T: Clone + Debug + PartialEq + PartialOrd + Into<U>,
-          U: Clone + Debug + PartialEq + PartialOrd + Into<T>,
+    where T: Clone + Debug + PartialEq + PartialOrd + Into<U> + Send + Sync,
+          U: Clone + Debug + PartialEq + PartialOrd + Into<T> + Send + Sync,
     {
       fn solve(&self) {
         let lhs = &self.lhs.borrow();
@@ -1,6 +1,6 @@
 use crate::*;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::sync::RefCell;
+use crate::sync::Rc;
 use std::fmt::*;
 use num_traits::*;
 
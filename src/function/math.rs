@@ -1,6 +1,6 @@
 use crate::*;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::sync::RefCell;
+use crate::sync::Rc;
 use std::fmt::*;
 use num_traits::*;
 use std::ops::*;
@@ -134,7 +134,7 @@ where T: std::ops::Neg<Output = T> + Copy + Debug
 }
 
 impl<T> MechFunction for NegateS<T> 
-where T: std::ops::Neg<Output = T> + Copy + Debug
+where T: std::ops::Neg<Output = T> + Copy + Debug + Send + Sync
 {
   fn solve(&self) {
     (self.out.borrow_mut())[0] = -((self.arg.borrow())[0]);
@@ -150,8 +150,8 @@ pub struct NegateV<T,U>
 }
 
 impl<T,U> MechFunction for NegateV<T,U>  
-where T: std::ops::Neg<Output = T> + Into<U> + Copy + Debug,
-      U: std::ops::Neg<Output = U> + Into<T> + Copy + Debug,
+where T: std::ops::Neg<Output = T> + Into<U> + Copy + Debug + Send + Sync,
+      U: std::ops::Neg<Output = U> + Into<T> + Copy + Debug + Send + Sync,
 {
   fn solve(&self) {
     self.out.borrow_mut().iter_mut().zip(self.arg.borrow().iter()).for_each(|(out, arg)| *out = -(T::into(*arg))); 
@@ -168,7 +168,7 @@ macro_rules! binary_infix_sv {
       pub lhs: ColumnV<T>, pub rhs: ColumnV<T>, pub out: ColumnV<T>
     }
     impl<T> MechFunction for $func_name<T> 
-    where T: MechNumArithmetic<T> + Copy + Debug
+    where T: MechNumArithmetic<T> + Copy + Debug + Send + Sync
     {
       fn solve(&self) {
         let lhs = self.lhs.borrow()[0];
@@ -187,7 +187,7 @@ macro_rules! binary_infix_vs {
       pub lhs: ColumnV<T>, pub rhs: ColumnV<T>, pub out: ColumnV<T>
     }
     impl<T> MechFunction for $func_name<T> 
-    where T: MechNumArithmetic<T> + Copy + Debug
+    where T: MechNumArithmetic<T> + Copy + Debug + Send + Sync
     {
       fn solve(&self) {
         let rhs = self.rhs.borrow()[0];
@@ -368,7 +368,7 @@ macro_rules! binary_infix_ss {
       pub lhs: ColumnV<T>, pub lix: usize, pub rhs: ColumnV<T>, pub rix: usize, pub out: ColumnV<T>
     }
     impl<T> MechFunction for $func_name<T> 
-    where T: MechNumArithmetic<T> + Copy + Debug
+    where T: MechNumArithmetic<T> + Copy + Debug + Send + Sync
     {
       fn solve(&self) {
         let lhs = self.lhs.borrow()[self.lix];
@@ -389,7 +389,7 @@ macro_rules! binary_infix_par_sv {
       pub lhs: ColumnV<T>, pub rhs: ColumnV<T>, pub out: ColumnV<T>
     }
     impl<T> MechFunction for $func_name<T> 
-    where T: MechNumArithmetic<T> + Copy + Debug
+    where T: MechNumArithmetic<T> + Copy + Debug + Send + Sync
     {
       fn solve(&self) {
         let lhs = self.lhs.borrow()[0];
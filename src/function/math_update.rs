@@ -1,6 +1,6 @@
 use crate::*;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::sync::RefCell;
+use crate::sync::Rc;
 use std::fmt::*;
 use num_traits::*;
 use std::ops::*;
@@ -35,8 +35,8 @@ macro_rules! math_update_SIxSIx {
       pub arg: ColumnV<T>, pub ix: usize, pub out: ColumnV<U>, pub oix: usize
     }
     impl<T,U> MechFunction for $func_name<T,U>
-    where T: Clone + Debug + Into<U> + MechNumArithmetic<T>,
-          U: Clone + Debug + Into<T> + MechNumArithmetic<U>
+    where T: Clone + Debug + Into<U> + MechNumArithmetic<T> + Send + Sync,
+          U: Clone + Debug + Into<T> + MechNumArithmetic<U> + Send + Sync
     {
       fn solve(&self) {
         (self.out.borrow_mut())[self.oix] $op1 T::into((self.arg.borrow())[self.ix].clone());
@@ -1,6 +1,6 @@
 use crate::*;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::sync::RefCell;
+use crate::sync::Rc;
 use std::fmt::*;
 use num_traits::{Zero,zero};
 use std::ops::*;
@@ -91,8 +91,8 @@ pub struct StatsSumVB<T,U> {
 }
 
 impl<T,U> MechFunction for StatsSumVB<T,U>
-where T: std::ops::Add<Output = T> + Debug + Copy + Into<U> + Zero, 
-      U: std::ops::Add<Output = U> + Debug + Copy + Into<T> + Zero,
+where T: std::ops::Add<Output = T> + Debug + Copy + Into<U> + Zero + Send + Sync,
+      U: std::ops::Add<Output = U> + Debug + Copy + Into<T> + Zero + Send + Sync,
 {
   fn solve(&self) {
     let result = self.col.borrow()
@@ -1,6 +1,6 @@
 use crate::*;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::sync::RefCell;
+use crate::sync::Rc;
 use std::fmt::*;
 use num_traits::*;
 use rust_core::iter::FromIterator;
@@ -126,8 +126,8 @@ pub struct CopySV<T,U> {
   pub arg: ColumnV<T>, pub ix: usize, pub out: ColumnV<U>
 }
 impl<T,U> MechFunction for CopySV<T,U>  
-where T: Clone + Debug + Into<U>,
-      U: Clone + Debug
+where T: Clone + Debug + Into<U> + Send + Sync,
+      U: Clone + Debug + Send + Sync
 {
   fn solve(&self) {
     let arg = self.arg.borrow()[self.ix].clone();
@@ -271,8 +271,8 @@ pub struct CopyVI<T,U> {
   pub arg: ColumnV<T>, pub ix: ColumnV<usize>, pub out: ColumnV<U>
 }
 impl<T,U> MechFunction for CopyVI<T,U> 
-where T: Clone + Debug + Into<U>,
-      U: Clone + Debug
+where T: Clone + Debug + Into<U> + Send + Sync,
+      U: Clone + Debug + Send + Sync
 {
   fn solve(&self) {
     let mut out_brrw = self.out.borrow_mut();
@@ -295,9 +295,9 @@ pub struct CopyVRV<T,U,V> {
   pub arg: ColumnV<T>, pub ix: ColumnV<U>, pub out: ColumnV<V>
 }
 impl<T,U,V> MechFunction for CopyVRV<T,U,V> 
-where T: Clone + Debug + Into<V>,
-      U: Clone + Debug + Into<usize>,
-      V: Clone + Debug
+where T: Clone + Debug + Into<V> + Send + Sync,
+      U: Clone + Debug + Into<usize> + Send + Sync,
+      V: Clone + Debug + Send + Sync
 {
   fn solve(&self) {
     let mut out_brrw = self.out.borrow_mut();
@@ -348,8 +348,8 @@ pub struct SetSIxSIx<T,U> {
   pub arg: ColumnV<T>, pub ix: usize, pub out: ColumnV<U>, pub oix: usize
 }
 impl<T,U> MechFunction for SetSIxSIx<T,U>
-where T: Clone + Debug + Into<U>,
-      U: Clone + Debug
+where T: Clone + Debug + Into<U> + Send + Sync,
+      U: Clone + Debug + Send + Sync
 {
   fn solve(&self) {
     (self.out.borrow_mut())[self.oix] = T::into((self.arg.borrow())[self.ix].clone());
@@ -364,8 +364,8 @@ pub struct SetSIxVB<T,U> {
   pub arg: ColumnV<T>, pub ix: usize, pub out: ColumnV<U>, pub oix: ColumnV<bool>
 }
 impl<T,U> MechFunction for SetSIxVB<T,U>
-where T: Clone + Debug + Into<U>,
-      U: Clone + Debug
+where T: Clone + Debug + Into<U> + Send + Sync,
+      U: Clone + Debug + Send + Sync
 {
   fn solve(&self) {
     let oix_brrw = self.oix.borrow();
@@ -416,8 +416,8 @@ pub struct SetVVB<T,U> {
   pub arg: ColumnV<T>, pub out: ColumnV<U>, pub oix: ColumnV<bool>
 }
 impl<T,U> MechFunction for SetVVB<T,U>
-where T: Clone + Debug + Into<U>,
-      U: Clone + Debug + Into<T>
+where T: Clone + Debug + Into<U> + Send + Sync,
+      U: Clone + Debug + Into<T> + Send + Sync
 {
   fn solve(&self) {
     self.out.borrow_mut()
@@ -437,8 +437,8 @@ pub struct SetVVRIx<T,U> {
   pub arg: ColumnV<T>, pub out: ColumnV<U>, pub oix: ColumnV<F32>
 }
 impl<T,U> MechFunction for SetVVRIx<T,U>
-where T: Clone + Debug + Into<U>,
-      U: Clone + Debug + Into<T>
+where T: Clone + Debug + Into<U> + Send + Sync,
+      U: Clone + Debug + Into<T> + Send + Sync
 {
   fn solve(&self) {
     let arg_brrw = self.arg.borrow();
@@ -479,8 +479,8 @@ pub struct SetVV<T,U> {
   pub arg: ColumnV<T>, pub out: ColumnV<U>
 }
 impl<T,U> MechFunction for SetVV<T,U>
-where T: Clone + Debug + Into<U>,
-      U: Clone + Debug
+where T: Clone + Debug + Into<U> + Send + Sync,
+      U: Clone + Debug + Send + Sync
 {
   fn solve(&self) {
     self.out.borrow_mut()
@@ -519,8 +519,8 @@ pub struct SetVS<T,U> {
   pub arg: ColumnV<T>, pub ix: usize, pub out: ColumnV<U>
 }
 impl<T,U> MechFunction for SetVS<T,U>
-where T: Clone + Debug + Into<U>,
-      U: Clone + Debug + Into<T>
+where T: Clone + Debug + Into<U> + Send + Sync,
+      U: Clone + Debug + Into<T> + Send + Sync
 {
   fn solve(&self) {
     let arg = &self.arg.borrow()[self.ix];
@@ -630,6 +630,39 @@ impl MechFunction for CopyT {
   }
 }
 
+// Copy Table Row : Table
+#[derive(Debug)]
+pub struct CopyTR {
+  pub arg: ArgTable, pub ix: usize, pub out: OutTable
+}
+impl MechFunction for CopyTR {
+  fn solve(&self) {
+    let mut out_brrw = self.out.borrow_mut();
+    let arg_brrw = self.arg.borrow();
+    // A value that doesn't fit the output stops the copy, rather than leaving
+    // the rest of the row to be written out of line with it.
+    for col in 0..arg_brrw.cols {
+      match arg_brrw.get_raw(self.ix,col) {
+        Ok(value) => if out_brrw.set_raw(0,col,value).is_err() {
+          return;
+        }
+        Err(_) => (),
+      }
+    }
+  }
+  fn to_string(&self) -> String { 
+    let mut box_drawing = BoxPrinter::new();
+    box_drawing.add_header("CopyTR");
+    box_drawing.add_header("arg");
+    box_drawing.add_line(format!("{:#?}", &self.arg.borrow()));
+    box_drawing.add_header("ix");
+    box_drawing.add_line(format!("{}", self.ix));
+    box_drawing.add_header("out");
+    box_drawing.add_line(format!("{:#?}", &self.out.borrow()));
+    box_drawing.print()
+  }
+}
+
 
 pub struct TableVerticalConcatenate{}
 impl MechFunctionCompiler for TableVerticalConcatenate {
@@ -1555,21 +1588,52 @@ impl MechFunctionCompiler for TableRange {
 }
 
 
-// AppendRow Table : Table
+// AppendRows Table : Table
+// Adds the selected rows of the argument to the end of the output every time
+// it's solved. Each pair in columns is an argument column and the output column
+// it's copied to. Values go through the table as values, so this works for any
+// kind of column.
 #[derive(Debug)]
-pub struct AppendRowSV {
-  pub arg: ArgTable, pub ix: usize,  pub out: OutTable
+pub struct AppendRowsT {
+  pub arg: ArgTable, pub row: Option<usize>, pub columns: Vec<(usize,usize)>, pub out: OutTable,
 }
 
-impl MechFunction for AppendRowSV {
+impl MechFunction for AppendRowsT {
   fn solve(&self) {
-    let mut out_brrw = self.out.borrow_mut();
     let arg_brrw = self.arg.borrow();
+    let mut out_brrw = self.out.borrow_mut();
+    // The argument can change size between solves, so the rows to append are
+    // worked out each time. Values are coerced before the output is resized,
+    // and a value that can't be appended leaves the output as it was.
+    let rows: Vec<usize> = match self.row {
+      Some(row) if row < arg_brrw.rows => vec![row],
+      Some(_) => {return;}
+      None => (0..arg_brrw.rows).collect(),
+    };
+    if rows.len() == 0 || self.columns.len() == 0 {
+      return;
+    }
+    let mut values = Vec::with_capacity(rows.len() * self.columns.len());
+    for row in &rows {
+      for (arg_col,out_col) in &self.columns {
+        match arg_brrw.get_raw(*row,*arg_col).and_then(|value| out_brrw.coerce(*out_col,value)) {
+          Ok(value) => values.push(value),
+          Err(_) => {return;}
+        }
+      }
+    }
     let orows = out_brrw.rows;
-    out_brrw.resize(orows + 1, 1);
-    let value = arg_brrw.get_linear(self.ix).unwrap();
-    out_brrw.set_col_kind(0,value.kind());
-    out_brrw.set_raw(orows,0,value);
+    let ocols = out_brrw.cols;
+    if out_brrw.resize(orows + rows.len(), ocols).is_err() {
+      return;
+    }
+    for (ix,row_values) in values.chunks(self.columns.len()).enumerate() {
+      for ((_,out_col),value) in self.columns.iter().zip(row_values) {
+        if out_brrw.set_raw(orows + ix,*out_col,value.clone()).is_err() {
+          return;
+        }
+      }
+    }
   }
   fn to_string(&self) -> String { format!("{:#?}", self)}
 }
@@ -1600,6 +1664,69 @@ impl MechFunctionCompiler for TableAppend {
 
     let dest_shape = {out_table.borrow().shape()};
     match (arg_shape,arow_ix,dest_shape) {
+      // Whole rows of a table with more than one column. Columns are matched by
+      // alias when both tables have them, and by position otherwise. An empty
+      // output takes its columns from the argument.
+      (TableShape::Row(_),TableIndex::All,_) |
+      (TableShape::Row(_),TableIndex::Index(_),_) |
+      (TableShape::Matrix(_,_),TableIndex::All,_) => {
+        if Rc::ptr_eq(&src_table,&out_table) {
+          return Err(MechError{msg: "".to_string(), id: 4940, kind: MechErrorKind::GenericError("Can't append a table to itself".to_string())});
+        }
+        let src_brrw = src_table.borrow();
+        let mut out_brrw = out_table.borrow_mut();
+        // A row shape with a row index only comes from a whole row, x{ix,:},
+        // so the index is a row number. x{ix} alone picks a single element,
+        // which is a scalar and appended below.
+        let row = match (arow_ix,src_cols) {
+          (TableIndex::Index(0),_) => {return Err(MechError{msg: "".to_string(), id: 4941, kind: MechErrorKind::ZeroIndex});}
+          (TableIndex::Index(ix),TableIndex::All) => Some(ix - 1),
+          (TableIndex::All,TableIndex::All) => None,
+          x => {return Err(MechError{msg: "".to_string(), id: 4942, kind: MechErrorKind::GenericError(format!("{:?}", x))});}
+        };
+        let rows: Vec<usize> = match row {
+          Some(row) => vec![row],
+          None => (0..src_brrw.rows).collect(),
+        };
+        if out_brrw.cols == 0 {
+          let orows = out_brrw.rows;
+          out_brrw.resize(orows,src_brrw.cols)?;
+          for col_ix in 0..src_brrw.cols {
+            match src_brrw.col_map.get_alias(&col_ix) {
+              Ok(0) | Err(_) => (),
+              Ok(alias) => {out_brrw.set_col_alias(col_ix,alias);}
+            }
+          }
+        }
+        let columns: Vec<(usize,usize)> = if src_brrw.has_col_aliases() && out_brrw.has_col_aliases() {
+          let mut columns = vec![];
+          for col_ix in 0..src_brrw.cols {
+            match src_brrw.col_map.get_alias(&col_ix) {
+              Ok(0) | Err(_) => (),
+              Ok(alias) => match out_brrw.col_map.get_index(&alias) {
+                Ok(out_col_ix) => columns.push((col_ix,out_col_ix)),
+                Err(_) => {return Err(MechError{msg: humanize(&alias), id: 4938, kind: MechErrorKind::MissingColumnAlias(out_table_id.clone(),alias)});}
+              }
+            }
+          }
+          columns
+        } else if src_brrw.cols == out_brrw.cols {
+          (0..src_brrw.cols).map(|col_ix| (col_ix,col_ix)).collect()
+        } else {
+          return Err(MechError{msg: "".to_string(), id: 4939, kind: MechErrorKind::DimensionMismatch(vec![(src_brrw.rows,src_brrw.cols),(out_brrw.rows,out_brrw.cols)])});
+        };
+        for (src_col_ix,out_col_ix) in &columns {
+          if out_brrw.col_kinds[*out_col_ix] == ValueKind::Empty {
+            out_brrw.set_col_kind(*out_col_ix,src_brrw.col_kinds[*src_col_ix].clone())?;
+          }
+          if let Some(row) = rows.first() {
+            out_brrw.coerce(*out_col_ix,src_brrw.get_raw(*row,*src_col_ix)?)?;
+          }
+        }
+        drop(out_brrw);
+        drop(src_brrw);
+        block.plan.push(AppendRowsT{arg: src_table.clone(), row, columns, out: out_table.clone()});
+      }
       (TableShape::Scalar,TableIndex::All,TableShape::Pending(_)) |
       (TableShape::Scalar,TableIndex::Index(_),TableShape::Column(_)) |
       (TableShape::Scalar,TableIndex::All,TableShape::Scalar) => {
@@ -3420,6 +3547,25 @@ impl MechFunctionCompiler for TableDefine {
           x => {return Err(MechError{msg: "".to_string(), id: 4931, kind: MechErrorKind::GenericError(format!("{:?}", x))});},
         }
       }
+      // Select a whole row (by row index, or by a row alias resolved to its index)
+      (TableIndex::Index(0), TableIndex::All) => {
+        return Err(MechError{msg: "".to_string(), id: 4943, kind: MechErrorKind::ZeroIndex});
+      }
+      (TableIndex::Index(row_ix), TableIndex::All) => {
+        {
+          let src_table_brrw = src_table.borrow();
+          let mut out_table_brrw = out_table.borrow_mut();
+          out_table_brrw.resize(1,src_table_brrw.cols)?;
+          for (col, kind) in src_table_brrw.col_kinds.iter().enumerate() {
+            out_table_brrw.set_col_kind(col, kind.clone())?;
+          }
+          out_table_brrw.col_map = src_table_brrw.col_map.clone();
+          if let Ok(alias @ 1..) = src_table_brrw.row_map.get_alias(&(row_ix - 1)) {
+            out_table_brrw.set_row_alias(0,alias)?;
+          }
+        }
+        block.plan.push(CopyTR{arg: src_table.clone(), ix: row_ix - 1, out: out_table.clone()});
+      }
       x => {return Err(MechError{msg: "".to_string(), id: 4932, kind: MechErrorKind::GenericError(format!("{:?}", x))});},
     }
     Ok(())
@@ -0,0 +1,428 @@
+// # History
+
+// A core can keep a bounded history of the transactions applied to it and the
+// ticks it has run. Each entry remembers the state of every table the step
+// touched before it was applied, and what changed in each of them
+// once the blocks it triggered had run. A table that kept its shape only
+// keeps the cells that changed. Undo and redo restore those states directly
+// rather than replaying computation, so the tables end up exactly as they
+// were at that step.
+
+// ## Prelude
+
+use crate::*;
+use crate::sync::Rc;
+use hashbrown::HashSet;
+use std::collections::VecDeque;
+
+// ## Table Snapshot
+
+// The full state of one table at a point in time. Columns are restored into
+// the same column handles that compiled blocks hold, so blocks keep working
+// on the restored data.
+pub struct TableSnapshot {
+  pub table_id: u64,
+  table: Option<TableRef>,
+  rows: usize,
+  cols: usize,
+  col_kinds: Vec<ValueKind>,
+  col_map: AliasMap,
+  row_map: AliasMap,
+  data: Vec<Column>,
+  values: Vec<Value>,
+}
+
+impl TableSnapshot {
+
+  pub fn capture(database: &Database, table_id: u64) -> TableSnapshot {
+    match database.tables.get(&table_id) {
+      Some(table) => {
+        let table_brrw = table.borrow();
+        let mut values = Vec::with_capacity(table_brrw.rows * table_brrw.cols);
+        for row in 0..table_brrw.rows {
+          for col in 0..table_brrw.cols {
+            values.push(table_brrw.get_raw(row,col).unwrap_or(Value::Empty));
+          }
+        }
+        TableSnapshot {
+          table_id,
+          table: Some(table.clone()),
+          rows: table_brrw.rows,
+          cols: table_brrw.cols,
+          col_kinds: table_brrw.col_kinds.clone(),
+          col_map: table_brrw.col_map.clone(),
+          row_map: table_brrw.row_map.clone(),
+          data: table_brrw.data.clone(),
+          values,
+        }
+      }
+      None => TableSnapshot {
+        table_id,
+        table: None,
+        rows: 0,
+        cols: 0,
+        col_kinds: vec![],
+        col_map: AliasMap::new(0),
+        row_map: AliasMap::new(0),
+        data: vec![],
+        values: vec![],
+      }
+    }
+  }
+
+  pub fn restore(&self, database: &mut Database) {
+    match &self.table {
+      Some(table) => {
+        database.tables.insert(self.table_id, table.clone());
+        let mut table_brrw = table.borrow_mut();
+        table_brrw.rows = self.rows;
+        table_brrw.cols = self.cols;
+        table_brrw.col_kinds = self.col_kinds.clone();
+        table_brrw.col_map = self.col_map.clone();
+        table_brrw.row_map = self.row_map.clone();
+        table_brrw.data = self.data.clone();
+        // Empty columns have nothing to resize, and cells that couldn't be read
+        // when the snapshot was taken were recorded as empty, so errors from
+        // either are skipped.
+        for column in &table_brrw.data {
+          column.resize(self.rows).ok();
+        }
+        for row in 0..self.rows {
+          for col in 0..self.cols {
+            table_brrw.set_raw(row,col,self.values[row * self.cols + col].clone()).ok();
+          }
+        }
+      }
+      None => {
+        database.tables.remove(&self.table_id);
+      }
+    }
+  }
+
+  // Whether the table has been created since the snapshot, or its shape,
+  // column kinds or aliases have changed, none of which a Set can describe.
+  pub fn reshaped(&self, database: &Database) -> bool {
+    match database.tables.get(&self.table_id) {
+      Some(table) => {
+        let table_brrw = table.borrow();
+        self.table.is_none() ||
+        table_brrw.rows != self.rows ||
+        table_brrw.cols != self.cols ||
+        table_brrw.col_kinds != self.col_kinds ||
+        table_brrw.col_map.ix_to_alias != self.col_map.ix_to_alias ||
+        table_brrw.row_map.ix_to_alias != self.row_map.ix_to_alias
+      }
+      None => false,
+    }
+  }
+
+  // The cells of the table that differ from the snapshot, as a single Set. Any
+  // cell outside the snapshot's shape counts as changed. A table that's been
+  // removed since the snapshot gives a RemoveTable instead.
+  pub fn diff(&self, database: &Database) -> Option<Change> {
+    let table = match database.tables.get(&self.table_id) {
+      Some(table) => table,
+      None if self.table.is_some() => return Some(Change::RemoveTable{table_id: self.table_id}),
+      None => return None,
+    };
+    let values: Vec<(TableIndex,TableIndex,Value)> = self.changed_cells(&table.borrow()).into_iter().map(|(row,col,value)| {
+      (TableIndex::Index(row+1), TableIndex::Index(col+1), value)
+    }).collect();
+    match values.len() {
+      0 => None,
+      _ => Some(Change::Set((self.table_id, values))),
+    }
+  }
+
+  // The cells that changed since the snapshot, counting from zero, if the
+  // table is still the same table with the same shape. Setting them brings
+  // the snapshot's state up to date.
+  pub fn cells(&self, database: &Database) -> Option<Vec<(usize,usize,Value)>> {
+    match (&self.table, database.tables.get(&self.table_id)) {
+      (Some(table), Some(current)) if Rc::ptr_eq(table,current) && !self.reshaped(database) => {
+        Some(self.changed_cells(&current.borrow()))
+      }
+      _ => None,
+    }
+  }
+
+  fn changed_cells(&self, table: &Table) -> Vec<(usize,usize,Value)> {
+    let mut cells = vec![];
+    for row in 0..table.rows {
+      for col in 0..table.cols {
+        let value = match table.get_raw(row,col) {
+          Ok(value) => value,
+          Err(_) => continue,
+        };
+        let unchanged = self.table.is_some() && row < self.rows && col < self.cols && self.values[row * self.cols + col] == value;
+        if !unchanged {
+          cells.push((row,col,value));
+        }
+      }
+    }
+    cells
+  }
+
+  // The changes that bring a copy of the snapshot up to date with the table.
+  // A table that was created or reshaped since can't be described by a Set, so
+  // it's sent whole, after removing the stale copy if there was one.
+  pub fn changes(&self, database: &Database) -> Vec<Change> {
+    if self.reshaped(database) {
+      let mut changes = match self.table {
+        Some(_) => vec![Change::RemoveTable{table_id: self.table_id}],
+        None => vec![],
+      };
+      if let Some(table) = database.tables.get(&self.table_id) {
+        changes.append(&mut table.borrow().to_changes());
+      }
+      return changes;
+    }
+    self.diff(database).into_iter().collect()
+  }
+
+}
+
+// ## Write Log
+
+// The tables written since the log was started, as they were before the first
+// write to each. A table is only copied the first time it's written, so a
+// transaction pays for the tables its blocks actually write rather than every
+// table they could.
+pub struct WriteLog {
+  snapshots: Vec<TableSnapshot>,
+  captured: HashSet<u64>,
+  copy_tables: bool,
+}
+
+impl WriteLog {
+
+  pub fn new() -> WriteLog {
+    WriteLog {
+      snapshots: vec![],
+      captured: HashSet::new(),
+      copy_tables: true,
+    }
+  }
+
+  // A log of which tables were written, without copies of them, for when
+  // nothing needs to be restored. Storage uses it to sync just those tables.
+  pub fn tables_only() -> WriteLog {
+    WriteLog {
+      copy_tables: false,
+      ..WriteLog::new()
+    }
+  }
+
+  pub fn tables(&self) -> &HashSet<u64> {
+    &self.captured
+  }
+
+  pub fn capture(&mut self, database: &Database, table_id: u64) {
+    let table_id = match database.get_table_by_id(&table_id) {
+      Some(table) => table.borrow().id,
+      None => table_id,
+    };
+    if self.captured.insert(table_id) && self.copy_tables {
+      self.snapshots.push(TableSnapshot::capture(database, table_id));
+    }
+  }
+
+  // Called before a compiled block is solved.
+  pub fn capture_outputs(&mut self, database: &Database, block: &Block) {
+    for (table_id,_,_) in &block.output {
+      if let TableId::Global(id) = table_id {
+        self.capture(database, *id);
+      }
+    }
+  }
+
+  // Called before a block is compiled. Its outputs aren't known until then, so
+  // every global table its transformations write is captured.
+  pub fn capture_transformations(&mut self, database: &Database, tfms: &Vec<Transformation>) {
+    for tfm in tfms {
+      let table_id = match tfm {
+        Transformation::NewTable{table_id, ..} |
+        Transformation::Constant{table_id, ..} |
+        Transformation::ColumnKind{table_id, ..} |
+        Transformation::ColumnAlias{table_id, ..} |
+        Transformation::RowAlias{table_id, ..} |
+        Transformation::StateMachine{table_id, ..} |
+        Transformation::Set{dest_id: table_id, ..} |
+        Transformation::UpdateData{dest_id: table_id, ..} |
+        Transformation::Function{out: (table_id,_,_), ..} |
+        Transformation::TableDefine{out: table_id, ..} => table_id,
+        _ => continue,
+      };
+      if let TableId::Global(id) = table_id {
+        self.capture(database, *id);
+      }
+    }
+  }
+
+  // Tables are restored latest first, so a table written both before and after
+  // an earlier capture ends up as it was at the earliest one.
+  pub fn restore(&self, database: &mut Database) {
+    for snapshot in self.snapshots.iter().rev() {
+      snapshot.restore(database);
+    }
+  }
+
+  // The snapshots in order of table id, so diffs made from them come out in
+  // the same order every time.
+  pub fn into_snapshots(mut self) -> Vec<TableSnapshot> {
+    self.snapshots.sort_by_key(|snapshot| snapshot.table_id);
+    self.snapshots
+  }
+
+}
+
+// ## History
+
+#[derive(Debug, Clone)]
+pub enum HistoryEvent {
+  Transaction(Transaction),
+  Tick,
+}
+
+// What a step did to one table. A table that kept its shape is kept as the
+// cells that changed, and any other table in full.
+enum TableChange {
+  Cells(u64,Vec<(usize,usize,Value)>),
+  Table(TableSnapshot),
+}
+
+impl TableChange {
+
+  fn apply(&self, database: &mut Database) {
+    match self {
+      TableChange::Cells(table_id,cells) => {
+        if let Some(table) = database.tables.get(table_id) {
+          let table_brrw = table.borrow();
+          for (row,col,value) in cells {
+            table_brrw.set_raw(*row,*col,value.clone()).ok();
+          }
+        }
+      }
+      TableChange::Table(snapshot) => snapshot.restore(database),
+    }
+  }
+
+}
+
+pub struct HistoryEntry {
+  pub step: usize,
+  pub event: HistoryEvent,
+  before: Vec<TableSnapshot>,
+  after: Vec<TableChange>,
+}
+
+pub struct History {
+  pub capacity: usize,  // Maximum number of entries kept. Zero disables the history.
+  pub step: usize,      // Number of transactions and ticks applied, less any that were undone.
+  entries: VecDeque<HistoryEntry>,
+  applied: usize,       // Entries at or after this position have been undone and can be redone.
+}
+
+impl History {
+
+  pub fn new() -> History {
+    History {
+      capacity: 0,
+      step: 0,
+      entries: VecDeque::new(),
+      applied: 0,
+    }
+  }
+
+  pub fn enabled(&self) -> bool {
+    self.capacity > 0
+  }
+
+  // Records a step that has just been applied, given the snapshots taken before
+  // it. Anything that was undone is no longer redoable, and the oldest entry is
+  // dropped once the history is over capacity.
+  pub fn push(&mut self, event: HistoryEvent, before: Vec<TableSnapshot>, database: &Database) {
+    let after = before.iter().filter_map(|snapshot| match snapshot.cells(database) {
+      Some(cells) if cells.len() == 0 => None,
+      Some(cells) => Some(TableChange::Cells(snapshot.table_id,cells)),
+      None if snapshot.table.is_none() && !database.tables.contains_key(&snapshot.table_id) => None,
+      None => Some(TableChange::Table(TableSnapshot::capture(database, snapshot.table_id))),
+    }).collect();
+    self.entries.truncate(self.applied);
+    self.step += 1;
+    self.entries.push_back(HistoryEntry{step: self.step, event, before, after});
+    while self.entries.len() > self.capacity {
+      self.entries.pop_front();
+    }
+    self.applied = self.entries.len();
+  }
+
+  pub fn undo(&mut self, database: &mut Database) -> Result<usize,MechError> {
+    if self.applied == 0 {
+      return Err(MechError{msg: "".to_string(), id: 8001, kind: MechErrorKind::MissingStep(self.step.saturating_sub(1))});
+    }
+    self.applied -= 1;
+    let entry = &self.entries[self.applied];
+    for snapshot in &entry.before {
+      snapshot.restore(database);
+    }
+    self.step = entry.step - 1;
+    Ok(self.step)
+  }
+
+  pub fn redo(&mut self, database: &mut Database) -> Result<usize,MechError> {
+    if self.applied == self.entries.len() {
+      return Err(MechError{msg: "".to_string(), id: 8002, kind: MechErrorKind::MissingStep(self.step + 1)});
+    }
+    let entry = &self.entries[self.applied];
+    for change in &entry.after {
+      change.apply(database);
+    }
+    self.applied += 1;
+    self.step = entry.step;
+    Ok(self.step)
+  }
+
+  // The earliest and latest steps that can be reached with undo and redo.
+  pub fn steps(&self) -> (usize,usize) {
+    match (self.entries.front(), self.entries.back()) {
+      (Some(first), Some(last)) => (first.step - 1, last.step),
+      _ => (self.step, self.step),
+    }
+  }
+
+  pub fn rewind_to(&mut self, step: usize, database: &mut Database) -> Result<usize,MechError> {
+    let (first,last) = self.steps();
+    if step < first || step > last {
+      return Err(MechError{msg: "".to_string(), id: 8003, kind: MechErrorKind::MissingStep(step)});
+    }
+    while self.step > step {
+      self.undo(database)?;
+    }
+    while self.step < step {
+      self.redo(database)?;
+    }
+    Ok(self.step)
+  }
+
+  // The tables written by the steps after first, up to and including last.
+  pub fn tables_between(&self, first: usize, last: usize) -> HashSet<u64> {
+    self.entries.iter().filter(|entry| entry.step > first && entry.step <= last).flat_map(|entry| entry.before.iter().map(|snapshot| snapshot.table_id)).collect()
+  }
+
+  pub fn transactions(&self) -> Vec<&Transaction> {
+    self.entries.iter().take(self.applied).filter_map(|entry| match &entry.event {
+      HistoryEvent::Transaction(transaction) => Some(transaction),
+      HistoryEvent::Tick => None,
+    }).collect()
+  }
+
+}
+
+impl fmt::Debug for History {
+  #[inline]
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f,"History(step: {}, entries: {}, applied: {}, capacity: {})",self.step,self.entries.len(),self.applied,self.capacity)?;
+    Ok(())
+  }
+}
@@ -0,0 +1,91 @@
+// # Interface
+
+// The interface of a program is the set of global tables it needs from
+// outside, and the set it produces. Inputs are the tables the loaded blocks
+// read that no block defines, so they have to be supplied by the host (in a
+// transaction, or by a machine). Outputs are the global tables the blocks
+// write, including blocks that are still waiting for one of their inputs.
+
+// Each table is described with its name, shape and columns, as far as they're
+// known. An input that doesn't exist yet is listed with its name only. The
+// description derives serde's traits, so a host can encode it as JSON (with
+// serde_json, for example) and check it against what it's going to provide.
+
+// ## Prelude
+
+use crate::*;
+
+// ## Interface Description
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColumnInterface {
+  pub ix: usize,
+  pub alias: Option<String>,
+  pub kind: ValueKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TableInterface {
+  pub id: u64,
+  pub name: Option<String>,
+  pub exists: bool,
+  pub rows: usize,
+  pub cols: usize,
+  pub shape: Option<TableShape>,
+  pub columns: Vec<ColumnInterface>,
+}
+
+impl TableInterface {
+
+  // Describes a table that hasn't been created.
+  pub fn missing(id: u64, name: Option<String>) -> TableInterface {
+    TableInterface {
+      id,
+      name,
+      exists: false,
+      rows: 0,
+      cols: 0,
+      shape: None,
+      columns: vec![],
+    }
+  }
+
+  pub fn from_table(table: &Table, name: Option<String>, dictionary: &StringDictionary) -> TableInterface {
+    let dictionary_brrw = dictionary.borrow();
+    let columns = table.col_kinds.iter().enumerate().map(|(ix,kind)| {
+      let alias = match table.col_map.get_alias(&ix) {
+        Ok(0) | Err(_) => None,
+        Ok(alias) => match dictionary_brrw.get(&alias) {
+          Some(name) => Some(name.to_string()),
+          None => Some(humanize(&alias)),
+        },
+      };
+      ColumnInterface{ix, alias, kind: kind.clone()}
+    }).collect();
+    TableInterface {
+      id: table.id,
+      name,
+      exists: true,
+      rows: table.rows,
+      cols: table.cols,
+      shape: Some(table.shape()),
+      columns,
+    }
+  }
+
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProgramInterface {
+  pub inputs: Vec<TableInterface>,
+  pub outputs: Vec<TableInterface>,
+}
+
+impl ProgramInterface {
+
+  // Inputs that the host hasn't created yet.
+  pub fn missing_inputs(&self) -> Vec<&TableInterface> {
+    self.inputs.iter().filter(|table| !table.exists).collect()
+  }
+
+}
@@ -0,0 +1,117 @@
+// # Journal
+
+// A journal records everything that happens to a core, in order, so the run
+// can be reproduced somewhere else. Every transaction the core receives, every
+// block it loads (as the transformations the block was compiled from), every
+// block it removes, disables or enables, every change to its iteration limit,
+// every machine it registers, every table inserted directly and every tick is
+// appended as an entry. Each entry is numbered with its step in the journal,
+// and stamped with the time the core's time source read when the entry was
+// made. A core that already holds something when recording starts is written
+// as a snapshot first. Undo, redo and rewind are refused while recording.
+
+// Replaying a journal builds a new core on a manual clock, and applies the
+// entries in order with the clock set to their recorded time, so waits and
+// timers come out the same as in the recorded run. Replay can stop before a
+// given step, to look at the core as it was at that point.
+
+// A journal file starts with a short header, followed by one entry after
+// another, each a little-endian u32 length and a bincode payload. Entries are
+// flushed as they're written, and a journal cut short in the middle of an
+// entry (by a crash, for example) is read up to the last complete entry.
+
+// ## Prelude
+
+use crate::*;
+use std::fs::File;
+use std::io::{Read, Write};
+
+pub const JOURNAL_MAGIC: [u8;4] = *b"MJNL";
+pub const JOURNAL_VERSION: u32 = 1;
+
+// ## Journal Entries
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JournalEvent {
+  Transaction(Transaction),
+  Blocks(Vec<Vec<Transformation>>),
+  Tick,
+  RemoveBlock(BlockId),
+  DisableBlock(BlockId),
+  EnableBlock(BlockId),
+  MaxIterations(usize),
+  Machine(u64,Vec<u64>),  // (machine id, tables it subscribes to)
+  Snapshot(Vec<u8>),      // The core as it was when recording started
+  InsertTable(Vec<Change>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+  pub step: u64,
+  pub time: u64,
+  pub event: JournalEvent,
+}
+
+// ## Journal
+
+pub struct Journal {
+  file: File,
+  pub step: u64,
+  pub max_iterations: usize,  // Iteration limit as of the last entry. A replayed core starts with the default.
+}
+
+impl Journal {
+
+  // Starts a new journal at path, replacing any file already there.
+  pub fn create(path: &str) -> Result<Journal,MechError> {
+    let mut file = File::create(path)?;
+    file.write_all(&JOURNAL_MAGIC)?;
+    file.write_all(&JOURNAL_VERSION.to_le_bytes())?;
+    file.flush()?;
+    Ok(Journal{file, step: 0, max_iterations: crate::core::MAX_ITERATIONS})
+  }
+
+  pub fn record(&mut self, time: u64, event: JournalEvent) -> Result<(),MechError> {
+    let entry = JournalEntry{step: self.step, time, event};
+    let payload = match bincode::serialize(&entry) {
+      Ok(payload) => payload,
+      Err(x) => {return Err(MechError{msg: format!("{:?}", x), id: 8301, kind: MechErrorKind::InvalidJournal});}
+    };
+    self.file.write_all(&(payload.len() as u32).to_le_bytes())?;
+    self.file.write_all(&payload)?;
+    self.file.flush()?;
+    self.step += 1;
+    Ok(())
+  }
+
+  pub fn read(path: &str) -> Result<Vec<JournalEntry>,MechError> {
+    let mut bytes = vec![];
+    match File::open(path) {
+      Ok(mut file) => {file.read_to_end(&mut bytes)?;}
+      Err(_) => {return Err(MechError{msg: "".to_string(), id: 8302, kind: MechErrorKind::FileNotFound(path.to_string())});}
+    }
+    if bytes.len() < 8 || bytes[0..4] != JOURNAL_MAGIC {
+      return Err(MechError{msg: path.to_string(), id: 8303, kind: MechErrorKind::InvalidJournal});
+    }
+    let version = u32::from_le_bytes([bytes[4],bytes[5],bytes[6],bytes[7]]);
+    if version != JOURNAL_VERSION {
+      return Err(MechError{msg: path.to_string(), id: 8304, kind: MechErrorKind::JournalVersionMismatch(version,JOURNAL_VERSION)});
+    }
+    let mut entries = vec![];
+    let mut position = 8;
+    while position + 4 <= bytes.len() {
+      let length = u32::from_le_bytes([bytes[position],bytes[position+1],bytes[position+2],bytes[position+3]]) as usize;
+      let start = position + 4;
+      if start + length > bytes.len() {
+        break;
+      }
+      match bincode::deserialize(&bytes[start..start+length]) {
+        Ok(entry) => entries.push(entry),
+        Err(x) => {return Err(MechError{msg: format!("{:?}", x), id: 8305, kind: MechErrorKind::InvalidJournal});}
+      }
+      position = start + length;
+    }
+    Ok(entries)
+  }
+
+}
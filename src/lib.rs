@@ -15,8 +15,9 @@ extern crate num_traits;
 extern crate lazy_static;
 extern crate seahash;
 extern crate indexmap;
-use std::rc::Rc;
-use std::cell::RefCell;
+extern crate bincode;
+use crate::sync::Rc;
+use crate::sync::RefCell;
 use std::fmt;
 use num_traits::*;
 use std::ops::*;
@@ -34,6 +35,16 @@ pub mod function;
 mod block;
 mod core;
 mod schedule;
+mod history;
+mod snapshot;
+mod replication;
+mod profiler;
+mod temporal;
+mod state_machine;
+mod journal;
+mod interface;
+mod storage;
+pub mod sync;
 pub mod nodes;
 
 
@@ -49,6 +60,15 @@ pub use self::database::*;
 pub use self::function::*;
 pub use self::block::*;
 pub use self::schedule::*;
+pub use self::history::*;
+pub use self::snapshot::*;
+pub use self::replication::*;
+pub use self::profiler::*;
+pub use self::temporal::*;
+pub use self::state_machine::*;
+pub use self::journal::*;
+pub use self::interface::*;
+pub use self::storage::*;
 pub use self::user_functions::*;
 
 
@@ -79,13 +99,19 @@ pub enum SectionElement {
   UserFunction(UserFunction),
 }
 
-pub trait MechFunctionCompiler {
+pub trait MechFunctionCompiler: sync::Shareable {
   fn compile(&self, block: &mut Block, arguments: &Vec<Argument>, out: &Out) -> std::result::Result<(),MechError>;
 }
 
-pub trait MechFunction {
+pub trait MechFunction: sync::Shareable {
   fn solve(&self);
   fn to_string(&self) -> String;
+  // The kernel's type name, without its module path or type parameters.
+  fn name(&self) -> &'static str {
+    let name = std::any::type_name::<Self>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+  }
 }
 
 pub fn resize_one(block: &mut Block, out: &Out) -> std::result::Result<(),MechError> {
@@ -96,7 +122,7 @@ pub fn resize_one(block: &mut Block, out: &Out) -> std::result::Result<(),MechEr
   Ok(())
 }
 
-pub trait Machine {
+pub trait Machine: sync::Shareable {
   fn name(&self) -> String;
   fn id(&self) -> u64;
   fn on_change(&mut self, table: &Table) -> Result<(), MechError>;
@@ -305,13 +331,35 @@ impl BoxTable {
         strings[col][row] = value_string;
       }
     }
+    // Aliased rows get a column of their own on the left, with their names
+    let mut cols = table.cols;
+    if table.has_row_aliases() {
+      let mut labels = vec!["".to_string(); table.rows];
+      let mut label_width = 0;
+      for (row,label) in labels.iter_mut().enumerate() {
+        if let Ok(alias @ 1..) = table.row_map.get_alias(&row) {
+          *label = match table.dictionary.borrow().get(&alias) {
+            Some(alias_string) => alias_string.to_string(),
+            None => humanize(&alias),
+          };
+          label_width = label_width.max(label.chars().count());
+        }
+      }
+      strings.insert(0,labels);
+      column_widths.insert(0,label_width);
+      if column_aliases.len() > 0 {
+        column_aliases.insert(0,"".to_string());
+      }
+      column_kinds.insert(0,"".to_string());
+      cols += 1;
+    }
     let width = column_widths.iter().sum();
     if width == 0 {column_widths.push(0);}
     BoxTable {
       title,
       width,
       rows: table.rows,
-      cols: table.cols,
+      cols,
       column_aliases,
       column_kinds,
       strings,
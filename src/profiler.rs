@@ -0,0 +1,140 @@
+// # Profiler
+
+// The profiler records how long blocks take to solve. For every block it keeps
+// the number of times the block was solved and the total and longest time it
+// took, along with the same numbers for each step of the block's plan. It's off
+// by default, since timing every step has a cost of its own.
+
+// The numbers only ever cover the latest transaction, tick or enabled block,
+// which makes it easy to see which blocks made a particular tick miss its
+// deadline. When the profiler
+// is enabled on a core they're also written to the #mech/profile table, with
+// one row per block followed by a row for each of its steps:
+
+//   block   id of the block
+//   step    0 for the block as a whole, otherwise the step's position in the plan
+//   kernel  name of the function compiled for the step
+//   calls   number of times it was solved
+//   total   total time spent solving it, in milliseconds
+//   max     longest single solve, in milliseconds
+
+// ## Prelude
+
+use crate::*;
+use hashbrown::HashMap;
+use std::time::Duration;
+
+lazy_static! {
+  pub static ref MECH_PROFILE: u64 = hash_str("mech/profile");
+  pub static ref MECH_PROFILE_BLOCK: u64 = hash_str("block");
+  pub static ref MECH_PROFILE_STEP: u64 = hash_str("step");
+  pub static ref MECH_PROFILE_KERNEL: u64 = hash_str("kernel");
+  pub static ref MECH_PROFILE_CALLS: u64 = hash_str("calls");
+  pub static ref MECH_PROFILE_TOTAL: u64 = hash_str("total");
+  pub static ref MECH_PROFILE_MAX: u64 = hash_str("max");
+}
+
+// ## Profile Entries
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileEntry {
+  pub calls: usize,
+  pub total: Duration,
+  pub max: Duration,
+}
+
+impl ProfileEntry {
+
+  pub fn new() -> ProfileEntry {
+    ProfileEntry {
+      calls: 0,
+      total: Duration::new(0,0),
+      max: Duration::new(0,0),
+    }
+  }
+
+  pub fn record(&mut self, elapsed: Duration) {
+    self.calls += 1;
+    self.total += elapsed;
+    if elapsed > self.max {
+      self.max = elapsed;
+    }
+  }
+
+}
+
+// How long a single solve of a block took, and how that time was split across
+// the steps of its plan.
+#[derive(Clone, Debug)]
+pub struct BlockTiming {
+  pub block_id: BlockId,
+  pub elapsed: Duration,
+  pub steps: Vec<Duration>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockProfile {
+  pub block_id: BlockId,
+  pub block: ProfileEntry,
+  pub steps: Vec<ProfileEntry>,
+}
+
+// ## Profiler
+
+#[derive(Clone, Debug)]
+pub struct Profiler {
+  pub enabled: bool,
+  pub blocks: HashMap<BlockId,BlockProfile>,
+}
+
+impl Profiler {
+
+  pub fn new() -> Profiler {
+    Profiler {
+      enabled: false,
+      blocks: HashMap::new(),
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.blocks.clear();
+  }
+
+  pub fn record(&mut self, timing: BlockTiming) {
+    let profile = self.blocks.entry(timing.block_id).or_insert_with(|| BlockProfile {
+      block_id: timing.block_id,
+      block: ProfileEntry::new(),
+      steps: vec![],
+    });
+    profile.block.record(timing.elapsed);
+    if profile.steps.len() < timing.steps.len() {
+      profile.steps.resize(timing.steps.len(), ProfileEntry::new());
+    }
+    for (step,elapsed) in profile.steps.iter_mut().zip(timing.steps) {
+      step.record(elapsed);
+    }
+  }
+
+  // Solves a block that's run outside of a schedule, on a tick or when a
+  // state is entered for instance, timing it if the profiler is enabled. The
+  // block keeps the error if the solve fails.
+  pub fn solve(&mut self, block: &mut Block) -> Result<(),MechError> {
+    let solved = match self.enabled {
+      true => block.solve_profiled().map(Some),
+      false => block.solve().map(|_| None),
+    };
+    block.last_error = solved.as_ref().err().cloned();
+    if let Some(timing) = solved? {
+      self.record(timing);
+    }
+    Ok(())
+  }
+
+  // Blocks ordered from the most total time spent solving them to the least.
+  pub fn slowest_blocks(&self) -> Vec<&BlockProfile> {
+    let mut blocks: Vec<&BlockProfile> = self.blocks.values().collect();
+    blocks.sort_by(|a,b| b.block.total.cmp(&a.block.total).then(a.block_id.cmp(&b.block_id)));
+    blocks
+  }
+
+}
@@ -0,0 +1,174 @@
+// # Replication
+
+// Replication shares tables between cores over any byte stream. The core that
+// owns a set of tables publishes them, and a peer connected over the stream
+// subscribes to some of them. The subscriber first receives the current state
+// of each table it asked for, which replaces any copy it already has, and after
+// that the publisher forwards diffs as they happen.
+
+// Every message is sent as a little-endian u32 length followed by a bincode
+// payload. The length comes from the peer, so a message longer than
+// MAX_MESSAGE_SIZE is rejected before anything is allocated for it. A
+// connection starts with both sides exchanging Hello, and is only kept if the
+// protocol versions match.
+
+// ## Prelude
+
+use crate::*;
+use hashbrown::HashSet;
+use std::io::{Read, Write};
+
+pub const REPLICATION_VERSION: u32 = 1;
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+// ## Messages
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ReplicationMessage {
+  Hello{version: u32},
+  Subscribe(Vec<u64>),
+  Transaction(Transaction),
+  Rejected(MechError),
+}
+
+pub fn write_message<S: Write>(stream: &mut S, message: &ReplicationMessage) -> Result<(),MechError> {
+  let payload = match bincode::serialize(message) {
+    Ok(payload) => payload,
+    Err(x) => {return Err(MechError{msg: format!("{:?}", x), id: 8201, kind: MechErrorKind::InvalidMessage});}
+  };
+  if payload.len() > MAX_MESSAGE_SIZE {
+    return Err(MechError{msg: "".to_string(), id: 8209, kind: MechErrorKind::MessageTooLarge(payload.len(),MAX_MESSAGE_SIZE)});
+  }
+  stream.write_all(&(payload.len() as u32).to_le_bytes())?;
+  stream.write_all(&payload)?;
+  stream.flush()?;
+  Ok(())
+}
+
+pub fn read_message<S: Read>(stream: &mut S) -> Result<ReplicationMessage,MechError> {
+  let mut length = [0;4];
+  stream.read_exact(&mut length)?;
+  let length = u32::from_le_bytes(length) as usize;
+  if length > MAX_MESSAGE_SIZE {
+    return Err(MechError{msg: "".to_string(), id: 8208, kind: MechErrorKind::MessageTooLarge(length,MAX_MESSAGE_SIZE)});
+  }
+  let mut payload = vec![0;length];
+  stream.read_exact(&mut payload)?;
+  match bincode::deserialize(&payload) {
+    Ok(message) => Ok(message),
+    Err(x) => Err(MechError{msg: format!("{:?}", x), id: 8202, kind: MechErrorKind::InvalidMessage}),
+  }
+}
+
+fn check_version<S: Write>(stream: &mut S, message: ReplicationMessage) -> Result<(),MechError> {
+  match message {
+    ReplicationMessage::Hello{version} if version == REPLICATION_VERSION => Ok(()),
+    ReplicationMessage::Hello{version} => {
+      let error = MechError{msg: "".to_string(), id: 8203, kind: MechErrorKind::ProtocolVersionMismatch(version,REPLICATION_VERSION)};
+      write_message(stream, &ReplicationMessage::Rejected(error.clone()))?;
+      Err(error)
+    }
+    ReplicationMessage::Rejected(error) => Err(error),
+    _ => Err(MechError{msg: "".to_string(), id: 8204, kind: MechErrorKind::InvalidMessage}),
+  }
+}
+
+// ## Publisher
+
+// The publishing end of a connection, held by the core that owns the tables.
+pub struct Publisher<S: Read + Write> {
+  stream: S,
+  pub tables: HashSet<u64>,
+}
+
+impl<S: Read + Write> Publisher<S> {
+
+  // Waits for a peer's handshake and subscription, then sends the current
+  // state of every requested table. Only tables in published can be
+  // subscribed to.
+  pub fn accept(mut stream: S, core: &Core, published: &HashSet<u64>) -> Result<Publisher<S>,MechError> {
+    let hello = read_message(&mut stream)?;
+    check_version(&mut stream, hello)?;
+    write_message(&mut stream, &ReplicationMessage::Hello{version: REPLICATION_VERSION})?;
+    let requested = match read_message(&mut stream)? {
+      ReplicationMessage::Subscribe(tables) => tables,
+      _ => {return Err(MechError{msg: "".to_string(), id: 8205, kind: MechErrorKind::InvalidMessage});}
+    };
+    let mut tables = HashSet::new();
+    let mut initial_state = vec![];
+    for table_id in requested {
+      let table = match core.get_table_by_id(table_id) {
+        Ok(table) if published.contains(&table_id) || published.contains(&table.borrow().id) => table,
+        _ => {
+          let error = MechError{msg: humanize(&table_id), id: 8206, kind: MechErrorKind::MissingTable(TableId::Global(table_id))};
+          write_message(&mut stream, &ReplicationMessage::Rejected(error.clone()))?;
+          return Err(error);
+        }
+      };
+      let table_brrw = table.borrow();
+      tables.insert(table_brrw.id);
+      initial_state.append(&mut table_brrw.to_changes());
+    }
+    write_message(&mut stream, &ReplicationMessage::Transaction(initial_state))?;
+    Ok(Publisher{stream, tables})
+  }
+
+  // Forwards the parts of a diff that touch subscribed tables, whatever kind
+  // of change they are, so a subscribed table that's reshaped, removed or
+  // created again is mirrored too. Nothing is sent if none of them changed.
+  pub fn send(&mut self, diff: &Transaction) -> Result<(),MechError> {
+    let changes: Transaction = diff.iter().filter(|change| self.tables.contains(&change.table_id())).cloned().collect();
+    if changes.len() > 0 {
+      write_message(&mut self.stream, &ReplicationMessage::Transaction(changes))?;
+    }
+    Ok(())
+  }
+
+}
+
+// ## Subscriber
+
+// The subscribing end of a connection, held by the core that mirrors the
+// tables.
+pub struct Subscriber<S: Read + Write> {
+  stream: S,
+  pub tables: Vec<u64>,
+  received: bool,   // Whether the initial state has been received
+}
+
+impl<S: Read + Write> Subscriber<S> {
+
+  pub fn connect(mut stream: S, tables: Vec<u64>) -> Result<Subscriber<S>,MechError> {
+    write_message(&mut stream, &ReplicationMessage::Hello{version: REPLICATION_VERSION})?;
+    let hello = read_message(&mut stream)?;
+    check_version(&mut stream, hello)?;
+    write_message(&mut stream, &ReplicationMessage::Subscribe(tables.clone()))?;
+    Ok(Subscriber{stream, tables, received: false})
+  }
+
+  // Blocks until the next transaction arrives from the publisher and applies
+  // it to the core. The first one received holds the initial state of the
+  // subscribed tables, which creates each of them anew, so a table the core
+  // already has (from an earlier connection, say) is removed in the same
+  // transaction first.
+  pub fn receive(&mut self, core: &mut Core) -> Result<Transaction,MechError> {
+    match read_message(&mut self.stream)? {
+      ReplicationMessage::Transaction(mut txn) => {
+        if !self.received {
+          let mut initial_state: Transaction = txn.iter().filter_map(|change| match change {
+            Change::NewTable{table_id, ..} if core.get_table_by_id(*table_id).is_ok() => Some(Change::RemoveTable{table_id: *table_id}),
+            _ => None,
+          }).collect();
+          initial_state.append(&mut txn);
+          txn = initial_state;
+        }
+        core.process_transaction(&txn)?;
+        self.received = true;
+        Ok(txn)
+      }
+      ReplicationMessage::Rejected(error) => Err(error),
+      _ => Err(MechError{msg: "".to_string(), id: 8207, kind: MechErrorKind::InvalidMessage}),
+    }
+  }
+
+}
@@ -1,288 +1,799 @@
-
-use hashbrown::{HashMap, HashSet};
-use crate::core::BlockRef;
-use crate::*;
-
-#[derive(Clone)]
-pub struct Schedule {
-  pub trigger_to_blocks: HashMap<(TableId,RegisterIndex,RegisterIndex),Vec<BlockGraph>>,
-  pub input_to_blocks: HashMap<(TableId,RegisterIndex,RegisterIndex),Vec<BlockGraph>>,
-  pub output_to_blocks: HashMap<(TableId,RegisterIndex,RegisterIndex),Vec<BlockGraph>>,
-  pub trigger_to_output: HashMap<(TableId,RegisterIndex,RegisterIndex),HashSet<(TableId,RegisterIndex,RegisterIndex)>>,
-  pub schedules: HashMap<(TableId,RegisterIndex,RegisterIndex),Vec<BlockGraph>>, // Block Graph is list of blocks that will trigger in order when the given register is set
-  unscheduled_blocks: Vec<BlockRef>,
-}
-
-impl Schedule {
-
-  pub fn new() -> Schedule {
-    Schedule {
-      trigger_to_blocks: HashMap::new(),
-      input_to_blocks: HashMap::new(),
-      output_to_blocks: HashMap::new(),
-      trigger_to_output: HashMap::new(),
-      schedules: HashMap::new(),
-      unscheduled_blocks: Vec::new(),
-    }
-  }
- 
-  pub fn add_block(&mut self, block_ref: BlockRef) -> Result<(),MechError> {
-
-    self.unscheduled_blocks.push(block_ref);
-
-    Ok(())
-  }
-
-
-  pub fn schedule_blocks(&mut self) -> Result<(),MechError> {
-    if  self.unscheduled_blocks.len() == 0 {
-      return Ok(())
-    }
-    let ready_blocks: Vec<BlockRef> = self.unscheduled_blocks.drain_filter(|b| b.borrow().state == BlockState::Ready).collect();
-
-    for block_ref in &ready_blocks {
-      let mut graph = BlockGraph::new(block_ref.clone());
-      let block_brrw = block_ref.borrow();
-
-      // Map trigger registers to blocks
-      for (trigger_table_id,row,col) in &block_brrw.triggers {
-        let ref mut dependent_blocks = self.trigger_to_blocks.entry((*trigger_table_id,*row,*col)).or_insert(vec![]);
-        dependent_blocks.push(graph.clone());
-        let ref mut dependent_blocks = self.schedules.entry((*trigger_table_id,*row,*col)).or_insert(vec![]);
-        dependent_blocks.push(graph.clone());
-
-        for ((output_table_id,row,col),ref mut producing_blocks) in self.output_to_blocks.iter_mut() {
-          if output_table_id == trigger_table_id {
-            for ref mut pblock in producing_blocks.iter_mut() {
-              pblock.add_child(&mut graph);
-            }
-          }
-        }
-      }
-
-      // Map input registers to blocks
-      for (input_table_id,row,col) in &block_brrw.input {
-        let ref mut consuming_blocks = self.input_to_blocks.entry((*input_table_id,*row,*col)).or_insert(vec![]);
-        consuming_blocks.push(graph.clone());
-      }
-
-      // Map output registers to blocks
-      for (output_table_id,row,col) in &block_brrw.output {
-        let ref mut producing_blocks = self.output_to_blocks.entry((*output_table_id,*row,*col)).or_insert(vec![]);
-        producing_blocks.push(graph.clone());
-        // Map block outputs to triggers
-        if let Some(consuming_blocks) = self.trigger_to_blocks.get(&(*output_table_id,*row,*col)) {
-          for block in consuming_blocks {
-            graph.add_child(&block);
-          }
-        }
-      }
-    }
-    // This collects all of the output that would be changed given a trigger
-    // TODO I'd like to do this incrementally instead of redoing it
-    // every time blocks are scheduled. But I'm short on time now and 
-    // this is all I can think of to do without changing too much.
-    for (register,block_graphs) in self.schedules.iter() {
-      let (table_id,row_ix,col_ix) = register;
-      let mut aggregate_output = HashSet::new();
-      for graph in block_graphs {
-        let mut node = &graph.root;
-        let node_brrw = node.borrow();
-        let mut output = node_brrw.aggregate_output();
-        aggregate_output = aggregate_output.union(&mut output).cloned().collect();
-      }
-      self.trigger_to_output.insert(register.clone(),aggregate_output);
-    }
-    Ok(())
-  }
-
-  pub fn run_schedule(&mut self, register: &(TableId,RegisterIndex,RegisterIndex)) -> Result<(),MechError> {
-    match self.schedules.get_mut(register) {
-      Some(ref mut block_graphs) => {
-        for ref mut block_graph in block_graphs.iter_mut() {
-          block_graph.solve();
-        }
-        Ok(())
-      }
-      None => {
-        Err(MechError{msg: "".to_string(), id: 5368, kind: MechErrorKind::GenericError(format!("No schedule assocaited with {:?}", register))})
-      }
-    }
-  }
-}
-
-impl fmt::Debug for Schedule {
-  #[inline]
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    let mut box_drawing = BoxPrinter::new();
-    box_drawing.add_header("triggers");
-    box_drawing.add_line(format!("{:#?}", &self.trigger_to_blocks));
-    box_drawing.add_header("input");
-    box_drawing.add_line(format!("{:#?}", &self.input_to_blocks));
-    box_drawing.add_header("output");
-    box_drawing.add_line(format!("{:#?}", &self.output_to_blocks));
-    box_drawing.add_header("output schedule");
-    box_drawing.add_line(format!("{:#?}", &self.trigger_to_output));
-    box_drawing.add_header("schedules");
-    box_drawing.add_line(format!("{:#?}", &self.schedules));
-    if self.unscheduled_blocks.len() > 0 {
-      box_drawing.add_header("unscheduled blocks");
-      box_drawing.add_line(format!("{:#?}", &self.unscheduled_blocks.iter().map(|b| humanize(&b.borrow().id)).collect::<Vec<String>>()));
-    }
-    write!(f,"{:?}",box_drawing)?;
-    Ok(())
-  }
-}
-
-
-#[derive(Clone)]
-pub struct Node {
-  block: BlockRef,
-  parents: Vec<Rc<RefCell<Node>>>,
-  children: Vec<Rc<RefCell<Node>>>,
-}
-
-impl Node {
-
-  pub fn new(block: BlockRef) -> Node {
-    Node {
-      block: block,
-      parents: Vec::new(),
-      children: Vec::new(),
-    }
-  }
-
-  pub fn recompile(&self) -> Result<(),MechError> {
-    {
-      self.block.borrow_mut().recompile()?;
-    }
-    for child in &self.children {
-      let mut child_brrw = child.borrow_mut();
-      child_brrw.recompile()?;
-    }
-    Ok(())
-  }
-
-  pub fn triggers(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
-    self.block.borrow().triggers.clone()
-  }
-
-  pub fn input(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
-    self.block.borrow().input.clone()
-  }
-
-  pub fn output(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
-    self.block.borrow().output.clone()
-  }
-
-  pub fn aggregate_output(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
-    let mut aggregate_output = self.output();
-    let mut child_output = self.output_recurse();
-    aggregate_output = aggregate_output.union(&mut child_output).cloned().collect();
-    aggregate_output
-  }
-
-  pub fn output_recurse(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
-    let mut aggregate_output = HashSet::new();
-    for child in &self.children {
-      let child_brrw = child.borrow();
-      let mut output = child_brrw.output();
-      let mut child_output = child_brrw.output_recurse();
-      aggregate_output = aggregate_output.union(&mut output).cloned().collect();
-      aggregate_output = aggregate_output.union(&mut child_output).cloned().collect();
-    }
-    aggregate_output
-  }
-
-  pub fn add_child(&mut self, child: Rc<RefCell<Node>>) {
-    self.children.push(child);
-  }
-
-  pub fn add_parent(&mut self, parent: Rc<RefCell<Node>>) {
-    self.parents.push(parent);
-  }
-
-  pub fn solve(&mut self) -> Result<(),MechError> {
-    self.block.borrow_mut().solve()?;
-    for ref mut child in &mut self.children {
-      child.borrow_mut().solve()?;
-    }
-    Ok(())
-  }
-
-}
-
-impl fmt::Debug for Node {
-  #[inline]
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f,"[{}]",humanize(&self.block.borrow().id))?;
-    for child in &self.children {
-      write!(f,"->{:?}\n",&child.borrow())?;
-    }
-    Ok(())
-  }
-}
-
-#[derive(Clone)]
-pub struct BlockGraph {
-  pub root: Rc<RefCell<Node>>,
-}
-
-impl BlockGraph {
-
-  pub fn new(block: BlockRef) -> BlockGraph {
-    let node = Rc::new(RefCell::new(Node::new(block)));
-    BlockGraph {
-      root: node,
-    }
-  }
-
-  pub fn id(&self) -> u64 {
-    self.root.borrow().block.borrow().id
-  }
-
-  pub fn recompile_blocks(&self) -> Result<(),MechError> {
-    let root_brrw = self.root.borrow();
-    root_brrw.recompile()?;
-    Ok(())
-  }
-
-  pub fn triggers(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
-    self.root.borrow().triggers()
-  }
-
-  pub fn input(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
-    self.root.borrow().input()
-  }
-
-  pub fn output(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
-    self.root.borrow().output()
-  }
-
-  pub fn add_child(&mut self, block: &BlockGraph) -> Result<(),MechError> {
-    {
-      let mut root_block = self.root.borrow_mut();
-      let rc = block.root.clone();
-      root_block.add_child(rc);
-    }
-    {
-      let mut child_block = block.root.borrow_mut();
-      child_block.add_parent(self.root.clone());
-    }
-    Ok(())
-  }
-
-  pub fn solve(&mut self) -> Result<(),MechError> {
-    self.root.borrow_mut().solve()
-  }
-
-
-}
-
-impl fmt::Debug for BlockGraph {
-  #[inline]
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f,"{:?}",self.root.borrow())?;
-    Ok(())
-  }
-}
\ No newline at end of file
+
+use hashbrown::{HashMap, HashSet};
+use crate::core::BlockRef;
+use crate::*;
+#[cfg(feature = "sync")]
+use rayon::prelude::*;
+
+#[derive(Clone)]
+pub struct Schedule {
+  pub trigger_to_blocks: HashMap<(TableId,RegisterIndex,RegisterIndex),Vec<BlockGraph>>,
+  pub input_to_blocks: HashMap<(TableId,RegisterIndex,RegisterIndex),Vec<BlockGraph>>,
+  pub output_to_blocks: HashMap<(TableId,RegisterIndex,RegisterIndex),Vec<BlockGraph>>,
+  pub schedules: HashMap<(TableId,RegisterIndex,RegisterIndex),Vec<BlockGraph>>, // Block Graph is list of blocks that will trigger in order when the given register is set
+  pub table_to_triggers: HashMap<TableId,HashSet<(TableId,RegisterIndex,RegisterIndex)>>,
+  pub table_to_outputs: HashMap<TableId,HashSet<(TableId,RegisterIndex,RegisterIndex)>>,
+  pub profiler: Profiler,
+  pub state_machines: HashMap<u64,StateMachine>,
+  unscheduled_blocks: Vec<BlockRef>,
+  pub cyclic_blocks: Vec<BlockRef>, // New blocks taken out of the schedule because they closed a cycle
+  trigger_to_order: HashMap<(TableId,RegisterIndex,RegisterIndex),Vec<BlockRef>>, // Every block reachable from a trigger, in the order they are solved
+  trigger_to_output: HashMap<(TableId,RegisterIndex,RegisterIndex),HashSet<(TableId,RegisterIndex,RegisterIndex)>>,
+  block_to_triggers: HashMap<BlockId,HashSet<(TableId,RegisterIndex,RegisterIndex)>>, // The triggers whose known order includes each block
+}
+
+impl Schedule {
+
+  pub fn new() -> Schedule {
+    Schedule {
+      trigger_to_blocks: HashMap::new(),
+      input_to_blocks: HashMap::new(),
+      output_to_blocks: HashMap::new(),
+      schedules: HashMap::new(),
+      table_to_triggers: HashMap::new(),
+      table_to_outputs: HashMap::new(),
+      profiler: Profiler::new(),
+      state_machines: HashMap::new(),
+      unscheduled_blocks: Vec::new(),
+      cyclic_blocks: Vec::new(),
+      trigger_to_order: HashMap::new(),
+      trigger_to_output: HashMap::new(),
+      block_to_triggers: HashMap::new(),
+    }
+  }
+ 
+  pub fn add_block(&mut self, block_ref: BlockRef) -> Result<(),MechError> {
+    self.unscheduled_blocks.push(block_ref);
+
+    Ok(())
+  }
+
+  // A transition or entry action can't be compiled before its machine's table
+  // exists, so the machine is always registered by the time its rules are.
+  fn add_state_machine_rules(&mut self, block_ref: &BlockRef) {
+    let block_brrw = block_ref.borrow();
+    for tfm in &block_brrw.transformations {
+      match tfm {
+        Transformation::StateMachine{table_id: TableId::Global(table_id), initial} => {
+          self.state_machines.insert(*table_id, StateMachine::new(*table_id, *initial, block_ref.clone()));
+        }
+        Transformation::StateTransition{table_id: TableId::Global(table_id), from, to, guard, indices} => {
+          if let Some(machine) = self.state_machines.get_mut(table_id) {
+            machine.transitions.push(StateTransition{from: *from, to: *to, guard: *guard, indices: indices.clone(), block: block_ref.clone()});
+          }
+        }
+        Transformation::OnEnter{table_id: TableId::Global(table_id), state} => {
+          if let Some(machine) = self.state_machines.get_mut(table_id) {
+            machine.entry_actions.entry(*state).or_insert(vec![]).push(block_ref.clone());
+          }
+        }
+        _ => (),
+      }
+    }
+  }
+
+  // Moves a state machine into a state, and returns the registers written by
+  // the machine and by the entry actions that ran.
+  pub fn enter_state(&mut self, table_id: u64, state: u64) -> Result<HashSet<(TableId,RegisterIndex,RegisterIndex)>,MechError> {
+    let mut registers = HashSet::new();
+    if let Some(machine) = self.state_machines.get_mut(&table_id) {
+      registers.extend(machine.enter(state, &mut self.profiler)?);
+      registers.insert((TableId::Global(table_id),RegisterIndex::All,RegisterIndex::All));
+    }
+    Ok(registers)
+  }
+
+
+  pub fn schedule_blocks(&mut self) -> Result<(),MechError> {
+    if  self.unscheduled_blocks.len() == 0 {
+      return Ok(())
+    }
+    let ready_blocks: Vec<BlockRef> = self.unscheduled_blocks.drain_filter(|b| b.borrow().state == BlockState::Ready).collect();
+    for block_ref in &ready_blocks {
+      self.add_state_machine_rules(block_ref);
+    }
+
+    let mut graphs = vec![];
+    for block_ref in &ready_blocks {
+      let mut graph = BlockGraph::new(block_ref.clone());
+      let block_brrw = block_ref.borrow();
+
+      // Map trigger registers to blocks
+      for (trigger_table_id,row,col) in &block_brrw.triggers {
+        let ref mut dependent_blocks = self.trigger_to_blocks.entry((*trigger_table_id,*row,*col)).or_insert(vec![]);
+        dependent_blocks.push(graph.clone());
+        let ref mut dependent_blocks = self.schedules.entry((*trigger_table_id,*row,*col)).or_insert(vec![]);
+        dependent_blocks.push(graph.clone());
+        self.table_to_triggers.entry(*trigger_table_id).or_insert(HashSet::new()).insert((*trigger_table_id,*row,*col));
+
+        if let Some(output_registers) = self.table_to_outputs.get(trigger_table_id) {
+          for output_register in output_registers {
+            if registers_overlap(output_register,&(*trigger_table_id,*row,*col)) {
+              for ref mut pblock in self.output_to_blocks.get_mut(output_register).unwrap().iter_mut() {
+                pblock.add_child(&mut graph);
+              }
+            }
+          }
+        }
+      }
+
+      // Map input registers to blocks
+      for (input_table_id,row,col) in &block_brrw.input {
+        let ref mut consuming_blocks = self.input_to_blocks.entry((*input_table_id,*row,*col)).or_insert(vec![]);
+        consuming_blocks.push(graph.clone());
+      }
+
+      // Map output registers to blocks
+      for (output_table_id,row,col) in &block_brrw.output {
+        let ref mut producing_blocks = self.output_to_blocks.entry((*output_table_id,*row,*col)).or_insert(vec![]);
+        producing_blocks.push(graph.clone());
+        self.table_to_outputs.entry(*output_table_id).or_insert(HashSet::new()).insert((*output_table_id,*row,*col));
+        // Map block outputs to triggers. A block never triggers itself.
+        if let Some(trigger_registers) = self.table_to_triggers.get(output_table_id) {
+          for trigger_register in trigger_registers {
+            if registers_overlap(&(*output_table_id,*row,*col),trigger_register) {
+              for block in &self.trigger_to_blocks[trigger_register] {
+                if block.id() != block_brrw.id {
+                  graph.add_child(&block);
+                }
+              }
+            }
+          }
+        }
+      }
+      graphs.push(graph);
+    }
+    // A new block changes the solve order of every trigger that reaches it,
+    // which are its own triggers and the triggers that reach one of its
+    // parents. Those orders are worked out again the next time they're needed.
+    // The new blocks on a cycle are taken back out of the schedule, so the
+    // blocks that were already scheduled keep running as they did, and the
+    // cycle's error is returned.
+    let mut new_cycles: Vec<Vec<BlockId>> = vec![];
+    for (graph,block_ref) in graphs.iter().zip(&ready_blocks) {
+      for trigger in &block_ref.borrow().triggers {
+        self.trigger_to_order.remove(trigger);
+        self.trigger_to_output.remove(trigger);
+      }
+      let parent_ids: Vec<BlockId> = graph.root.borrow().parents.iter().map(|parent| parent.borrow().block.borrow().id).collect();
+      for parent_id in parent_ids {
+        self.forget_orders(parent_id);
+      }
+      if new_cycles.iter().any(|cycle| cycle.contains(&graph.id())) {
+        continue;
+      }
+      if let Some(cycle) = find_cycle(&graph.root) {
+        new_cycles.push(cycle);
+      }
+    }
+    for block_ref in &ready_blocks {
+      let block_id = block_ref.borrow().id;
+      if new_cycles.iter().any(|cycle| cycle.contains(&block_id)) {
+        self.remove_block(block_id);
+        self.cyclic_blocks.push(block_ref.clone());
+      }
+    }
+    match new_cycles.first() {
+      Some(cycle) => {
+        let names = cycle.iter().map(|id| humanize(id)).collect::<Vec<String>>().join(" -> ");
+        Err(MechError{msg: names, id: 5369, kind: MechErrorKind::CyclicDependency(cycle.clone())})
+      }
+      None => Ok(()),
+    }
+  }
+
+  // Forgets the orders that include a block, so they're worked out again the
+  // next time they're needed.
+  fn forget_orders(&mut self, block_id: BlockId) {
+    if let Some(triggers) = self.block_to_triggers.remove(&block_id) {
+      for trigger in triggers {
+        self.trigger_to_order.remove(&trigger);
+        self.trigger_to_output.remove(&trigger);
+      }
+    }
+  }
+
+  // Works out the solve order and aggregate output of the schedule for one
+  // trigger register, unless they're already known.
+  fn order_schedule(&mut self, register: &(TableId,RegisterIndex,RegisterIndex)) {
+    if self.trigger_to_order.contains_key(register) {
+      return;
+    }
+    let roots = match self.schedules.get(register) {
+      Some(block_graphs) => block_graphs.iter().map(|graph| graph.root.clone()).collect(),
+      None => vec![],
+    };
+    let (order, _) = topological_order(&roots);
+    let mut aggregate_output = HashSet::new();
+    for block_ref in &order {
+      let block_brrw = block_ref.borrow();
+      aggregate_output.extend(block_brrw.output.iter().cloned());
+      self.block_to_triggers.entry(block_brrw.id).or_insert(HashSet::new()).insert(*register);
+    }
+    self.trigger_to_output.insert(*register,aggregate_output);
+    self.trigger_to_order.insert(*register,order);
+  }
+
+  // Every block reached from a trigger register, in the order they're solved.
+  pub fn trigger_order(&mut self, register: &(TableId,RegisterIndex,RegisterIndex)) -> &Vec<BlockRef> {
+    self.order_schedule(register);
+    &self.trigger_to_order[register]
+  }
+
+  // Every register written by the blocks reached from a trigger register.
+  pub fn trigger_output(&mut self, register: &(TableId,RegisterIndex,RegisterIndex)) -> &HashSet<(TableId,RegisterIndex,RegisterIndex)> {
+    self.order_schedule(register);
+    &self.trigger_to_output[register]
+  }
+
+  // Takes a block out of every map and detaches its node from the blocks it
+  // was linked to.
+  pub fn remove_block(&mut self, block_id: BlockId) {
+    self.unscheduled_blocks.retain(|block_ref| block_ref.borrow().id != block_id);
+    self.state_machines.retain(|_,machine| machine.block.borrow().id != block_id);
+    for machine in self.state_machines.values_mut() {
+      machine.transitions.retain(|transition| transition.block.borrow().id != block_id);
+      for actions in machine.entry_actions.values_mut() {
+        actions.retain(|block_ref| block_ref.borrow().id != block_id);
+      }
+    }
+    let mut node = None;
+    for block_graphs in [&mut self.trigger_to_blocks, &mut self.input_to_blocks, &mut self.output_to_blocks, &mut self.schedules] {
+      for graphs in block_graphs.values_mut() {
+        graphs.retain(|graph| match graph.id() == block_id {
+          true => {
+            node = Some(graph.root.clone());
+            false
+          }
+          false => true,
+        });
+      }
+      block_graphs.retain(|_,graphs| graphs.len() > 0);
+    }
+    if let Some(node) = node {
+      let node_brrw = node.borrow();
+      for parent in &node_brrw.parents {
+        parent.borrow_mut().children.retain(|child| !Rc::ptr_eq(child,&node));
+      }
+      for child in &node_brrw.children {
+        child.borrow_mut().parents.retain(|parent| !Rc::ptr_eq(parent,&node));
+      }
+    }
+    for triggers in self.table_to_triggers.values_mut() {
+      triggers.retain(|trigger| self.trigger_to_blocks.contains_key(trigger));
+    }
+    self.table_to_triggers.retain(|_,triggers| triggers.len() > 0);
+    for outputs in self.table_to_outputs.values_mut() {
+      outputs.retain(|output| self.output_to_blocks.contains_key(output));
+    }
+    self.table_to_outputs.retain(|_,outputs| outputs.len() > 0);
+    self.forget_orders(block_id);
+  }
+
+  // Nodes hold their parents and children, so the graph is made of reference
+  // cycles that are never freed on their own. This unlinks every node, which
+  // frees the graph once the maps holding it are dropped.
+  pub fn detach_nodes(&mut self) {
+    for block_graphs in [&self.trigger_to_blocks, &self.input_to_blocks, &self.output_to_blocks, &self.schedules] {
+      for graph in block_graphs.values().flatten() {
+        let mut node = graph.root.borrow_mut();
+        node.parents.clear();
+        node.children.clear();
+      }
+    }
+  }
+
+  // All of the trigger registers that fire when the given register changes.
+  pub fn triggered_registers(&self, register: &(TableId,RegisterIndex,RegisterIndex)) -> Vec<(TableId,RegisterIndex,RegisterIndex)> {
+    let (table_id,_,_) = register;
+    match self.table_to_triggers.get(table_id) {
+      Some(triggers) => triggers.iter().filter(|trigger| registers_overlap(register,trigger)).cloned().collect(),
+      None => vec![],
+    }
+  }
+
+  pub fn run_schedule(&mut self, register: &(TableId,RegisterIndex,RegisterIndex)) -> Result<(),MechError> {
+    if self.triggered_registers(register).len() == 0 {
+      return Err(MechError{msg: "".to_string(), id: 5368, kind: MechErrorKind::GenericError(format!("No schedule assocaited with {:?}", register))});
+    }
+    let mut registers = HashSet::new();
+    registers.insert(*register);
+    self.run_schedules(&registers)?;
+    Ok(())
+  }
+
+  // Runs the schedules triggered by a set of changed registers. Every affected
+  // block is solved exactly once, after all of the blocks that feed into it.
+  // Disabled and suspended blocks are skipped.
+  //
+  // Blocks that feed back into a block that has already been solved this round
+  // (including into themselves) can't be handled in the same round. Their output
+  // is compared before and after solving, and the registers that actually changed
+  // are returned so the caller can start another round with them.
+  //
+  // With the "sync" feature, blocks that don't share any global tables are
+  // solved in parallel. See solve_levels for how the order is split up.
+  pub fn run_schedules(&mut self, registers: &HashSet<(TableId,RegisterIndex,RegisterIndex)>) -> Result<HashSet<(TableId,RegisterIndex,RegisterIndex)>,MechError> {
+    let order = self.solve_order(registers);
+    self.run_order(&order)
+  }
+
+  // The blocks triggered by a set of changed registers, in the order they're
+  // solved.
+  pub fn solve_order(&mut self, registers: &HashSet<(TableId,RegisterIndex,RegisterIndex)>) -> Vec<BlockRef> {
+    let mut triggered = vec![];
+    for register in registers {
+      for trigger in self.triggered_registers(register) {
+        if !triggered.contains(&trigger) {
+          triggered.push(trigger);
+        }
+      }
+    }
+    match triggered.as_slice() {
+      [] => vec![],
+      [trigger] => self.trigger_order(trigger).clone(),
+      triggers => {
+        let mut roots = vec![];
+        for trigger in triggers {
+          if let Some(block_graphs) = self.schedules.get(trigger) {
+            roots.extend(block_graphs.iter().map(|graph| graph.root.clone()));
+          }
+        }
+        let (order,_) = topological_order(&roots);
+        order
+      }
+    }
+  }
+
+  // Solves the blocks of a solve order, and returns the feedback registers that
+  // changed.
+  pub fn run_order(&mut self, order: &Vec<BlockRef>) -> Result<HashSet<(TableId,RegisterIndex,RegisterIndex)>,MechError> {
+    let positions: HashMap<BlockId,usize> = order.iter().enumerate().map(|(ix,block_ref)| (block_ref.borrow().id,ix)).collect();
+    let mut changed_feedback = HashSet::new();
+    #[cfg(not(feature = "sync"))]
+    for (ix,block_ref) in order.iter().enumerate() {
+      let (changed,timing) = self.solve_block(block_ref, ix, &positions)?;
+      changed_feedback.extend(changed);
+      if let Some(timing) = timing {
+        self.profiler.record(timing);
+      }
+    }
+    #[cfg(feature = "sync")]
+    for level in solve_levels(&order) {
+      let results: Vec<Result<(Vec<(TableId,RegisterIndex,RegisterIndex)>,Option<BlockTiming>),MechError>> = level.par_iter().map(|ix| self.solve_block(&order[*ix], *ix, &positions)).collect();
+      for result in results {
+        let (changed,timing) = result?;
+        changed_feedback.extend(changed);
+        if let Some(timing) = timing {
+          self.profiler.record(timing);
+        }
+      }
+    }
+    Ok(changed_feedback)
+  }
+
+  // Solves one block of a solve order, and returns its feedback registers that
+  // changed as a result. The block is timed if the profiler is enabled.
+  fn solve_block(&self, block_ref: &BlockRef, position: usize, positions: &HashMap<BlockId,usize>) -> Result<(Vec<(TableId,RegisterIndex,RegisterIndex)>,Option<BlockTiming>),MechError> {
+    block_ref.borrow_mut().sample_edges();
+    let block_brrw = block_ref.borrow();
+    if block_brrw.state == BlockState::Disabled || block_brrw.is_suspended() {
+      return Ok((vec![],None));
+    }
+    let feedback = self.feedback_registers(&block_brrw, position, positions);
+    let mut before = vec![];
+    for register in &feedback {
+      before.push(snapshot(&block_brrw, register));
+    }
+    let solved = match self.profiler.enabled {
+      true => block_brrw.solve_profiled().map(Some),
+      false => block_brrw.solve().map(|_| None),
+    };
+    drop(block_brrw);
+    block_ref.borrow_mut().last_error = solved.as_ref().err().cloned();
+    let timing = solved?;
+    let block_brrw = block_ref.borrow();
+    let mut changed = vec![];
+    for (register,before) in feedback.iter().zip(before) {
+      if snapshot(&block_brrw, register) != before {
+        changed.push(*register);
+      }
+    }
+    Ok((changed,timing))
+  }
+
+  // Output registers of a block that trigger a block at or before it in the
+  // current solve order.
+  fn feedback_registers(&self, block: &Block, position: usize, positions: &HashMap<BlockId,usize>) -> Vec<(TableId,RegisterIndex,RegisterIndex)> {
+    let mut feedback = vec![];
+    for register in &block.output {
+      let feeds_back = self.triggered_registers(register).iter().any(|trigger| {
+        match self.schedules.get(trigger) {
+          Some(block_graphs) => block_graphs.iter().any(|graph| {
+            match positions.get(&graph.id()) {
+              Some(consumer_position) => *consumer_position <= position,
+              None => false,
+            }
+          }),
+          None => false,
+        }
+      });
+      if feeds_back {
+        feedback.push(*register);
+      }
+    }
+    feedback
+  }
+}
+
+// Splits a solve order into levels of blocks that can be solved at the same
+// time. A block goes in the level after the last block it conflicts with,
+// which is one that writes a global table it reads or writes, or reads a
+// global table it writes. Blocks that write the same table are still solved
+// one after another in their original order, so the result is the same as
+// solving the whole order sequentially.
+#[cfg(feature = "sync")]
+fn solve_levels(order: &Vec<BlockRef>) -> Vec<Vec<usize>> {
+  let mut levels: Vec<Vec<usize>> = vec![];
+  let mut last_read: HashMap<u64,usize> = HashMap::new();
+  let mut last_write: HashMap<u64,usize> = HashMap::new();
+  for (ix,block_ref) in order.iter().enumerate() {
+    let block_brrw = block_ref.borrow();
+    let global = |registers: &HashSet<(TableId,RegisterIndex,RegisterIndex)>| -> HashSet<u64> {
+      registers.iter().filter_map(|(table_id,_,_)| match table_id {
+        TableId::Global(id) => Some(*id),
+        TableId::Local(_) => None,
+      }).collect()
+    };
+    let reads = global(&block_brrw.input);
+    let writes = global(&block_brrw.output);
+    let mut level = 0;
+    for table_id in &reads {
+      if let Some(write_level) = last_write.get(table_id) {
+        level = level.max(write_level + 1);
+      }
+    }
+    for table_id in &writes {
+      if let Some(write_level) = last_write.get(table_id) {
+        level = level.max(write_level + 1);
+      }
+      if let Some(read_level) = last_read.get(table_id) {
+        level = level.max(read_level + 1);
+      }
+    }
+    for table_id in reads {
+      let read_level = last_read.entry(table_id).or_insert(level);
+      *read_level = (*read_level).max(level);
+    }
+    for table_id in writes {
+      last_write.insert(table_id, level);
+    }
+    if levels.len() <= level {
+      levels.push(vec![]);
+    }
+    levels[level].push(ix);
+  }
+  levels
+}
+
+// The shape and contents of the table behind a register, used to tell whether
+// solving a block changed it.
+fn snapshot(block: &Block, register: &(TableId,RegisterIndex,RegisterIndex)) -> Option<(usize,usize,Vec<Value>)> {
+  let table = match register {
+    (TableId::Global(id),_,_) => block.global_database.borrow().get_table_by_id(id).cloned(),
+    (TableId::Local(id),_,_) => block.tables.get_table_by_id(id).cloned(),
+  };
+  match table {
+    Some(table) => {
+      let table_brrw = table.borrow();
+      let values = (0..table_brrw.len()).filter_map(|ix| table_brrw.get_linear(ix).ok()).collect();
+      Some((table_brrw.rows,table_brrw.cols,values))
+    }
+    None => None,
+  }
+}
+
+impl fmt::Debug for Schedule {
+  #[inline]
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut box_drawing = BoxPrinter::new();
+    box_drawing.add_header("triggers");
+    box_drawing.add_line(format!("{:#?}", &self.trigger_to_blocks));
+    box_drawing.add_header("input");
+    box_drawing.add_line(format!("{:#?}", &self.input_to_blocks));
+    box_drawing.add_header("output");
+    box_drawing.add_line(format!("{:#?}", &self.output_to_blocks));
+    box_drawing.add_header("output schedule");
+    box_drawing.add_line(format!("{:#?}", &self.trigger_to_output));
+    box_drawing.add_header("schedules");
+    box_drawing.add_line(format!("{:#?}", &self.schedules));
+    if self.unscheduled_blocks.len() > 0 {
+      box_drawing.add_header("unscheduled blocks");
+      box_drawing.add_line(format!("{:#?}", &self.unscheduled_blocks.iter().map(|b| humanize(&b.borrow().id)).collect::<Vec<String>>()));
+    }
+    write!(f,"{:?}",box_drawing)?;
+    Ok(())
+  }
+}
+
+
+// Two registers overlap if they are on the same table and their rows and
+// columns either match, or one of them covers the whole dimension.
+pub fn registers_overlap(a: &(TableId,RegisterIndex,RegisterIndex), b: &(TableId,RegisterIndex,RegisterIndex)) -> bool {
+  let (a_table,a_row,a_col) = a;
+  let (b_table,b_row,b_col) = b;
+  let row_overlaps = *a_row == RegisterIndex::All || *b_row == RegisterIndex::All || a_row == b_row;
+  let col_overlaps = *a_col == RegisterIndex::All || *b_col == RegisterIndex::All || a_col == b_col;
+  a_table == b_table && row_overlaps && col_overlaps
+}
+
+#[derive(Clone)]
+pub struct Node {
+  block: BlockRef,
+  parents: Vec<Rc<RefCell<Node>>>,
+  children: Vec<Rc<RefCell<Node>>>,
+}
+
+impl Node {
+
+  pub fn new(block: BlockRef) -> Node {
+    Node {
+      block: block,
+      parents: Vec::new(),
+      children: Vec::new(),
+    }
+  }
+
+  pub fn recompile(&self) -> Result<(),MechError> {
+    self.block.borrow_mut().recompile()
+  }
+
+  pub fn triggers(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
+    self.block.borrow().triggers.clone()
+  }
+
+  pub fn input(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
+    self.block.borrow().input.clone()
+  }
+
+  pub fn output(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
+    self.block.borrow().output.clone()
+  }
+
+  pub fn aggregate_output(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
+    let mut aggregate_output = self.output();
+    let mut child_output = self.output_recurse();
+    aggregate_output = aggregate_output.union(&mut child_output).cloned().collect();
+    aggregate_output
+  }
+
+  pub fn output_recurse(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
+    let mut aggregate_output = HashSet::new();
+    let (order,_) = topological_order(&self.children);
+    for block_ref in &order {
+      let mut output = block_ref.borrow().output.clone();
+      aggregate_output = aggregate_output.union(&mut output).cloned().collect();
+    }
+    aggregate_output
+  }
+
+  pub fn add_child(&mut self, child: Rc<RefCell<Node>>) {
+    self.children.push(child);
+  }
+
+  pub fn add_parent(&mut self, parent: Rc<RefCell<Node>>) {
+    self.parents.push(parent);
+  }
+
+  pub fn solve(&mut self) -> Result<(),MechError> {
+    self.block.borrow_mut().solve()
+  }
+
+}
+
+impl fmt::Debug for Node {
+  #[inline]
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f,"[{}]",humanize(&self.block.borrow().id))?;
+    for child in &self.children {
+      write!(f,"->[{}]\n",humanize(&child.borrow().block.borrow().id))?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Clone)]
+pub struct BlockGraph {
+  pub root: Rc<RefCell<Node>>,
+}
+
+impl BlockGraph {
+
+  pub fn new(block: BlockRef) -> BlockGraph {
+    let node = Rc::new(RefCell::new(Node::new(block)));
+    BlockGraph {
+      root: node,
+    }
+  }
+
+  pub fn id(&self) -> u64 {
+    self.root.borrow().block.borrow().id
+  }
+
+  pub fn recompile_blocks(&self) -> Result<(),MechError> {
+    let (order,_) = topological_order(&vec![self.root.clone()]);
+    for block_ref in &order {
+      block_ref.borrow_mut().recompile()?;
+    }
+    Ok(())
+  }
+
+  pub fn triggers(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
+    self.root.borrow().triggers()
+  }
+
+  pub fn input(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
+    self.root.borrow().input()
+  }
+
+  pub fn output(&self) -> HashSet<(TableId,RegisterIndex,RegisterIndex)> {
+    self.root.borrow().output()
+  }
+
+  pub fn add_child(&mut self, block: &BlockGraph) -> Result<(),MechError> {
+    {
+      let mut root_block = self.root.borrow_mut();
+      let rc = block.root.clone();
+      root_block.add_child(rc);
+    }
+    {
+      let mut child_block = block.root.borrow_mut();
+      child_block.add_parent(self.root.clone());
+    }
+    Ok(())
+  }
+
+  pub fn solve(&mut self) -> Result<(),MechError> {
+    let (order,_) = topological_order(&vec![self.root.clone()]);
+    for block_ref in &order {
+      block_ref.borrow().solve()?;
+    }
+    Ok(())
+  }
+
+
+}
+
+impl fmt::Debug for BlockGraph {
+  #[inline]
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f,"{:?}",self.root.borrow())?;
+    Ok(())
+  }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+  InProgress,
+  Done,
+}
+
+// Orders every block reachable from the given nodes so that each block comes
+// after all of the blocks that feed into it. An edge that would close a cycle
+// is skipped, and the blocks on that cycle are returned alongside the order.
+pub fn topological_order(roots: &Vec<Rc<RefCell<Node>>>) -> (Vec<BlockRef>,Vec<Vec<BlockId>>) {
+  let mut visits = HashMap::new();
+  let mut path = vec![];
+  let mut order = vec![];
+  let mut cycles = vec![];
+  for root in roots {
+    visit_node(root, &mut visits, &mut path, &mut order, &mut cycles);
+  }
+  order.reverse();
+  (order,cycles)
+}
+
+// Looks for a cycle through a node, and returns the blocks on it. The blocks
+// downstream and upstream of the node are searched a step at a time in turn,
+// and the search stops as soon as either side runs out. A block added to
+// either end of a long chain is checked without walking the whole chain.
+fn find_cycle(node: &Rc<RefCell<Node>>) -> Option<Vec<BlockId>> {
+  let mut downstream = CycleSearch::new(node, true);
+  let mut upstream = CycleSearch::new(node, false);
+  loop {
+    for search in [&mut downstream, &mut upstream] {
+      match search.step() {
+        SearchStep::Searching => (),
+        SearchStep::Exhausted => return None,
+        SearchStep::Cycle(cycle) => return Some(cycle),
+      }
+    }
+  }
+}
+
+enum SearchStep {
+  Searching,
+  Exhausted,
+  Cycle(Vec<BlockId>),
+}
+
+struct CycleSearch {
+  start: BlockId,
+  downstream: bool,
+  stack: Vec<Rc<RefCell<Node>>>,
+  came_from: HashMap<BlockId,BlockId>,
+}
+
+impl CycleSearch {
+
+  fn new(node: &Rc<RefCell<Node>>, downstream: bool) -> CycleSearch {
+    CycleSearch {
+      start: node.borrow().block.borrow().id,
+      downstream,
+      stack: vec![node.clone()],
+      came_from: HashMap::new(),
+    }
+  }
+
+  // Follows the edges of one node. Blocks on a cycle are returned in the
+  // direction of the edges, starting and ending with the start block.
+  fn step(&mut self) -> SearchStep {
+    let node = match self.stack.pop() {
+      Some(node) => node,
+      None => return SearchStep::Exhausted,
+    };
+    let node_brrw = node.borrow();
+    let id = node_brrw.block.borrow().id;
+    let next_nodes = match self.downstream {
+      true => &node_brrw.children,
+      false => &node_brrw.parents,
+    };
+    for next_node in next_nodes {
+      let next_id = next_node.borrow().block.borrow().id;
+      if next_id == self.start {
+        let mut path = vec![id];
+        let mut at = id;
+        while at != self.start {
+          at = self.came_from[&at];
+          path.push(at);
+        }
+        let cycle = match self.downstream {
+          true => path.iter().rev().cloned().chain(Some(self.start)).collect(),
+          false => Some(self.start).into_iter().chain(path).collect(),
+        };
+        return SearchStep::Cycle(cycle);
+      }
+      if !self.came_from.contains_key(&next_id) {
+        self.came_from.insert(next_id, id);
+        self.stack.push(next_node.clone());
+      }
+    }
+    SearchStep::Searching
+  }
+
+}
+
+fn visit_node(node: &Rc<RefCell<Node>>, visits: &mut HashMap<BlockId,Visit>, path: &mut Vec<BlockId>, order: &mut Vec<BlockRef>, cycles: &mut Vec<Vec<BlockId>>) {
+  let node_brrw = node.borrow();
+  let id = node_brrw.block.borrow().id;
+  match visits.get(&id) {
+    Some(Visit::Done) => return,
+    Some(Visit::InProgress) => {
+      if let Some(start) = path.iter().position(|path_id| *path_id == id) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(id);
+        cycles.push(cycle);
+      }
+      return;
+    }
+    None => (),
+  }
+  visits.insert(id, Visit::InProgress);
+  path.push(id);
+  for child in &node_brrw.children {
+    visit_node(child, visits, path, order, cycles);
+  }
+  path.pop();
+  visits.insert(id, Visit::Done);
+  order.push(node_brrw.block.clone());
+}
@@ -0,0 +1,53 @@
+// # Snapshot
+
+// A snapshot holds everything needed to rebuild a running core: its global
+// tables, table aliases, string dictionary, and the transformations of every
+// loaded block. It's encoded as a short header followed by a bincode payload.
+// The header carries a format version, so a snapshot written by an
+// incompatible runtime is rejected instead of misread.
+
+// ## Prelude
+
+use crate::*;
+
+pub const SNAPSHOT_MAGIC: [u8;4] = *b"MECH";
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+// ## Core Snapshot
+
+#[derive(Serialize, Deserialize)]
+pub struct CoreSnapshot {
+  pub tables: Vec<Vec<Change>>,
+  pub table_aliases: Vec<(u64,TableId)>,
+  pub dictionary: Vec<(u64,MechString)>,
+  pub blocks: Vec<Vec<Transformation>>,
+  pub max_iterations: usize,
+}
+
+impl CoreSnapshot {
+
+  pub fn to_bytes(&self) -> Result<Vec<u8>,MechError> {
+    let mut bytes = SNAPSHOT_MAGIC.to_vec();
+    bytes.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+    match bincode::serialize(self) {
+      Ok(mut payload) => bytes.append(&mut payload),
+      Err(x) => {return Err(MechError{msg: format!("{:?}", x), id: 8101, kind: MechErrorKind::InvalidSnapshot});}
+    }
+    Ok(bytes)
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<CoreSnapshot,MechError> {
+    if bytes.len() < 8 || bytes[0..4] != SNAPSHOT_MAGIC {
+      return Err(MechError{msg: "".to_string(), id: 8102, kind: MechErrorKind::InvalidSnapshot});
+    }
+    let version = u32::from_le_bytes([bytes[4],bytes[5],bytes[6],bytes[7]]);
+    if version != SNAPSHOT_VERSION {
+      return Err(MechError{msg: "".to_string(), id: 8103, kind: MechErrorKind::SnapshotVersionMismatch(version,SNAPSHOT_VERSION)});
+    }
+    match bincode::deserialize(&bytes[8..]) {
+      Ok(snapshot) => Ok(snapshot),
+      Err(x) => Err(MechError{msg: format!("{:?}", x), id: 8104, kind: MechErrorKind::InvalidSnapshot}),
+    }
+  }
+
+}
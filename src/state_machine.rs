@@ -0,0 +1,107 @@
+// # State Machines
+
+// A state machine is a global table holding the name of a current state,
+// along with transition rules that move it between states. Each rule goes from
+// one state to another, and is guarded by a boolean table. Blocks can be
+// marked as entry actions of a state, in which case they're solved every time
+// the machine enters it and never otherwise.
+
+// Transitions are evaluated once per step, after every triggered block has
+// run. A machine takes at most one transition per step, which is the first
+// rule (in the order the rules were loaded) that leaves the current state and
+// whose guard holds. Newly loaded machines enter their initial state on the
+// first step after they're loaded.
+
+// The state is read back from the table on every step, so writing a state's
+// name to the table moves the machine there without running entry actions.
+
+// ## Prelude
+
+use crate::*;
+use crate::core::BlockRef;
+use hashbrown::{HashMap, HashSet};
+
+// ## State Machine
+
+#[derive(Clone)]
+pub struct StateTransition {
+  pub from: u64,
+  pub to: u64,
+  pub guard: TableId,
+  pub indices: Vec<(TableIndex, TableIndex)>,
+  pub block: BlockRef,   // The block the transition was defined in, which guard tables are read through
+}
+
+#[derive(Clone)]
+pub struct StateMachine {
+  pub table_id: u64,
+  pub initial: u64,
+  pub block: BlockRef,   // The block that defines the machine
+  pub transitions: Vec<StateTransition>,
+  pub entry_actions: HashMap<u64,Vec<BlockRef>>,
+  entered: bool,
+}
+
+impl StateMachine {
+
+  pub fn new(table_id: u64, initial: u64, block: BlockRef) -> StateMachine {
+    StateMachine {
+      table_id,
+      initial,
+      block,
+      transitions: Vec::new(),
+      entry_actions: HashMap::new(),
+      entered: false,
+    }
+  }
+
+  // The value a state is stored as in the machine's table.
+  pub fn state_value(&self, state: u64) -> Value {
+    let name = match self.block.borrow().strings.borrow().get(&state) {
+      Some(name) => name.to_string(),
+      None => humanize(&state),
+    };
+    Value::String(MechString::from_string(name))
+  }
+
+  fn table(&self) -> Result<TableRef,MechError> {
+    self.block.borrow().get_table(&TableId::Global(self.table_id))
+  }
+
+  pub fn current_state(&self) -> Result<Value,MechError> {
+    self.table()?.borrow().get_raw(0,0)
+  }
+
+  // The state the machine moves to this step, if any.
+  pub fn next_state(&self) -> Result<Option<u64>,MechError> {
+    if !self.entered {
+      return Ok(Some(self.initial));
+    }
+    let current = self.current_state()?;
+    for transition in &self.transitions {
+      if self.state_value(transition.from) == current && transition.block.borrow().condition_holds(&transition.guard, &transition.indices) {
+        return Ok(Some(transition.to));
+      }
+    }
+    Ok(None)
+  }
+
+  // Moves the machine to a state and solves the state's entry actions. Returns
+  // the output registers of the actions that ran.
+  pub fn enter(&mut self, state: u64, profiler: &mut Profiler) -> Result<HashSet<(TableId,RegisterIndex,RegisterIndex)>,MechError> {
+    self.table()?.borrow().set_raw(0,0,self.state_value(state))?;
+    self.entered = true;
+    let mut output = HashSet::new();
+    if let Some(actions) = self.entry_actions.get(&state) {
+      for block_ref in actions {
+        let mut block_brrw = block_ref.borrow_mut();
+        if block_brrw.state == BlockState::Ready {
+          profiler.solve(&mut block_brrw)?;
+          output.extend(block_brrw.output.iter().cloned());
+        }
+      }
+    }
+    Ok(output)
+  }
+
+}
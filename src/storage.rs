@@ -0,0 +1,285 @@
+// # Storage
+
+// Storage keeps the tables marked persistent in a directory on disk, so they
+// survive the process ending, whether it exits cleanly or crashes. The
+// directory holds two files. The write-ahead log gets a record every time a
+// transaction or tick changes a persistent table, holding the changes that
+// bring the stored tables up to date. The snapshot holds the full contents of
+// every persistent table as of the last compaction. Compaction writes a new
+// snapshot and starts an empty log. It happens every snapshot_interval records,
+// or whenever the host asks for it.
+
+// Each log record is a little-endian u32 length, a u64 checksum of the payload
+// and a bincode payload, and it's synced to disk before the write returns.
+// Recovery applies the snapshot, then the log up to the last record that's
+// complete and matches its checksum, so a record cut short by a crash is
+// dropped (and cut off the file, so new records follow the last good one).
+// The snapshot is written to a temporary file and renamed into place. Both
+// files carry a generation number, so a log left over from a compaction that
+// was interrupted isn't applied on top of the newer snapshot.
+
+// ## Prelude
+
+use crate::*;
+use hashbrown::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const STORAGE_MAGIC: [u8;4] = *b"MWAL";
+pub const STORAGE_VERSION: u32 = 1;
+pub const STORAGE_SNAPSHOT_INTERVAL: usize = 1000;
+
+const LOG_FILE: &str = "wal";
+const SNAPSHOT_FILE: &str = "snapshot";
+
+// ## Storage Records
+
+// Each entry of a Tables record holds the changes for one table. An entry that
+// starts with NewTable rewrites the table from scratch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum StorageRecord {
+  Persist(u64),
+  Tables(Vec<Vec<Change>>),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StorageSnapshot {
+  pub generation: u64,
+  pub persistent: Vec<u64>,
+  pub tables: Vec<Vec<Change>>,
+}
+
+// ## Storage
+
+pub struct Storage {
+  path: PathBuf,
+  log: File,
+  pub generation: u64,
+  pub persistent: HashSet<u64>,
+  pub records: usize,
+  pub snapshot_interval: usize,
+  stored: HashMap<u64,TableSnapshot>,
+}
+
+impl Storage {
+
+  // Opens the storage in the directory at path, creating it if it doesn't
+  // exist. Returns the records to apply to an empty core to recover the stored
+  // tables, starting with the snapshot.
+  pub fn open(path: &str) -> Result<(Storage,Vec<StorageRecord>),MechError> {
+    let path = PathBuf::from(path);
+    fs::create_dir_all(&path)?;
+    let snapshot = match fs::read(path.join(SNAPSHOT_FILE)) {
+      Ok(bytes) => Storage::read_snapshot(&bytes)?,
+      Err(_) => StorageSnapshot{generation: 0, persistent: vec![], tables: vec![]},
+    };
+    let mut records: Vec<StorageRecord> = snapshot.persistent.iter().map(|table_id| StorageRecord::Persist(*table_id)).collect();
+    if snapshot.tables.len() > 0 {
+      records.push(StorageRecord::Tables(snapshot.tables));
+    }
+    let log_path = path.join(LOG_FILE);
+    let (mut log_records, valid_len) = match fs::read(&log_path) {
+      Ok(bytes) => Storage::read_log(&bytes, snapshot.generation)?,
+      Err(_) => (vec![], 0),
+    };
+    let log = match valid_len {
+      0 => Storage::create_log(&path, snapshot.generation)?,
+      _ => {
+        let log = OpenOptions::new().write(true).open(&log_path)?;
+        log.set_len(valid_len as u64)?;
+        log.sync_all()?;
+        OpenOptions::new().append(true).open(&log_path)?
+      }
+    };
+    let storage = Storage {
+      path,
+      log,
+      generation: snapshot.generation,
+      persistent: HashSet::new(),
+      records: log_records.len(),
+      snapshot_interval: STORAGE_SNAPSHOT_INTERVAL,
+      stored: HashMap::new(),
+    };
+    records.append(&mut log_records);
+    Ok((storage,records))
+  }
+
+  fn read_snapshot(bytes: &[u8]) -> Result<StorageSnapshot,MechError> {
+    if bytes.len() < 8 || bytes[0..4] != STORAGE_MAGIC {
+      return Err(MechError{msg: "".to_string(), id: 8401, kind: MechErrorKind::InvalidStorage});
+    }
+    let version = u32::from_le_bytes([bytes[4],bytes[5],bytes[6],bytes[7]]);
+    if version != STORAGE_VERSION {
+      return Err(MechError{msg: "".to_string(), id: 8402, kind: MechErrorKind::StorageVersionMismatch(version,STORAGE_VERSION)});
+    }
+    match bincode::deserialize(&bytes[8..]) {
+      Ok(snapshot) => Ok(snapshot),
+      Err(x) => Err(MechError{msg: format!("{:?}", x), id: 8403, kind: MechErrorKind::InvalidStorage}),
+    }
+  }
+
+  // Returns the records in the log, and the length of the file up to the end
+  // of the last good record. A log from another generation has no records and
+  // a length of zero, so it's replaced.
+  fn read_log(bytes: &[u8], generation: u64) -> Result<(Vec<StorageRecord>,usize),MechError> {
+    if bytes.len() < 16 || bytes[0..4] != STORAGE_MAGIC {
+      return Ok((vec![],0));
+    }
+    let version = u32::from_le_bytes([bytes[4],bytes[5],bytes[6],bytes[7]]);
+    if version != STORAGE_VERSION {
+      return Err(MechError{msg: "".to_string(), id: 8404, kind: MechErrorKind::StorageVersionMismatch(version,STORAGE_VERSION)});
+    }
+    let mut log_generation = [0;8];
+    log_generation.copy_from_slice(&bytes[8..16]);
+    if u64::from_le_bytes(log_generation) != generation {
+      return Ok((vec![],0));
+    }
+    let mut records = vec![];
+    let mut position = 16;
+    while position + 12 <= bytes.len() {
+      let length = u32::from_le_bytes([bytes[position],bytes[position+1],bytes[position+2],bytes[position+3]]) as usize;
+      let mut checksum = [0;8];
+      checksum.copy_from_slice(&bytes[position+4..position+12]);
+      let start = position + 12;
+      if start + length > bytes.len() || seahash::hash(&bytes[start..start+length]) != u64::from_le_bytes(checksum) {
+        break;
+      }
+      match bincode::deserialize(&bytes[start..start+length]) {
+        Ok(record) => records.push(record),
+        Err(_) => break,
+      }
+      position = start + length;
+    }
+    Ok((records,position))
+  }
+
+  // Writes an empty log for the generation, and opens it for appending.
+  fn create_log(path: &Path, generation: u64) -> Result<File,MechError> {
+    let temp_path = path.join(format!("{}.tmp", LOG_FILE));
+    let mut file = File::create(&temp_path)?;
+    file.write_all(&STORAGE_MAGIC)?;
+    file.write_all(&STORAGE_VERSION.to_le_bytes())?;
+    file.write_all(&generation.to_le_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path.join(LOG_FILE))?;
+    Storage::sync_dir(path);
+    Ok(OpenOptions::new().append(true).open(path.join(LOG_FILE))?)
+  }
+
+  // Makes a rename durable. Not every platform can sync a directory, and the
+  // rename itself is still atomic, so a failure here is ignored.
+  fn sync_dir(path: &Path) {
+    if let Ok(dir) = File::open(path) {
+      dir.sync_all().ok();
+    }
+  }
+
+  fn append(&mut self, record: &StorageRecord) -> Result<(),MechError> {
+    let payload = match bincode::serialize(record) {
+      Ok(payload) => payload,
+      Err(x) => {return Err(MechError{msg: format!("{:?}", x), id: 8405, kind: MechErrorKind::InvalidStorage});}
+    };
+    let mut bytes = Vec::with_capacity(payload.len() + 12);
+    bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&seahash::hash(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    // A record that isn't written whole is cut off again, so the records
+    // written after it can still be read back.
+    let length = self.log.metadata()?.len();
+    if let Err(x) = self.log.write_all(&bytes).and_then(|_| self.log.sync_data()) {
+      self.log.set_len(length).ok();
+      return Err(x.into());
+    }
+    self.records += 1;
+    Ok(())
+  }
+
+  // Starts comparing the persistent tables against their current contents.
+  pub fn track(&mut self, database: &Database) {
+    self.stored = self.persistent.iter().map(|table_id| (*table_id,TableSnapshot::capture(database, *table_id))).collect();
+  }
+
+  // Marks a table as persistent and stores its current contents. The table
+  // doesn't have to exist yet, it's stored once it's created.
+  pub fn persist(&mut self, table_id: u64, database: &Database) -> Result<(),MechError> {
+    if !self.persistent.insert(table_id) {
+      return Ok(());
+    }
+    self.append(&StorageRecord::Persist(table_id))?;
+    if let Some(table) = database.tables.get(&table_id) {
+      self.append(&StorageRecord::Tables(vec![table.borrow().to_changes()]))?;
+    }
+    self.stored.insert(table_id, TableSnapshot::capture(database, table_id));
+    Ok(())
+  }
+
+  // Logs the changes to the given tables since they were last stored, as one
+  // record. The core passes the tables it wrote, so only those are compared,
+  // and any that aren't persistent are skipped. A table with the same shape is
+  // stored as the cells that changed, and any other table is stored in full.
+  pub fn sync(&mut self, database: &Database, table_ids: &HashSet<u64>) -> Result<(),MechError> {
+    let mut table_ids: Vec<u64> = table_ids.iter().map(|table_id| match database.get_table_by_id(table_id) {
+      Some(table) => table.borrow().id,
+      None => *table_id,
+    }).filter(|table_id| self.persistent.contains(table_id)).collect();
+    table_ids.sort();
+    table_ids.dedup();
+    let mut tables = vec![];
+    let mut changed = vec![];
+    for table_id in table_ids {
+      let changes = match self.stored.get(&table_id) {
+        Some(stored) if !stored.reshaped(database) => match stored.diff(database) {
+          Some(change) => vec![change],
+          None => continue,
+        },
+        _ => match database.tables.get(&table_id) {
+          Some(table) => table.borrow().to_changes(),
+          None => continue,
+        },
+      };
+      tables.push(changes);
+      changed.push(table_id);
+    }
+    if tables.len() == 0 {
+      return Ok(());
+    }
+    self.append(&StorageRecord::Tables(tables))?;
+    for table_id in changed {
+      self.stored.insert(table_id, TableSnapshot::capture(database, table_id));
+    }
+    Ok(())
+  }
+
+  pub fn compaction_due(&self) -> bool {
+    self.snapshot_interval > 0 && self.records >= self.snapshot_interval
+  }
+
+  // Replaces the snapshot with the current contents of the persistent tables,
+  // and starts an empty log.
+  pub fn compact(&mut self, database: &Database) -> Result<(),MechError> {
+    let generation = self.generation + 1;
+    let mut persistent: Vec<u64> = self.persistent.iter().cloned().collect();
+    persistent.sort();
+    let tables = persistent.iter().filter_map(|table_id| database.tables.get(table_id)).map(|table| table.borrow().to_changes()).collect();
+    let snapshot = StorageSnapshot{generation, persistent, tables};
+    let mut bytes = STORAGE_MAGIC.to_vec();
+    bytes.extend_from_slice(&STORAGE_VERSION.to_le_bytes());
+    match bincode::serialize(&snapshot) {
+      Ok(mut payload) => bytes.append(&mut payload),
+      Err(x) => {return Err(MechError{msg: format!("{:?}", x), id: 8406, kind: MechErrorKind::InvalidStorage});}
+    }
+    let temp_path = self.path.join(format!("{}.tmp", SNAPSHOT_FILE));
+    let mut file = File::create(&temp_path)?;
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, self.path.join(SNAPSHOT_FILE))?;
+    Storage::sync_dir(&self.path);
+    self.generation = generation;
+    self.log = Storage::create_log(&self.path, generation)?;
+    self.records = 0;
+    self.track(database);
+    Ok(())
+  }
+
+}
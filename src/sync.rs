@@ -0,0 +1,92 @@
+// # Sync
+
+// Shared ownership and interior mutability for tables, columns, blocks and
+// the schedule. By default these are Rc and RefCell from std. With the "sync"
+// feature Rc is Arc, and RefCell is a thin wrapper around RwLock with the
+// same borrow interface. That makes a Core Send + Sync, so it can move
+// between threads and the schedule can solve independent blocks in parallel.
+// The wrapper only ever tries the lock, so a conflicting borrow panics the same
+// way it does with RefCell, rather than deadlocking when a thread borrows a
+// cell it already holds. The blocks solved in parallel never share a table
+// they write, so they never contend for a lock.
+
+// ## Prelude
+
+#[cfg(not(feature = "sync"))]
+pub use std::rc::Rc;
+#[cfg(not(feature = "sync"))]
+pub use std::cell::{RefCell, Ref, RefMut};
+
+#[cfg(feature = "sync")]
+pub use std::sync::Arc as Rc;
+#[cfg(feature = "sync")]
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+#[cfg(feature = "sync")]
+use std::fmt;
+
+// ## Shareable
+
+// Functions and machines are stored as trait objects, which only cross
+// threads if their implementations do.
+#[cfg(not(feature = "sync"))]
+pub trait Shareable {}
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> Shareable for T {}
+
+#[cfg(feature = "sync")]
+pub trait Shareable: Send + Sync {}
+#[cfg(feature = "sync")]
+impl<T: ?Sized + Send + Sync> Shareable for T {}
+
+// ## RefCell
+
+#[cfg(feature = "sync")]
+pub type Ref<'a,T> = RwLockReadGuard<'a,T>;
+#[cfg(feature = "sync")]
+pub type RefMut<'a,T> = RwLockWriteGuard<'a,T>;
+
+#[cfg(feature = "sync")]
+pub struct RefCell<T>(RwLock<T>);
+
+#[cfg(feature = "sync")]
+impl<T> RefCell<T> {
+
+  pub fn new(value: T) -> RefCell<T> {
+    RefCell(RwLock::new(value))
+  }
+
+  pub fn borrow(&self) -> Ref<T> {
+    match self.0.try_read() {
+      Ok(value) => value,
+      Err(TryLockError::WouldBlock) => panic!("already mutably borrowed"),
+      Err(TryLockError::Poisoned(x)) => panic!("{}", x),
+    }
+  }
+
+  pub fn borrow_mut(&self) -> RefMut<T> {
+    match self.0.try_write() {
+      Ok(value) => value,
+      Err(TryLockError::WouldBlock) => panic!("already borrowed"),
+      Err(TryLockError::Poisoned(x)) => panic!("{}", x),
+    }
+  }
+
+  pub fn try_borrow(&self) -> Result<Ref<T>,()> {
+    self.0.try_read().map_err(|_| ())
+  }
+
+  pub fn try_borrow_mut(&self) -> Result<RefMut<T>,()> {
+    self.0.try_write().map_err(|_| ())
+  }
+
+}
+
+#[cfg(feature = "sync")]
+impl<T: fmt::Debug> fmt::Debug for RefCell<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.0.try_read() {
+      Ok(value) => f.debug_struct("RefCell").field("value", &*value).finish(),
+      Err(_) => f.debug_struct("RefCell").field("value", &"<borrowed>").finish(),
+    }
+  }
+}
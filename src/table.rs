@@ -5,8 +5,8 @@
 
 // ## Prelude
 
-use std::rc::Rc;
-use std::cell::RefCell;
+use crate::sync::Rc;
+use crate::sync::RefCell;
 use std::fmt;
 use crate::*;
 use hashbrown::HashMap;
@@ -145,6 +145,82 @@ impl Table {
     Ok(())
   }
 
+  // Inserts count rows before row ix, so ix equal to the number of rows adds
+  // them at the end. The new rows hold the zero value of their column's kind.
+  pub fn insert_rows(&mut self, ix: usize, count: usize) -> std::result::Result<(),MechError> {
+    if ix > self.rows {
+      return Err(MechError{msg: "".to_string(), id: 7050, kind: MechErrorKind::GenericError(format!("Can't insert rows at {} in a table with {} rows", ix, self.rows))});
+    }
+    for column in &self.data {
+      column.insert_rows(ix,count)?;
+    }
+    self.row_map.insert_ixes(ix,count);
+    self.rows += count;
+    Ok(())
+  }
+
+  // Removes every row whose entry in remove is true. The mask has an entry for
+  // every row.
+  pub fn remove_rows(&mut self, remove: &Vec<bool>) -> std::result::Result<(),MechError> {
+    if remove.len() != self.rows {
+      return Err(MechError{msg: "".to_string(), id: 7051, kind: MechErrorKind::DimensionMismatch(vec![(remove.len(),1),(self.rows,1)])});
+    }
+    // Columns are checked before any is changed, so a column that's fallen out
+    // of step with the table leaves the table as it was.
+    for column in &self.data {
+      match column {
+        Column::Empty | Column::Reference(_) => (),
+        column if column.len() != self.rows => {
+          return Err(MechError{msg: "".to_string(), id: 7053, kind: MechErrorKind::DimensionMismatch(vec![(column.len(),1),(self.rows,1)])});
+        }
+        _ => (),
+      }
+    }
+    let keep: Vec<bool> = remove.iter().map(|removed| !removed).collect();
+    for column in &self.data {
+      column.retain_rows(&keep)?;
+    }
+    self.row_map.retain_ixes(&keep);
+    self.rows = keep.iter().filter(|kept| **kept).count();
+    Ok(())
+  }
+
+  // Adds rows to the end of the table. Every row has a value for each column,
+  // in column order. A column that doesn't have a kind yet takes the kind of
+  // the first value appended to it. Nothing is appended if any of the values
+  // can't be written to its column.
+  pub fn append_rows(&mut self, rows: &Vec<Vec<Value>>) -> std::result::Result<(),MechError> {
+    for row in rows {
+      if row.len() != self.cols {
+        return Err(MechError{msg: "".to_string(), id: 7052, kind: MechErrorKind::DimensionMismatch(vec![(1,row.len()),(1,self.cols)])});
+      }
+    }
+    for col_ix in 0..self.cols {
+      if self.col_kinds[col_ix] == ValueKind::Empty {
+        if let Some(value) = rows.iter().map(|row| &row[col_ix]).find(|value| **value != Value::Empty) {
+          self.set_col_kind(col_ix, value.kind())?;
+        }
+      }
+    }
+    let mut coerced = vec![];
+    for row in rows {
+      let mut coerced_row = vec![];
+      for (col_ix,value) in row.iter().enumerate() {
+        coerced_row.push(self.coerce(col_ix, value.clone())?);
+      }
+      coerced.push(coerced_row);
+    }
+    let orows = self.rows;
+    let cols = self.cols;
+    self.resize(orows + rows.len(), cols)?;
+    for (row_ix,row) in coerced.into_iter().enumerate() {
+      for (col_ix,value) in row.into_iter().enumerate() {
+        self.set_raw(orows + row_ix, col_ix, value)?;
+      }
+    }
+    Ok(())
+  }
+
   pub fn is_empty(&self) -> bool {
     if self.rows == 0 || self.cols == 0 {
       true
@@ -208,6 +284,8 @@ impl Table {
           Err(MechError{msg: "".to_string(), id: 7005, kind: MechErrorKind::None})
         }
       }
+      // A selection of one named column is just that column
+      TableIndex::Aliases(aliases) if aliases.len() == 1 => self.get_column(&TableIndex::Alias(aliases[0])),
       TableIndex::Aliases(_) |
       TableIndex::ReshapeColumn |
       TableIndex::IxTable(_) |
@@ -215,6 +293,37 @@ impl Table {
     }
   }  
 
+  // The register written when the given row of this table is set. Like
+  // columns, rows with an alias are reported by alias.
+  pub fn row_register(&self, row: &TableIndex) -> RegisterIndex {
+    match row {
+      TableIndex::Alias(alias) => RegisterIndex::Alias(*alias),
+      TableIndex::Index(ix) if *ix > 0 => {
+        match self.row_map.get_alias(&(ix - 1)) {
+          Ok(alias) if alias != 0 => RegisterIndex::Alias(alias),
+          _ => RegisterIndex::Index(*ix),
+        }
+      }
+      _ => RegisterIndex::All,
+    }
+  }
+
+  // The register written when the given column of this table is set. Columns
+  // with an alias are always reported by alias, since that's how blocks
+  // subscribe to them.
+  pub fn column_register(&self, col: &TableIndex) -> RegisterIndex {
+    match col {
+      TableIndex::Alias(alias) => RegisterIndex::Alias(*alias),
+      TableIndex::Index(ix) if *ix > 0 => {
+        match self.col_map.get_alias(&(ix - 1)) {
+          Ok(alias) if alias != 0 => RegisterIndex::Alias(alias),
+          _ => RegisterIndex::Index(*ix),
+        }
+      }
+      _ => RegisterIndex::All,
+    }
+  }
+
   pub fn set_col_alias(&mut self, ix: usize, alias: u64) -> Result<(),MechError> {
     if ix < self.cols {
       self.col_map.insert(ix,alias);
@@ -224,6 +333,19 @@ impl Table {
     }
   }
 
+  pub fn set_row_alias(&mut self, ix: usize, alias: u64) -> Result<(),MechError> {
+    if ix < self.rows {
+      self.row_map.insert(ix,alias);
+      Ok(())
+    } else {
+      Err(MechError{msg: "".to_string(), id: 7046, kind: MechErrorKind::None})
+    }
+  }
+
+  pub fn has_row_aliases(&self) -> bool {
+    self.row_map.len() > 0
+  }
+
   pub fn set_kind(&mut self, kind: ValueKind) -> Result<(),MechError> {
     match kind {
       ValueKind::Compound(kinds) => {
@@ -339,6 +461,12 @@ impl Table {
           self.data[col] = Column::Speed(column);
           self.col_kinds[col] = ValueKind::Speed;
         },
+        (Column::Angle(_), ValueKind::Angle) => (),
+        (Column::Empty, ValueKind::Angle) => {
+          let column = ColumnV::<F32>::new(vec![F32::new(0.0);self.rows]);
+          self.data[col] = Column::Angle(column);
+          self.col_kinds[col] = ValueKind::Angle;
+        },
         (Column::Bool(_), ValueKind::Bool) => (),
         (Column::Empty, ValueKind::Bool) => {
           let column = ColumnV::<bool>::new(vec![false;self.rows]);
@@ -411,6 +539,27 @@ impl Table {
     }
   }
 
+  // A table made of the named columns of this one, in the order they're
+  // given. The columns are shared with this table rather than copied, so the
+  // sub-table sees every change made here, and anything written to the
+  // sub-table is written to this table.
+  pub fn sub_table(&self, id: u64, aliases: &Vec<u64>) -> Result<Table, MechError> {
+    let mut table = Table::new(id, self.rows, aliases.len());
+    table.dynamic = self.dynamic;
+    table.dictionary = self.dictionary.clone();
+    table.row_map = self.row_map.clone();
+    for (ix,alias) in aliases.iter().enumerate() {
+      let col_ix = match self.col_map.get_index(alias) {
+        Ok(col_ix) => col_ix,
+        Err(_) => {return Err(MechError{msg: humanize(alias), id: 7049, kind: MechErrorKind::GenericError(format!("Missing column {}", humanize(alias)))});}
+      };
+      table.data[ix] = self.data[col_ix].clone();
+      table.col_kinds[ix] = self.col_kinds[col_ix].clone();
+      table.col_map.insert(ix,*alias)?;
+    }
+    Ok(table)
+  }
+
   pub fn get_columns(&self, col: &TableIndex) -> Result<Vec<Column>, MechError> {
     match col {
       TableIndex::All => {
@@ -438,6 +587,12 @@ impl Table {
     let row_ix = match row {
       TableIndex::Index(0) => {return Err(MechError{msg: "".to_string(), id: 0001, kind: MechErrorKind::None});},
       TableIndex::Index(ix) => ix - 1,
+      TableIndex::Alias(alias) => {
+        match self.row_map.get_index(alias) {
+          Ok(ix) => ix,
+          Err(_) => {return Err(MechError{msg: humanize(alias), id: 7047, kind: MechErrorKind::MissingRowAlias(TableId::Global(self.id),*alias)})}
+        }
+      }
       _ => 0,
     };
     let col_ix = match col {
@@ -454,14 +609,48 @@ impl Table {
     self.set_raw(row_ix,col_ix,val)
   }
 
+  // Converts a value to the kind of the column it's about to be written to,
+  // following the rules in Value::coerce. Values that can't be converted are
+  // rejected with an error naming the column and both kinds.
+  pub fn coerce(&self, col: usize, val: Value) -> Result<Value,MechError> {
+    let expected = match self.col_kinds.get(col) {
+      Some(kind) => kind,
+      None => {return Ok(val);}
+    };
+    let given = val.kind();
+    match val.coerce(expected) {
+      Some(val) => Ok(val),
+      None => {
+        let alias = match self.col_map.get_alias(&col) {
+          Ok(0) | Err(_) => None,
+          Ok(alias) => Some(alias),
+        };
+        let table_name = match self.name() {
+          Some(name) => name,
+          None => humanize(&self.id),
+        };
+        let column_name = match alias {
+          Some(alias) => match self.dictionary.borrow().get(&alias) {
+            Some(name) => name.to_string(),
+            None => humanize(&alias),
+          },
+          None => format!("{}", col + 1),
+        };
+        let msg = format!("#{}.{} expects {:?}, given {:?}", table_name, column_name, expected, given);
+        Err(MechError{msg, id: 7045, kind: MechErrorKind::ColumnKindMismatch(TableId::Global(self.id),col,alias,expected.clone(),given)})
+      }
+    }
+  }
+
   pub fn set_raw(&self, row: usize, col: usize, val: Value) -> Result<(),MechError> {
     if col < self.cols && row < self.rows {
+      let val = self.coerce(col, val)?;
       match (&self.data[col], val) {
         (Column::Length(c), Value::Length(v)) |
         (Column::Time(c), Value::Time(v)) |
         (Column::Speed(c), Value::Speed(v)) |
+        (Column::Angle(c), Value::Angle(v)) |
         (Column::F32(c), Value::F32(v)) => c.borrow_mut()[row] = v,
-        (Column::F32(c), Value::U64(v)) => c.borrow_mut()[row] = v.into(),
         (Column::f32(c), Value::f32(v)) => c.borrow_mut()[row] = v,
         (Column::F64(c), Value::F64(v)) => c.borrow_mut()[row] = v,
         (Column::U8(c), Value::U8(v)) => c.borrow_mut()[row] = v,
@@ -493,6 +682,12 @@ impl Table {
     let row_ix = match row {
       TableIndex::Index(0) => {return Err(MechError{msg: "".to_string(), id: 7018, kind: MechErrorKind::None})},
       TableIndex::Index(ix) => ix - 1,
+      TableIndex::Alias(alias) => {
+        match self.row_map.get_index(alias) {
+          Ok(ix) => ix,
+          Err(_) => {return Err(MechError{msg: humanize(alias), id: 7048, kind: MechErrorKind::MissingRowAlias(TableId::Global(self.id),*alias)})}
+        }
+      }
       _ => 0,
     };
     let col_ix = match col {
@@ -515,6 +710,7 @@ impl Table {
         Column::Time(c) => Ok(Value::Time(c.borrow()[row])),
         Column::Length(c) => Ok(Value::Length(c.borrow()[row])),
         Column::Speed(c) => Ok(Value::Speed(c.borrow()[row])),
+        Column::Angle(c) => Ok(Value::Angle(c.borrow()[row])),
         Column::F32(c) => Ok(Value::F32(c.borrow()[row])),
         Column::f32(c) => Ok(Value::f32(c.borrow()[row])),
         Column::F64(c) => Ok(Value::F64(c.borrow()[row])),
@@ -621,6 +817,9 @@ impl Table {
     for ((alias,ix)) in self.col_map.iter() {
       changes.push(Change::ColumnAlias{table_id: self.id, column_ix: *ix, column_alias: *alias});
     } 
+    for (alias,ix) in self.row_map.iter() {
+      changes.push(Change::RowAlias{table_id: self.id, row_ix: *ix, row_alias: *alias});
+    }
     for (ix,kind) in self.col_kinds.iter().enumerate() {
       changes.push(Change::ColumnKind{table_id: self.id, column_ix: ix, column_kind: kind.clone()});
     } 
@@ -693,9 +892,36 @@ impl AliasMap {
     }
   }
 
+  // Aliases of indices that no longer exist are dropped.
   pub fn resize(&mut self, new_capacity: usize) {
     self.capacity = new_capacity;
     self.ix_to_alias.resize(new_capacity,0);
+    self.alias_to_ix.retain(|_,ix| *ix < new_capacity);
+  }
+
+  // Makes room for count unaliased indices before ix. Aliases after them move
+  // along with their indices.
+  pub fn insert_ixes(&mut self, ix: TableIx, count: usize) {
+    self.ix_to_alias.splice(ix..ix, std::iter::repeat(0).take(count));
+    self.capacity += count;
+    self.reindex();
+  }
+
+  // Keeps only the indices whose entry in keep is true. Aliases of removed
+  // indices are dropped.
+  pub fn retain_ixes(&mut self, keep: &Vec<bool>) {
+    let mut ix = 0;
+    self.ix_to_alias.retain(|_| {
+      let kept = keep[ix];
+      ix += 1;
+      kept
+    });
+    self.capacity = self.ix_to_alias.len();
+    self.reindex();
+  }
+
+  fn reindex(&mut self) {
+    self.alias_to_ix = self.ix_to_alias.iter().enumerate().filter(|(_,alias)| **alias != 0).map(|(ix,alias)| (*alias,ix)).collect();
   }
 
   pub fn insert(&mut self, ix: TableIx, alias: Alias) -> std::result::Result<(),MechError> {
@@ -0,0 +1,90 @@
+// # Temporal
+
+// Blocks can be held back in time. A block that waits is suspended when it's
+// loaded, and solved for the first time once a timer elapses or a condition
+// table becomes true. A block that repeats until a condition holds is solved
+// again on every tick of the core until the condition table becomes true.
+// Entry actions of state machines are held back in the same way, and are only
+// solved when their machine enters the state. So are edge-triggered whenevers,
+// which are only solved when their condition goes from false to true.
+
+// Time is read from a time source owned by the core. The system clock counts
+// milliseconds since the core was created. Tests, or hosts that drive the core
+// at a fixed rate, can use a manual clock instead and advance it themselves,
+// in which case durations are in whatever unit the clock is advanced by
+// (ticks, for example).
+
+// ## Prelude
+
+use crate::*;
+use crate::sync::{Rc, RefCell, Shareable};
+use std::time::Instant;
+
+// ## Time Sources
+
+pub trait TimeSource: Shareable {
+  fn now(&self) -> u64;
+}
+
+pub struct SystemClock {
+  start: Instant,
+}
+
+impl SystemClock {
+
+  pub fn new() -> SystemClock {
+    SystemClock {
+      start: Instant::now(),
+    }
+  }
+
+}
+
+impl TimeSource for SystemClock {
+  fn now(&self) -> u64 {
+    self.start.elapsed().as_millis() as u64
+  }
+}
+
+// A clock that only moves when it's told to. Clones share the same time, so
+// one can be given to a core while another is kept to advance it.
+#[derive(Clone)]
+pub struct ManualClock {
+  now: Rc<RefCell<u64>>,
+}
+
+impl ManualClock {
+
+  pub fn new() -> ManualClock {
+    ManualClock {
+      now: Rc::new(RefCell::new(0)),
+    }
+  }
+
+  pub fn advance(&self, duration: u64) {
+    *self.now.borrow_mut() += duration;
+  }
+
+  pub fn set(&self, now: u64) {
+    *self.now.borrow_mut() = now;
+  }
+
+}
+
+impl TimeSource for ManualClock {
+  fn now(&self) -> u64 {
+    *self.now.borrow()
+  }
+}
+
+// ## Temporal Conditions
+
+// The temporal transformations of a block, with the state of their timers.
+#[derive(Clone, Debug)]
+pub enum Temporal {
+  Wait{duration: u64, deadline: Option<u64>, elapsed: bool},
+  WaitFor{table_id: TableId, indices: Vec<(TableIndex, TableIndex)>},
+  Until{table_id: TableId, indices: Vec<(TableIndex, TableIndex)>},
+  OnEnter{table_id: TableId, state: u64},
+  Rising{table_id: TableId, indices: Vec<(TableIndex, TableIndex)>, last: bool, rising: bool},
+}
@@ -1,7 +1,7 @@
 use crate::*;
 use crate::Argument;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::sync::RefCell;
+use crate::sync::Rc;
 use std::fmt;
 use std::cmp::Ordering;
 
@@ -19,6 +19,13 @@ pub enum Transformation {
   ColumnAlias{table_id: TableId, column_ix: usize, column_alias: u64},
   RowAlias{table_id: TableId, row_ix: usize, row_alias: u64},
   Whenever{table_id: TableId, indices: Vec<(TableIndex, TableIndex)>},
+  WheneverRising{table_id: TableId, indices: Vec<(TableIndex, TableIndex)>},   // Solves the block when the table goes from false to true
+  Wait{duration: u64},                                                   // Suspends the block until the duration has passed
+  WaitFor{table_id: TableId, indices: Vec<(TableIndex, TableIndex)>},    // Suspends the block until the table is true
+  Until{table_id: TableId, indices: Vec<(TableIndex, TableIndex)>},      // Repeats the block every tick until the table is true
+  StateMachine{table_id: TableId, initial: u64},
+  StateTransition{table_id: TableId, from: u64, to: u64, guard: TableId, indices: Vec<(TableIndex, TableIndex)>},
+  OnEnter{table_id: TableId, state: u64},                                // Solves the block only when the machine enters the state
   Function{name: u64, arguments: Vec<Argument>, out: (TableId, TableIndex, TableIndex)},
   TableDefine{table_id: TableId, indices: Vec<(TableIndex, TableIndex)>, out: TableId},
   Select{table_id: TableId, indices: Vec<(TableIndex, TableIndex)>},
@@ -38,6 +45,20 @@ impl fmt::Debug for Transformation {
         write!(f,"🐙 NumberLiteral(kind: {:?}, bytes: {:?})",humanize(kind),bytes)?,
       Transformation::Whenever{table_id,indices} => 
         write!(f,"⏲️ Whenever(table_id: {:#?}, indices: {:#?})",table_id,indices)?,
+      Transformation::WheneverRising{table_id,indices} => 
+        write!(f,"⏲️ WheneverRising(table_id: {:#?}, indices: {:#?})",table_id,indices)?,
+      Transformation::Wait{duration} => 
+        write!(f,"⏳ Wait(duration: {})",duration)?,
+      Transformation::WaitFor{table_id,indices} => 
+        write!(f,"⏳ WaitFor(table_id: {:#?}, indices: {:#?})",table_id,indices)?,
+      Transformation::Until{table_id,indices} => 
+        write!(f,"🔁 Until(table_id: {:#?}, indices: {:#?})",table_id,indices)?,
+      Transformation::StateMachine{table_id,initial} => 
+        write!(f,"🚦 StateMachine(table_id: {:?}, initial: {})",table_id,humanize(initial))?,
+      Transformation::StateTransition{table_id,from,to,guard,indices} => 
+        write!(f,"🚦 StateTransition(table_id: {:?}, from: {}, to: {}, guard: {:?}, indices: {:?})",table_id,humanize(from),humanize(to),guard,indices)?,
+      Transformation::OnEnter{table_id,state} => 
+        write!(f,"🚦 OnEnter(table_id: {:?}, state: {})",table_id,humanize(state))?,
       Transformation::TableAlias{table_id,alias} => 
         write!(f,"🥸 TableAlias(table_id: {:?}, alias: {})",table_id,humanize(alias))?,
       Transformation::NewTable{table_id, rows, columns} =>  
@@ -102,6 +123,22 @@ impl PartialOrd for Transformation {
       (_,Transformation::Function{..}) => {
         return Some(Ordering::Less);
       }
+      // Temporal transformations come first, so the functions they hold back
+      // aren't solved while the block is being compiled.
+      (_,Transformation::Wait{..}) |
+      (_,Transformation::WaitFor{..}) |
+      (_,Transformation::Until{..}) |
+      (_,Transformation::OnEnter{..}) |
+      (_,Transformation::WheneverRising{..}) => {
+        return Some(Ordering::Greater);
+      }
+      (Transformation::Wait{..},_) |
+      (Transformation::WaitFor{..},_) |
+      (Transformation::Until{..},_) |
+      (Transformation::OnEnter{..},_) |
+      (Transformation::WheneverRising{..},_) => {
+        return Some(Ordering::Less);
+      }
       (Transformation::TableReference{..},
        Transformation::TableReference{..}) => {
         Some(Ordering::Less)
@@ -115,6 +152,8 @@ impl PartialOrd for Transformation {
       },
       (Transformation::TableReference{..},Transformation::ColumnAlias{..}) => Some(Ordering::Greater),
       (Transformation::ColumnAlias{..},Transformation::TableReference{..}) => Some(Ordering::Less),
+      (Transformation::TableReference{..},Transformation::RowAlias{..}) => Some(Ordering::Greater),
+      (Transformation::RowAlias{..},Transformation::TableReference{..}) => Some(Ordering::Less),
       (_,Transformation::NewTable{..}) => Some(Ordering::Greater),
       (Transformation::NewTable{..},_) => Some(Ordering::Less),
       (_,Transformation::Identifier{..}) => Some(Ordering::Greater),
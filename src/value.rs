@@ -86,7 +86,86 @@ impl Value {
       Value::Empty => ValueKind::Empty,
     }
   }
-  
+
+  // Converts the value to another kind, if it can be done without changing
+  // what the value means. The conversions are:
+  //
+  // - An unsigned integer to a wider unsigned or signed integer
+  // - A signed integer to a wider signed integer
+  // - Any integer to F32 or F64, rounding to the nearest float for integers
+  //   too large to be represented exactly
+  // - An F32 (or f32) to F64, and an f32 to F32
+  //
+  // Everything else, including narrowing and converting floats to integers,
+  // returns None. Quantities (lengths, times, speeds and angles) are never
+  // converted to or from plain numbers, since that would drop their units.
+  pub fn coerce(self, kind: &ValueKind) -> Option<Value> {
+    let from = self.kind();
+    if from == *kind || *kind == ValueKind::Any {
+      return Some(self);
+    }
+    let integer = match &self {
+      Value::U8(v) => Some(v.unwrap() as i128),
+      Value::U16(v) => Some(v.unwrap() as i128),
+      Value::U32(v) => Some(v.unwrap() as i128),
+      Value::U64(v) => Some(v.unwrap() as i128),
+      Value::I8(v) => Some(v.unwrap() as i128),
+      Value::I16(v) => Some(v.unwrap() as i128),
+      Value::I32(v) => Some(v.unwrap() as i128),
+      Value::I64(v) => Some(v.unwrap() as i128),
+      Value::I128(v) => Some(v.unwrap()),
+      _ => None,
+    };
+    match (integer, integer_width(&from), integer_width(kind)) {
+      (Some(x), Some((from_signed,from_bits)), Some((to_signed,to_bits))) => {
+        if to_bits <= from_bits || (from_signed && !to_signed) {
+          return None;
+        }
+        match kind {
+          ValueKind::U16 => Some(Value::U16(U16::new(x as u16))),
+          ValueKind::U32 => Some(Value::U32(U32::new(x as u32))),
+          ValueKind::U64 => Some(Value::U64(U64::new(x as u64))),
+          ValueKind::U128 => Some(Value::U128(U128::new(x as u128))),
+          ValueKind::I16 => Some(Value::I16(I16::new(x as i16))),
+          ValueKind::I32 => Some(Value::I32(I32::new(x as i32))),
+          ValueKind::I64 => Some(Value::I64(I64::new(x as i64))),
+          ValueKind::I128 => Some(Value::I128(I128::new(x))),
+          _ => None,
+        }
+      }
+      (Some(x), _, None) => match kind {
+        ValueKind::F32 => Some(Value::F32(F32::new(x as f32))),
+        ValueKind::F64 => Some(Value::F64(F64::new(x as f64))),
+        _ => None,
+      }
+      _ => match (self, kind) {
+        (Value::U128(v), ValueKind::F32) => Some(Value::F32(F32::new(v.unwrap() as f32))),
+        (Value::U128(v), ValueKind::F64) => Some(Value::F64(F64::new(v.unwrap() as f64))),
+        (Value::F32(v), ValueKind::F64) => Some(Value::F64(F64::new(v.unwrap() as f64))),
+        (Value::f32(v), ValueKind::F32) => Some(Value::F32(F32::new(v))),
+        (Value::f32(v), ValueKind::F64) => Some(Value::F64(F64::new(v as f64))),
+        _ => None,
+      }
+    }
+  }
+
+}
+
+// Whether an integer kind is signed, and how many bits it has.
+fn integer_width(kind: &ValueKind) -> Option<(bool,u32)> {
+  match kind {
+    ValueKind::U8 => Some((false,8)),
+    ValueKind::U16 => Some((false,16)),
+    ValueKind::U32 => Some((false,32)),
+    ValueKind::U64 => Some((false,64)),
+    ValueKind::U128 => Some((false,128)),
+    ValueKind::I8 => Some((true,8)),
+    ValueKind::I16 => Some((true,16)),
+    ValueKind::I32 => Some((true,32)),
+    ValueKind::I64 => Some((true,64)),
+    ValueKind::I128 => Some((true,128)),
+    _ => None,
+  }
 }
 
 impl fmt::Debug for Value {
@@ -0,0 +1,82 @@
+use mech_core::*;
+use mech_core::function::math::*;
+use mech_core::function::stats::*;
+
+fn f32(value: f32) -> Value {
+  Value::F32(F32::new(value))
+}
+
+fn get(core: &Core, name: &str, row: usize, col: usize) -> Value {
+  core.get_table_by_id(hash_str(name)).unwrap().borrow().get_raw(row,col).unwrap()
+}
+
+fn columns(names: &[&str]) -> TableIndex {
+  TableIndex::Aliases(names.iter().map(|name| hash_str(name)).collect())
+}
+
+fn balls(cols: TableIndex) -> (TableId, Vec<(TableIndex,TableIndex)>) {
+  (TableId::Global(hash_str("balls")), vec![(TableIndex::All, cols)])
+}
+
+fn balls_core() -> Core {
+  let mut core = Core::new();
+  let balls = hash_str("balls");
+  let mut txn = vec![Change::NewTable{table_id: balls, rows: 2, columns: 3}];
+  for (ix,name) in ["x","y","z"].iter().enumerate() {
+    txn.push(Change::ColumnAlias{table_id: balls, column_ix: ix, column_alias: hash_str(name)});
+    txn.push(Change::ColumnKind{table_id: balls, column_ix: ix, column_kind: ValueKind::F32});
+  }
+  txn.push(Change::Set((balls, vec![
+    (TableIndex::Index(1), TableIndex::Index(1), f32(1.0)), (TableIndex::Index(2), TableIndex::Index(1), f32(2.0)),
+    (TableIndex::Index(1), TableIndex::Index(2), f32(3.0)), (TableIndex::Index(2), TableIndex::Index(2), f32(4.0)),
+    (TableIndex::Index(1), TableIndex::Index(3), f32(10.0)), (TableIndex::Index(2), TableIndex::Index(3), f32(20.0)),
+  ])));
+  core.process_transaction(&txn).unwrap();
+  core
+}
+
+fn function_block(name: u64, arguments: Vec<(TableId, Vec<(TableIndex,TableIndex)>)>, out: &str) -> Block {
+  let arg_name = if name == *STATS_SUM { hash_str("column") } else { 0 };
+  let mut block = Block::new();
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Global(hash_str(out)), rows: 1, columns: 1}).unwrap();
+  block.add_tfm(Transformation::Function{name, arguments: arguments.into_iter().map(|(table_id,indices)| (arg_name,table_id,indices)).collect(), out: (TableId::Global(hash_str(out)),TableIndex::All,TableIndex::All)}).ok();
+  block
+}
+
+#[test]
+fn kernels_read_selected_columns() {
+  let mut core = balls_core();
+  core.load_blocks(&vec![function_block(*MATH_ADD, vec![balls(columns(&["x","y"])), balls(columns(&["x","y"]))], "sum2")]);
+  core.load_blocks(&vec![function_block(*STATS_SUM, vec![balls(columns(&["x","y"]))], "colsum")]);
+  core.load_blocks(&vec![function_block(*MATH_ADD, vec![balls(columns(&["z"])), balls(TableIndex::Alias(hash_str("z")))], "z2")]);
+  assert_eq!(core.get_table_by_id(hash_str("sum2")).unwrap().borrow().cols, 2);
+  assert_eq!(get(&core,"sum2",0,0), f32(2.0));
+  assert_eq!(get(&core,"sum2",1,1), f32(8.0));
+  assert_eq!(get(&core,"colsum",0,0), f32(3.0));
+  assert_eq!(get(&core,"colsum",0,1), f32(7.0));
+  assert_eq!(get(&core,"z2",1,0), f32(40.0));
+  core.process_transaction(&vec![Change::Set((hash_str("balls"), vec![(TableIndex::Index(2), TableIndex::Alias(hash_str("y")), f32(5.0))]))]).unwrap();
+  assert_eq!(get(&core,"sum2",1,1), f32(10.0));
+  assert_eq!(get(&core,"colsum",0,1), f32(8.0));
+}
+
+#[test]
+fn set_writes_selected_columns() {
+  let mut core = balls_core();
+  let vel = hash_str("vel");
+  let mut txn = vec![Change::NewTable{table_id: vel, rows: 2, columns: 2}];
+  for ix in 0..2 {
+    txn.push(Change::ColumnKind{table_id: vel, column_ix: ix, column_kind: ValueKind::F32});
+  }
+  txn.push(Change::Set((vel, vec![(TableIndex::Index(1), TableIndex::Index(1), f32(2.0)), (TableIndex::Index(2), TableIndex::Index(2), f32(8.0))])));
+  core.process_transaction(&txn).unwrap();
+  let mut block = Block::new();
+  block.add_tfm(Transformation::Set{src_id: TableId::Global(vel), src_row: TableIndex::All, src_col: TableIndex::All, dest_id: TableId::Global(hash_str("balls")), dest_row: TableIndex::All, dest_col: columns(&["x","y"])}).ok();
+  let (_,errors) = core.load_blocks(&vec![block]);
+  assert!(errors.is_empty());
+  assert_eq!(get(&core,"balls",0,0), f32(2.0));
+  assert_eq!(get(&core,"balls",1,1), f32(8.0));
+  assert_eq!(get(&core,"balls",1,2), f32(20.0));
+  core.process_transaction(&vec![Change::Set((vel, vec![(TableIndex::Index(2), TableIndex::Index(1), f32(6.0))]))]).unwrap();
+  assert_eq!(get(&core,"balls",1,0), f32(6.0));
+}
@@ -0,0 +1,169 @@
+use mech_core::*;
+use mech_core::function::table::*;
+use mech_core::sync::{Rc, RefCell};
+use hashbrown::HashSet;
+use std::sync::mpsc::channel;
+
+mod common;
+use common::{set, pipe};
+
+fn f32(value: f32) -> Value {
+  Value::F32(F32::new(value))
+}
+
+fn u8(value: u8) -> Value {
+  Value::U8(U8::new(value))
+}
+
+fn string(value: &str) -> Value {
+  Value::String(MechString::from_str(value))
+}
+
+fn angle(value: f32) -> Value {
+  Value::Angle(F32::new(value))
+}
+
+fn get(core: &Core, name: &str, row: usize, col: usize) -> Value {
+  core.get_table_by_id(hash_str(name)).unwrap().borrow().get_raw(row,col).unwrap()
+}
+
+fn rows(core: &Core, name: &str) -> usize {
+  core.get_table_by_id(hash_str(name)).unwrap().borrow().rows
+}
+
+fn new_table(name: &str, rows: usize, columns: Vec<(&str,ValueKind)>) -> Transaction {
+  let table_id = hash_str(name);
+  let mut txn = vec![Change::NewTable{table_id, rows, columns: columns.len()}];
+  for (ix,(alias,kind)) in columns.into_iter().enumerate() {
+    txn.push(Change::ColumnAlias{table_id, column_ix: ix, column_alias: hash_str(alias)});
+    txn.push(Change::ColumnKind{table_id, column_ix: ix, column_kind: kind});
+  }
+  txn
+}
+
+#[test]
+fn append_checks_and_coerces_rows() {
+  let mut core = Core::new();
+  let log = hash_str("log");
+  core.process_transaction(&new_table("log", 0, vec![("id",ValueKind::U8),("msg",ValueKind::String),("x",ValueKind::F32),("a",ValueKind::Angle),("b",ValueKind::Bool)])).unwrap();
+  core.process_transaction(&vec![Change::Append{table_id: log, rows: vec![
+    vec![u8(1), string("hi"), f32(1.5), angle(2.0), Value::Bool(true)],
+    vec![u8(2), string("yo"), u8(3), angle(3.0), Value::Bool(false)],
+  ]}]).unwrap();
+  assert_eq!(rows(&core,"log"), 2);
+  assert_eq!(get(&core,"log",1,0), u8(2));
+  assert_eq!(get(&core,"log",0,1), string("hi"));
+  assert_eq!(get(&core,"log",1,2), f32(3.0));
+  assert_eq!(get(&core,"log",1,3), angle(3.0));
+  // A row of the wrong kind rolls back the rows appended before it.
+  assert!(core.process_transaction(&vec![
+    Change::Append{table_id: log, rows: vec![vec![u8(3), string("a"), f32(1.0), angle(0.0), Value::Bool(true)]]},
+    Change::Append{table_id: log, rows: vec![vec![string("bad"), string("a"), f32(1.0), angle(0.0), Value::Bool(true)]]},
+  ]).is_err());
+  assert_eq!(rows(&core,"log"), 2);
+  assert!(core.process_transaction(&vec![Change::Append{table_id: log, rows: vec![vec![u8(3)]]}]).is_err());
+  assert_eq!(rows(&core,"log"), 2);
+  // Columns without a kind take the kind of the first row.
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: hash_str("untyped"), rows: 0, columns: 2},
+    Change::Append{table_id: hash_str("untyped"), rows: vec![vec![f32(1.0), string("z")]]},
+  ]).unwrap();
+  assert_eq!(get(&core,"untyped",0,1), string("z"));
+}
+
+#[test]
+fn table_append_matches_columns_by_alias() {
+  let mut core = Core::new();
+  let mut txn = new_table("spawn", 1, vec![("name",ValueKind::String),("x",ValueKind::F32),("hp",ValueKind::U8)]);
+  txn.push(Change::Set((hash_str("spawn"), vec![(TableIndex::Index(1), TableIndex::Index(1), string("orc")), (TableIndex::Index(1), TableIndex::Index(2), f32(4.0)), (TableIndex::Index(1), TableIndex::Index(3), u8(9))])));
+  txn.extend(new_table("ents", 1, vec![("hp",ValueKind::U8),("x",ValueKind::F32),("name",ValueKind::String)]));
+  txn.push(Change::Set((hash_str("ents"), vec![(TableIndex::Index(1), TableIndex::Index(3), string("elf"))])));
+  core.process_transaction(&txn).unwrap();
+  let mut block = Block::new();
+  block.add_tfm(Transformation::Function{name: *TABLE_APPEND, arguments: vec![(0, TableId::Global(hash_str("spawn")), vec![(TableIndex::All, TableIndex::All)])], out: (TableId::Global(hash_str("ents")),TableIndex::All,TableIndex::All)}).ok();
+  let (_,errors) = core.load_blocks(&vec![block]);
+  assert!(errors.is_empty());
+  assert_eq!(rows(&core,"ents"), 2);
+  assert_eq!(get(&core,"ents",1,0), u8(9));
+  assert_eq!(get(&core,"ents",1,1), f32(4.0));
+  assert_eq!(get(&core,"ents",1,2), string("orc"));
+  assert_eq!(get(&core,"ents",0,2), string("elf"));
+  core.process_transaction(&vec![Change::Set((hash_str("spawn"), vec![(TableIndex::Index(1), TableIndex::Index(1), string("imp"))]))]).unwrap();
+  assert_eq!(rows(&core,"ents"), 3);
+  assert_eq!(get(&core,"ents",2,2), string("imp"));
+}
+
+#[test]
+fn append_kernel_reads_rows_when_solved() {
+  let mut arg = Table::new(hash_str("a"),1,1);
+  arg.set_col_kind(0,ValueKind::F32).unwrap();
+  arg.set_raw(0,0,f32(1.0)).unwrap();
+  let mut out = Table::new(hash_str("o"),0,1);
+  out.set_col_kind(0,ValueKind::F32).unwrap();
+  let arg = Rc::new(RefCell::new(arg));
+  let out = Rc::new(RefCell::new(out));
+  let kernel = AppendRowsT{arg: arg.clone(), row: None, columns: vec![(0,0)], out: out.clone()};
+  kernel.solve();
+  assert_eq!(out.borrow().rows, 1);
+  arg.borrow_mut().resize(3,1).unwrap();
+  arg.borrow_mut().set_raw(2,0,f32(3.0)).unwrap();
+  kernel.solve();
+  assert_eq!(out.borrow().rows, 4);
+  assert_eq!(out.borrow().get_raw(3,0).unwrap(), f32(3.0));
+  // Rows out of range and values of the wrong kind leave the output alone.
+  let kernel = AppendRowsT{arg: arg.clone(), row: Some(7), columns: vec![(0,0)], out: out.clone()};
+  kernel.solve();
+  assert_eq!(out.borrow().rows, 4);
+  let mut strings = Table::new(hash_str("s"),1,1);
+  strings.set_col_kind(0,ValueKind::String).unwrap();
+  strings.set_raw(0,0,string("x")).unwrap();
+  let kernel = AppendRowsT{arg: Rc::new(RefCell::new(strings)), row: Some(0), columns: vec![(0,0)], out: out.clone()};
+  kernel.solve();
+  assert_eq!(out.borrow().rows, 4);
+}
+
+fn table_changes(core: &Core, name: &str) -> String {
+  format!("{:?}", core.get_table_by_id(hash_str(name)).map(|table| table.borrow().to_changes()).ok())
+}
+
+#[test]
+fn reshaping_changes_to_a_published_table_are_forwarded() {
+  let (publisher_end,subscriber_end) = pipe();
+  let (dump_tx,dump_rx) = channel::<String>();
+  let publisher = std::thread::spawn(move || {
+    let mut core = Core::new();
+    core.process_transaction(&vec![
+      Change::NewTable{table_id: hash_str("a"), rows: 1, columns: 1},
+      Change::ColumnKind{table_id: hash_str("a"), column_ix: 0, column_kind: ValueKind::F32},
+      Change::NewTable{table_id: hash_str("hidden"), rows: 1, columns: 1},
+      Change::ColumnKind{table_id: hash_str("hidden"), column_ix: 0, column_kind: ValueKind::Bool},
+    ]).unwrap();
+    let mut published = HashSet::new();
+    published.insert(hash_str("a"));
+    let mut publisher = Publisher::accept(publisher_end, &core, &published).unwrap();
+    let steps = vec![
+      set("a", 7.0),
+      vec![Change::Resize{table_id: hash_str("a"), rows: 3, columns: 1}, Change::Set((hash_str("hidden"), vec![(TableIndex::Index(1), TableIndex::Index(1), Value::Bool(true))]))],
+      vec![Change::Append{table_id: hash_str("a"), rows: vec![vec![Value::F32(F32::new(2.0))]]}],
+      vec![Change::RemoveRows{table_id: hash_str("a"), rows: RowSelection::Indices(vec![0])}],
+      vec![Change::InsertRows{table_id: hash_str("a"), row_ix: 1, rows: 2}],
+      vec![Change::RemoveTable{table_id: hash_str("a")}],
+      vec![Change::NewTable{table_id: hash_str("a"), rows: 2, columns: 1}, Change::ColumnKind{table_id: hash_str("a"), column_ix: 0, column_kind: ValueKind::F32}],
+    ];
+    for txn in steps {
+      let (diff,_) = core.process_transaction_diff(&txn).unwrap();
+      publisher.send(&diff).unwrap();
+      dump_tx.send(table_changes(&core, "a")).unwrap();
+    }
+  });
+  let mut core = Core::new();
+  let mut subscriber = Subscriber::connect(subscriber_end, vec![hash_str("a")]).unwrap();
+  subscriber.receive(&mut core).unwrap();
+  for _ in 0..7 {
+    let txn = subscriber.receive(&mut core).unwrap();
+    assert!(txn.iter().all(|change| change.table_id() == hash_str("a")));
+    assert_eq!(table_changes(&core, "a"), dump_rx.recv().unwrap());
+  }
+  assert!(core.get_table_by_id(hash_str("hidden")).is_err());
+  publisher.join().unwrap();
+}
@@ -0,0 +1,57 @@
+use mech_core::*;
+use mech_core::function::math::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn disabled_blocks_are_skipped_until_enabled() {
+  let mut core = Core::new();
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: hash_str("a"), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str("a"), column_ix: 0, column_kind: ValueKind::F32},
+  ]).unwrap();
+  core.process_transaction(&set("a", 3.0)).unwrap();
+  let (block_ids,_) = core.load_blocks(&vec![binary_block(*MATH_ADD,"a","b"), binary_block(*MATH_ADD,"b","c"), binary_block(*MATH_ADD,"missing","d")]);
+  core.disable_block(block_ids[0]).unwrap();
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  assert_eq!(get(&core,"c"), Some(Value::F32(F32::new(12.0))));
+  core.enable_block(block_ids[0]).unwrap();
+  assert_eq!(get(&core,"c"), Some(Value::F32(F32::new(16.0))));
+  let states = core.block_states();
+  assert_eq!(states.len(), 3);
+  assert_eq!(states.iter().filter(|state| state.error.is_some()).count(), 1);
+}
+
+#[test]
+fn waiting_blocks_stay_disabled_until_enabled() {
+  let mut core = Core::new();
+  core.load_blocks(&vec![binary_block(*MATH_ADD,"a","b")]);
+  let block_id = core.block_states()[0].id;
+  core.disable_block(block_id).unwrap();
+  core.process_transaction(&new_f32_table("a")).unwrap();
+  core.process_transaction(&set("a", 3.0)).unwrap();
+  assert_eq!(get(&core,"b"), None);
+  assert_eq!(core.block_states()[0].state, BlockState::Disabled);
+  core.enable_block(block_id).unwrap();
+  assert_eq!(get(&core,"b"), f32(6.0));
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(8.0));
+  let states = core.block_states();
+  assert_eq!(states.len(), 1);
+  assert_eq!(states[0].state, BlockState::Ready);
+}
+
+#[test]
+fn block_states_report_the_last_solve_error() {
+  let mut core = Core::new();
+  core.process_transaction(&new_f32_table("a")).unwrap();
+  let (block_ids,_) = core.load_blocks(&vec![binary_block(*MATH_ADD,"a","b")]);
+  core.blocks[&block_ids[0]].borrow_mut().state = BlockState::Error;
+  assert!(core.process_transaction(&set("a", 3.0)).is_err());
+  assert_eq!(core.block_states()[0].error.as_ref().map(|error| error.id), Some(2126));
+  core.blocks[&block_ids[0]].borrow_mut().state = BlockState::Ready;
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  assert!(core.block_states()[0].error.is_none());
+  assert_eq!(get(&core,"b"), f32(8.0));
+}
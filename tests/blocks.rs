@@ -0,0 +1,71 @@
+use mech_core::*;
+use mech_core::function::math::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn replace_and_remove_loaded_blocks() {
+  let mut core = Core::new();
+  core.process_transaction(&new_f32_table("a")).unwrap();
+  core.process_transaction(&set("a", 3.0)).unwrap();
+  let (block_ids,_) = core.load_blocks(&vec![binary_block(*MATH_ADD,"a","b"), binary_block(*MATH_ADD,"b","c")]);
+  assert_eq!(get(&core,"c"), f32(12.0));
+  let (block_ids,_) = core.replace_block(block_ids[0], binary_block(*MATH_MULTIPLY,"a","b")).unwrap();
+  assert_eq!(get(&core,"b"), f32(9.0));
+  assert_eq!(get(&core,"c"), f32(18.0));
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  assert_eq!(get(&core,"c"), f32(32.0));
+  assert_eq!(core.blocks.len(), 2);
+  // Removing the block that defines #b drops #b, and the block reading it
+  // waits for #b to be defined again.
+  core.remove_block(block_ids[0]).unwrap();
+  assert_eq!(get(&core,"b"), None);
+  assert_eq!(get(&core,"c"), None);
+  assert_eq!(core.blocks.len(), 0);
+  core.process_transaction(&set("a", 5.0)).unwrap();
+  core.load_blocks(&vec![binary_block(*MATH_ADD,"a","b")]);
+  assert_eq!(get(&core,"c"), f32(20.0));
+}
+
+// The id of the first block waiting to be satisfied.
+fn waiting_block_id(core: &Core) -> BlockId {
+  core.errors.values().flat_map(|block_refs| block_refs.iter()).next().unwrap().borrow().id
+}
+
+#[test]
+fn replace_and_remove_unsatisfied_blocks() {
+  let mut core = Core::new();
+  core.load_blocks(&vec![binary_block(*MATH_ADD,"z","w")]);
+  let block_id = waiting_block_id(&core);
+  core.remove_block(block_id).unwrap();
+  assert_eq!(core.errors.len(), 0);
+  assert!(core.blocks.is_empty());
+  core.process_transaction(&new_f32_table("z")).unwrap();
+  assert_eq!(get(&core,"w"), None);
+  assert!(core.remove_block(block_id).is_err());
+  core.load_blocks(&vec![binary_block(*MATH_ADD,"y","w")]);
+  let block_id = waiting_block_id(&core);
+  core.replace_block(block_id, binary_block(*MATH_ADD,"z","w")).unwrap();
+  assert_eq!(core.errors.len(), 0);
+  core.process_transaction(&set("z", 2.0)).unwrap();
+  assert_eq!(get(&core,"w"), f32(4.0));
+}
+
+// A block that closes a cycle is left unscheduled, and replacing it with one
+// that doesn't schedules the replacement.
+#[test]
+fn replace_block_that_closed_a_cycle() {
+  let mut core = Core::new();
+  core.process_transaction(&new_f32_table("a")).unwrap();
+  let (_,errors) = core.load_blocks(&vec![binary_block(*MATH_ADD,"a","b"), binary_block(*MATH_ADD,"b","a")]);
+  assert!(errors.iter().any(|error| matches!(error.kind, MechErrorKind::CyclicDependency(_))));
+  let (_,block_refs) = core.full_errors.iter().find(|(error,_)| matches!(error.kind, MechErrorKind::CyclicDependency(_))).unwrap();
+  let cyclic_id = block_refs[0].borrow().id;
+  assert!(!core.blocks.contains_key(&cyclic_id));
+  core.replace_block(cyclic_id, binary_block(*MATH_ADD,"b","c")).unwrap();
+  assert!(core.errors.is_empty());
+  core.process_transaction(&set("a", 2.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(4.0));
+  assert_eq!(get(&core,"c"), f32(8.0));
+}
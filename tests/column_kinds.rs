@@ -0,0 +1,44 @@
+use mech_core::*;
+
+#[test]
+fn set_converts_or_rejects_values() {
+  let mut core = Core::new();
+  let t = hash_str("t");
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: t, rows: 2, columns: 3},
+    Change::ColumnAlias{table_id: t, column_ix: 0, column_alias: hash_str("x")},
+    Change::ColumnKind{table_id: t, column_ix: 0, column_kind: ValueKind::F64},
+    Change::ColumnKind{table_id: t, column_ix: 1, column_kind: ValueKind::I32},
+    Change::ColumnKind{table_id: t, column_ix: 2, column_kind: ValueKind::F32},
+  ]).unwrap();
+  core.process_transaction(&vec![Change::Set((t, vec![
+    (TableIndex::Index(1), TableIndex::Index(1), Value::F32(F32::new(1.5))),
+    (TableIndex::Index(1), TableIndex::Index(2), Value::U8(U8::new(7))),
+    (TableIndex::Index(1), TableIndex::Index(3), Value::U64(U64::new(3))),
+  ]))]).unwrap();
+  let table = core.get_table_by_id(t).unwrap();
+  assert_eq!(table.borrow().get_raw(0,0).unwrap(), Value::F64(F64::new(1.5)));
+  assert_eq!(table.borrow().get_raw(0,1).unwrap(), Value::I32(I32::new(7)));
+  assert_eq!(table.borrow().get_raw(0,2).unwrap(), Value::F32(F32::new(3.0)));
+  let error = core.process_transaction(&vec![
+    Change::Set((t, vec![(TableIndex::Index(2), TableIndex::Index(2), Value::I8(I8::new(4)))])),
+    Change::Set((t, vec![(TableIndex::Index(2), TableIndex::Alias(hash_str("x")), Value::String(MechString::from_str("no")))])),
+  ]).unwrap_err();
+  match error.kind {
+    MechErrorKind::InvalidChange(1, inner) => match inner.kind {
+      MechErrorKind::ColumnKindMismatch(TableId::Global(table_id), 0, Some(alias), ValueKind::F64, ValueKind::String) => {
+        assert_eq!(table_id, t);
+        assert_eq!(alias, hash_str("x"));
+      }
+      kind => panic!("{:?}", kind),
+    },
+    kind => panic!("{:?}", kind),
+  }
+  // The first change was rolled back with the second.
+  assert_eq!(table.borrow().get_raw(1,1).unwrap(), Value::I32(I32::new(0)));
+  // Values that don't fit are rejected.
+  assert!(core.process_transaction(&vec![Change::Set((t, vec![(TableIndex::Index(1), TableIndex::Index(2), Value::I64(I64::new(4)))]))]).is_err());
+  assert!(core.process_transaction(&vec![Change::Set((t, vec![(TableIndex::Index(1), TableIndex::Index(2), Value::U32(U32::new(4)))]))]).is_err());
+  assert!(core.process_transaction(&vec![Change::Set((t, vec![(TableIndex::Index(1), TableIndex::Index(2), Value::F32(F32::new(4.0)))]))]).is_err());
+  assert!(matches!(table.borrow().set(&TableIndex::Index(1), &TableIndex::Index(3), Value::Bool(true)).unwrap_err().kind, MechErrorKind::ColumnKindMismatch(..)));
+}
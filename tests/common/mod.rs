@@ -0,0 +1,126 @@
+// Helpers shared by the integration tests. Not every test uses all of them.
+#![allow(dead_code)]
+
+use mech_core::*;
+use mech_core::function::math::*;
+use std::io::{Read, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+// Sets the first cell of #name.
+pub fn set_value(name: &str, value: Value) -> Transaction {
+  vec![Change::Set((hash_str(name), vec![(TableIndex::Index(1), TableIndex::Index(1), value)]))]
+}
+
+pub fn set(name: &str, value: f32) -> Transaction {
+  set_value(name, Value::F32(F32::new(value)))
+}
+
+pub fn set_bool(name: &str, value: bool) -> Transaction {
+  set_value(name, Value::Bool(value))
+}
+
+// The first cell of #name, if the table exists.
+pub fn get(core: &Core, name: &str) -> Option<Value> {
+  core.get_table_by_id(hash_str(name)).ok().map(|table| table.borrow().get_raw(0,0).unwrap())
+}
+
+pub fn f32(value: f32) -> Option<Value> {
+  Some(Value::F32(F32::new(value)))
+}
+
+pub fn register(name: &str) -> (TableId,RegisterIndex,RegisterIndex) {
+  (TableId::Global(hash_str(name)),RegisterIndex::All,RegisterIndex::All)
+}
+
+pub fn new_f32_table(name: &str) -> Transaction {
+  vec![
+    Change::NewTable{table_id: hash_str(name), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str(name), column_ix: 0, column_kind: ValueKind::F32},
+  ]
+}
+
+// Every table's contents, in order of table id.
+pub fn dump(core: &Core) -> Vec<String> {
+  let db = core.database.borrow();
+  let mut table_ids: Vec<&u64> = db.tables.keys().collect();
+  table_ids.sort();
+  table_ids.iter().map(|id| format!("{:?}", db.tables[*id].borrow().to_changes())).collect()
+}
+
+// Every table's contents and the dynamic tables, for checking that nothing at
+// all changed.
+pub fn dump_database(core: &Core) -> String {
+  let mut dynamic_tables: Vec<String> = core.database.borrow().dynamic_tables.iter().map(|x| format!("{:?}",x)).collect();
+  dynamic_tables.sort();
+  format!("{:?} {:?}", dump(core), dynamic_tables)
+}
+
+// A kernel that doubles the first cell of one column into another.
+pub struct Double(pub ColumnV<F32>, pub ColumnV<F32>);
+impl MechFunction for Double {
+  fn solve(&self) { let v = self.0.borrow()[0].unwrap(); self.1.borrow_mut()[0] = F32::new(v * 2.0); }
+  fn to_string(&self) -> String { "double".to_string() }
+}
+
+// A block that writes the function of #arg with itself to #out, after the
+// given transformations. A block waiting on a table that doesn't exist yet
+// holds its later transformations back with an error, so errors are ignored.
+pub fn function_block(before: Vec<Transformation>, function: u64, arg: &str, out: &str) -> Block {
+  let mut block = Block::new();
+  for tfm in before {
+    block.add_tfm(tfm).ok();
+  }
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Global(hash_str(out)), rows: 1, columns: 1}).ok();
+  block.add_tfm(Transformation::Function{name: function, arguments: vec![
+    (0,TableId::Global(hash_str(arg)),vec![(TableIndex::All,TableIndex::All)]),
+    (0,TableId::Global(hash_str(arg)),vec![(TableIndex::All,TableIndex::All)])],
+    out: (TableId::Global(hash_str(out)),TableIndex::All,TableIndex::All)}).ok();
+  block
+}
+
+pub fn binary_block(function: u64, arg: &str, out: &str) -> Block {
+  function_block(vec![], function, arg, out)
+}
+
+// A block that doubles #arg into #out, after the given transformations.
+pub fn doubling_block(before: Vec<Transformation>, arg: &str, out: &str) -> Block {
+  function_block(before, *MATH_ADD, arg, out)
+}
+
+// One end of an in-memory byte stream.
+pub struct Pipe {
+  tx: Sender<Vec<u8>>,
+  rx: Receiver<Vec<u8>>,
+  buffer: Vec<u8>,
+}
+
+impl Read for Pipe {
+  fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+    if self.buffer.is_empty() {
+      match self.rx.recv() {
+        Ok(bytes) => self.buffer = bytes,
+        Err(_) => return Ok(0),
+      }
+    }
+    let n = out.len().min(self.buffer.len());
+    out[..n].copy_from_slice(&self.buffer[..n]);
+    self.buffer.drain(..n);
+    Ok(n)
+  }
+}
+
+impl Write for Pipe {
+  fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+    self.tx.send(bytes.to_vec()).ok();
+    Ok(bytes.len())
+  }
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+pub fn pipe() -> (Pipe,Pipe) {
+  let (a_tx,a_rx) = channel();
+  let (b_tx,b_rx) = channel();
+  (Pipe{tx: a_tx, rx: b_rx, buffer: vec![]}, Pipe{tx: b_tx, rx: a_rx, buffer: vec![]})
+}
@@ -0,0 +1,54 @@
+use mech_core::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn diff_holds_changed_cells_of_every_table() {
+  let mut core = Core::new();
+  let mut a = Table::new(hash_str("a"),1,2);
+  a.set_col_kind(0,ValueKind::F32).unwrap();
+  a.set_col_kind(1,ValueKind::F32).unwrap();
+  let mut b = Table::new(hash_str("b"),1,1);
+  b.set_col_kind(0,ValueKind::F32).unwrap();
+  let (a_column,b_column) = (a.get_column_unchecked(0), b.get_column_unchecked(0));
+  core.insert_table(a).unwrap();
+  core.insert_table(b).unwrap();
+  let mut block = Block::new();
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Local(hash_str("x")), rows: 1, columns: 1}).unwrap();
+  block.triggers.insert(register("a"));
+  block.input.insert(register("a"));
+  block.output.insert(register("b"));
+  if let (Column::F32(a_column),Column::F32(b_column)) = (a_column,b_column) {
+    block.plan.push(Double(a_column, b_column));
+  }
+  core.load_blocks(&vec![block]);
+  let (diff,_) = core.process_transaction_diff(&set("a", 4.0)).unwrap();
+  assert_eq!(diff.len(), 2);
+  let (diff,_) = core.process_transaction_diff(&set("a", 4.0)).unwrap();
+  assert_eq!(diff.len(), 0);
+}
+
+#[test]
+fn created_table_diffs_replay_on_a_peer() {
+  let mut core = Core::new();
+  let mut a = Table::new(hash_str("a"),1,2);
+  a.set_col_kind(0,ValueKind::F32).unwrap();
+  a.set_col_kind(1,ValueKind::F32).unwrap();
+  core.insert_table(a).unwrap();
+  let mut peer = Core::new();
+  let init = core.get_table("a").unwrap().borrow().to_changes();
+  peer.process_transaction(&init).unwrap();
+  let (diff,_) = core.process_transaction_diff(&vec![
+    Change::NewTable{table_id: hash_str("c"), rows: 2, columns: 1},
+    Change::ColumnKind{table_id: hash_str("c"), column_ix: 0, column_kind: ValueKind::F32},
+    Change::Set((hash_str("c"), vec![(TableIndex::Index(2), TableIndex::Index(1), Value::F32(F32::new(7.0)))])),
+    Change::Set((hash_str("a"), vec![(TableIndex::Index(1), TableIndex::Index(2), Value::F32(F32::new(5.0)))])),
+  ]).unwrap();
+  peer.process_transaction(&diff).unwrap();
+  assert_eq!(dump(&core), dump(&peer));
+  let (diff,_) = core.process_transaction_diff(&set("c", 1.0)).unwrap();
+  assert_eq!(diff.len(), 1);
+  peer.process_transaction(&diff).unwrap();
+  assert_eq!(dump(&core), dump(&peer));
+}
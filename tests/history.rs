@@ -0,0 +1,48 @@
+use mech_core::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn undo_redo_and_rewind() {
+  let mut core = Core::new();
+  core.history.capacity = 3;
+  let mut a = Table::new(hash_str("a"),1,1);
+  a.set_col_kind(0,ValueKind::F32).unwrap();
+  let mut b = Table::new(hash_str("b"),1,1);
+  b.set_col_kind(0,ValueKind::F32).unwrap();
+  let (a_column,b_column) = (a.get_column_unchecked(0), b.get_column_unchecked(0));
+  core.insert_table(a).unwrap();
+  core.insert_table(b).unwrap();
+  let mut block = Block::new();
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Local(hash_str("x")), rows: 1, columns: 1}).unwrap();
+  block.triggers.insert(register("a"));
+  block.input.insert(register("a"));
+  block.output.insert(register("b"));
+  if let (Column::F32(a_column),Column::F32(b_column)) = (a_column,b_column) {
+    block.plan.push(Double(a_column, b_column));
+  }
+  core.load_blocks(&vec![block]);
+  for i in 1..=4 {
+    core.process_transaction(&set("a", i as f32)).unwrap();
+  }
+  assert_eq!(core.history.step, 4);
+  assert_eq!(get(&core,"b"), f32(8.0));
+  assert_eq!(core.undo().unwrap(), 3);
+  assert_eq!(get(&core,"a"), f32(3.0));
+  assert_eq!(get(&core,"b"), f32(6.0));
+  // Only the last three steps are kept.
+  assert_eq!(core.rewind_to(1).unwrap(), 1);
+  assert_eq!(get(&core,"b"), f32(2.0));
+  assert!(core.rewind_to(0).is_err());
+  assert_eq!(core.redo().unwrap(), 2);
+  assert_eq!(get(&core,"b"), f32(4.0));
+  // A new transaction drops the steps that were undone.
+  core.process_transaction(&vec![Change::NewTable{table_id: hash_str("c"), rows: 1, columns: 1}]).unwrap();
+  assert!(core.get_table("c").is_ok());
+  assert!(core.redo().is_err());
+  core.undo().unwrap();
+  assert!(core.get_table("c").is_err());
+  core.redo().unwrap();
+  assert!(core.get_table("c").is_ok());
+}
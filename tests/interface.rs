@@ -0,0 +1,38 @@
+use mech_core::*;
+
+mod common;
+use common::*;
+
+fn identifier(name: &str) -> Transformation {
+  Transformation::Identifier{name: name.chars().collect(), id: hash_str(name)}
+}
+
+// A doubling block that names #arg and #out, so they show up in the interface.
+fn named_block(arg: &str, out: &str) -> Block {
+  doubling_block(vec![identifier(arg), identifier(out)], arg, out)
+}
+
+#[test]
+fn interface_lists_inputs_and_outputs() {
+  let mut core = Core::new();
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: hash_str("a"), rows: 1, columns: 1},
+    Change::ColumnAlias{table_id: hash_str("a"), column_ix: 0, column_alias: hash_str("x")},
+    Change::ColumnKind{table_id: hash_str("a"), column_ix: 0, column_kind: ValueKind::F32},
+  ]).unwrap();
+  core.load_blocks(&vec![named_block("a", "b"), named_block("b", "c"), named_block("m", "n")]);
+  let interface = core.interface();
+  let inputs: Vec<(Option<String>,bool)> = interface.inputs.iter().map(|table| (table.name.clone(), table.exists)).collect();
+  assert_eq!(inputs, vec![(Some("a".to_string()), true), (Some("m".to_string()), false)]);
+  let outputs: Vec<String> = interface.outputs.iter().map(|table| table.name.clone().unwrap()).collect();
+  assert_eq!(outputs, vec!["b".to_string(), "c".to_string(), "n".to_string()]);
+  assert_eq!(interface.inputs[0].columns[0].kind, ValueKind::F32);
+  assert_eq!(interface.inputs[0].shape, Some(TableShape::Scalar));
+  assert_eq!(interface.missing_inputs().len(), 1);
+  let bytes = bincode::serialize(&interface).unwrap();
+  let deserialized: ProgramInterface = bincode::deserialize(&bytes).unwrap();
+  assert_eq!(deserialized, interface);
+  let json = serde_json::to_string(&interface).unwrap();
+  let deserialized: ProgramInterface = serde_json::from_str(&json).unwrap();
+  assert_eq!(deserialized, interface);
+}
@@ -0,0 +1,100 @@
+use mech_core::*;
+use mech_core::sync::{Rc, RefCell};
+
+mod common;
+use common::dump_database;
+
+// Counts up to a limit, one step each time it's solved.
+struct CountTo(ColumnV<F32>, f32, Rc<RefCell<usize>>);
+impl MechFunction for CountTo {
+  fn solve(&self) {
+    *self.2.borrow_mut() += 1;
+    let v = self.0.borrow()[0].unwrap();
+    if v < self.1 {
+      self.0.borrow_mut()[0] = F32::new(v + 1.0);
+    }
+  }
+  fn to_string(&self) -> String { "count-to".to_string() }
+}
+
+fn counting_core(limit: f32) -> (Core,Rc<RefCell<usize>>) {
+  let mut core = Core::new();
+  let mut table = Table::new(hash_str("a"),1,1);
+  table.set_col_kind(0,ValueKind::F32).unwrap();
+  let column = table.get_column_unchecked(0);
+  core.insert_table(table).unwrap();
+  let solves = Rc::new(RefCell::new(0));
+  let register = (TableId::Global(hash_str("a")),RegisterIndex::All,RegisterIndex::All);
+  let mut block = Block::new();
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Local(hash_str("x")), rows: 1, columns: 1}).unwrap();
+  block.triggers.insert(register);
+  block.output.insert(register);
+  if let Column::F32(column) = column {
+    block.plan.push(CountTo(column, limit, solves.clone()));
+  }
+  core.load_blocks(&vec![block]);
+  (core,solves)
+}
+
+fn set_a(value: f32) -> Transaction {
+  vec![Change::Set((hash_str("a"), vec![(TableIndex::Index(1), TableIndex::Index(1), Value::F32(F32::new(value)))]))]
+}
+
+#[test]
+fn feedback_runs_until_steady_state() {
+  let (mut core,solves) = counting_core(10.0);
+  *solves.borrow_mut() = 0;
+  core.process_transaction(&set_a(0.0)).unwrap();
+  assert_eq!(core.get_table("a").unwrap().borrow().get_raw(0,0).unwrap(), Value::F32(F32::new(10.0)));
+  assert_eq!(*solves.borrow(), 11);
+}
+
+#[test]
+fn feedback_stops_at_iteration_limit() {
+  let (mut core,_) = counting_core(1e9);
+  core.max_iterations = 5;
+  let result = core.process_transaction(&set_a(0.0));
+  assert!(matches!(result.unwrap_err().kind, MechErrorKind::IterationLimitReached(5)));
+}
+
+// Copies one cell into another.
+struct CopyCell(ColumnV<F32>, ColumnV<F32>);
+impl MechFunction for CopyCell {
+  fn solve(&self) {
+    let v = self.0.borrow()[0];
+    self.1.borrow_mut()[0] = v;
+  }
+  fn to_string(&self) -> String { "copy".to_string() }
+}
+
+#[test]
+fn iteration_limit_rolls_back_transaction() {
+  let (mut core,_) = counting_core(1e9);
+  let mut table = Table::new(hash_str("b"),2,1);
+  table.set_col_kind(0,ValueKind::F32).unwrap();
+  core.insert_table(table).unwrap();
+  // d is only written by a block, and follows a.
+  let a = core.get_table("a").unwrap().borrow().get_column_unchecked(0);
+  let mut table = Table::new(hash_str("d"),1,1);
+  table.set_col_kind(0,ValueKind::F32).unwrap();
+  let d = table.get_column_unchecked(0);
+  core.insert_table(table).unwrap();
+  let mut block = Block::new();
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Local(hash_str("y")), rows: 1, columns: 1}).unwrap();
+  block.triggers.insert((TableId::Global(hash_str("a")),RegisterIndex::All,RegisterIndex::All));
+  block.input.insert((TableId::Global(hash_str("a")),RegisterIndex::All,RegisterIndex::All));
+  block.output.insert((TableId::Global(hash_str("d")),RegisterIndex::All,RegisterIndex::All));
+  if let (Column::F32(a), Column::F32(d)) = (a,d) {
+    block.plan.push(CopyCell(a,d));
+  }
+  core.load_blocks(&vec![block]);
+  core.max_iterations = 5;
+  let before = dump_database(&core);
+  let result = core.process_transaction(&vec![
+    Change::Set((hash_str("b"), vec![(TableIndex::Index(2), TableIndex::Index(1), Value::F32(F32::new(3.0)))])),
+    Change::NewTable{table_id: hash_str("c"), rows: 1, columns: 1},
+    Change::Set((hash_str("a"), vec![(TableIndex::Index(1), TableIndex::Index(1), Value::F32(F32::new(100.0)))])),
+  ]);
+  assert!(matches!(result.unwrap_err().kind, MechErrorKind::IterationLimitReached(5)));
+  assert_eq!(dump_database(&core), before);
+}
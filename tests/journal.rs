@@ -0,0 +1,131 @@
+use mech_core::*;
+use std::path::PathBuf;
+
+mod common;
+use common::*;
+
+fn journal_path(name: &str) -> PathBuf {
+  let path = std::env::temp_dir().join(format!("mech-{}-{}.journal", name, std::process::id()));
+  std::fs::remove_file(&path).ok();
+  path
+}
+
+#[test]
+fn replay_transactions_blocks_and_ticks() {
+  let path = journal_path("replay");
+  let path = path.to_str().unwrap();
+  let mut core = Core::new();
+  let clock = ManualClock::new();
+  core.set_time_source(Box::new(clock.clone()));
+  core.record(path).unwrap();
+  core.process_transaction(&new_f32_table("a")).unwrap();
+  core.process_transaction(&set("a", 3.0)).unwrap();
+  core.load_blocks(&vec![doubling_block(vec![], "a", "b"), doubling_block(vec![Transformation::Wait{duration: 5}], "b", "c")]);
+  assert!(core.process_transaction(&set("missing", 3.0)).is_err());
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  clock.advance(5);
+  core.tick().unwrap();
+  core.process_transaction(&set("a", 5.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(10.0));
+  assert_eq!(get(&core,"c"), f32(20.0));
+  assert_eq!(core.journal.as_ref().unwrap().step, 7);
+  let replayed = Core::replay(path).unwrap();
+  assert_eq!(get(&replayed,"b"), f32(10.0));
+  assert_eq!(get(&replayed,"c"), f32(20.0));
+  let partial = Core::replay_to(path, 5).unwrap();
+  assert_eq!(get(&partial,"b"), f32(8.0));
+  assert_eq!(get(&partial,"c"), f32(0.0));
+  let partial = Core::replay_to(path, 6).unwrap();
+  assert_eq!(get(&partial,"c"), f32(16.0));
+  // An entry cut short by a crash is dropped.
+  let truncated = journal_path("truncated");
+  let bytes = std::fs::read(path).unwrap();
+  std::fs::write(&truncated, &bytes[..bytes.len()-3]).unwrap();
+  assert_eq!(Journal::read(truncated.to_str().unwrap()).unwrap().len(), 6);
+  assert!(matches!(Journal::read(journal_path("missing").to_str().unwrap()).unwrap_err().kind, MechErrorKind::FileNotFound(_)));
+  std::fs::remove_file(path).ok();
+  std::fs::remove_file(truncated).ok();
+}
+
+#[test]
+fn replay_block_changes() {
+  let path = journal_path("blocks");
+  let path = path.to_str().unwrap();
+  let mut core = Core::new();
+  core.record(path).unwrap();
+  core.process_transaction(&new_f32_table("a")).unwrap();
+  core.process_transaction(&set("a", 3.0)).unwrap();
+  let (block_ids,_) = core.load_blocks(&vec![doubling_block(vec![], "a", "b"), doubling_block(vec![], "b", "c")]);
+  core.max_iterations = 7;
+  let (new_block_ids,_) = core.replace_block(block_ids[1], doubling_block(vec![], "b", "d")).unwrap();
+  assert_eq!(get(&core,"c"), None);
+  core.disable_block(block_ids[0]).unwrap();
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  assert_eq!(get(&core,"d"), f32(12.0));
+  core.remove_block(new_block_ids[0]).unwrap();
+  core.load_blocks(&vec![doubling_block(vec![], "b", "c")]);
+  core.enable_block(block_ids[0]).unwrap();
+  assert_eq!(get(&core,"c"), f32(16.0));
+  core.process_transaction(&set("a", 6.0)).unwrap();
+  assert_eq!(get(&core,"c"), f32(24.0));
+  assert_eq!(get(&core,"d"), None);
+  let replayed = Core::replay(path).unwrap();
+  assert_eq!(dump(&replayed), dump(&core));
+  assert_eq!(replayed.max_iterations, 7);
+  assert_eq!(replayed.blocks.len(), core.blocks.len());
+  std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn recording_starts_with_the_existing_core() {
+  let path = journal_path("existing");
+  let path = path.to_str().unwrap();
+  let mut core = Core::new();
+  core.process_transaction(&new_f32_table("a")).unwrap();
+  core.process_transaction(&set("a", 3.0)).unwrap();
+  let (block_ids,_) = core.load_blocks(&vec![doubling_block(vec![], "a", "b"), doubling_block(vec![], "b", "c")]);
+  core.disable_block(block_ids[1]).unwrap();
+  core.max_iterations = 7;
+  core.record(path).unwrap();
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(8.0));
+  assert_eq!(get(&core,"c"), f32(12.0));
+  let replayed = Core::replay(path).unwrap();
+  assert_eq!(dump(&replayed), dump(&core));
+  assert_eq!(replayed.max_iterations, 7);
+  std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn inserted_tables_are_replayed() {
+  let path = journal_path("insert");
+  let path = path.to_str().unwrap();
+  let mut core = Core::new();
+  core.record(path).unwrap();
+  let mut table = Table::new(hash_str("a"), 1, 1);
+  table.set_col_kind(0, ValueKind::F32).unwrap();
+  core.insert_table(table).unwrap();
+  core.load_blocks(&vec![doubling_block(vec![], "a", "b")]);
+  core.process_transaction(&set("a", 3.0)).unwrap();
+  let replayed = Core::replay(path).unwrap();
+  assert_eq!(get(&replayed,"b"), f32(6.0));
+  assert_eq!(dump(&replayed), dump(&core));
+  std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn history_is_refused_while_recording() {
+  let path = journal_path("history");
+  let path = path.to_str().unwrap();
+  let mut core = Core::new();
+  core.history.capacity = 10;
+  core.process_transaction(&new_f32_table("a")).unwrap();
+  core.process_transaction(&set("a", 3.0)).unwrap();
+  core.record(path).unwrap();
+  assert_eq!(core.undo().unwrap_err().id, 1026);
+  assert_eq!(core.rewind_to(0).unwrap_err().id, 1026);
+  core.stop_recording();
+  core.undo().unwrap();
+  assert_eq!(get(&core,"a"), f32(0.0));
+  std::fs::remove_file(path).ok();
+}
@@ -0,0 +1,52 @@
+use mech_core::*;
+use mech_core::sync::{Rc, RefCell};
+
+mod common;
+use common::*;
+
+// Logs the first cell of every table it's sent, and fails if asked to.
+struct Logger(u64, Rc<RefCell<Vec<String>>>, bool);
+impl Machine for Logger {
+  fn name(&self) -> String { format!("logger{}", self.0) }
+  fn id(&self) -> u64 { self.0 }
+  fn on_change(&mut self, table: &Table) -> Result<(), MechError> {
+    self.1.borrow_mut().push(format!("{}:{:?}", self.0, table.get_raw(0,0).unwrap()));
+    match self.2 {
+      true => Err(MechError{msg: "".to_string(), id: 1, kind: MechErrorKind::None}),
+      false => Ok(()),
+    }
+  }
+}
+
+#[test]
+fn machines_see_changes_to_their_tables() {
+  let mut core = Core::new();
+  let mut columns = vec![];
+  for name in &["a","b","c"] {
+    let mut table = Table::new(hash_str(name),1,1);
+    table.set_col_kind(0,ValueKind::F32).unwrap();
+    columns.push(table.get_column_unchecked(0));
+    core.insert_table(table).unwrap();
+  }
+  let mut block = Block::new();
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Local(hash_str("x")), rows: 1, columns: 1}).unwrap();
+  block.triggers.insert(register("a"));
+  block.input.insert(register("a"));
+  block.output.insert(register("b"));
+  if let (Column::F32(a),Column::F32(b)) = (columns[0].clone(),columns[1].clone()) {
+    block.plan.push(Double(a, b));
+  }
+  core.load_blocks(&vec![block]);
+  let log = Rc::new(RefCell::new(vec![]));
+  core.register_machine(Box::new(Logger(1, log.clone(), false)), &vec![hash_str("b")]).unwrap();
+  core.register_machine(Box::new(Logger(2, log.clone(), true)), &vec![hash_str("c")]).unwrap();
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  assert_eq!(*log.borrow(), vec!["1:8".to_string()]);
+  // The failing machine doesn't undo the transaction, and its error comes back
+  // with it.
+  let (_,_,machine_errors) = core.process_transaction(&set("c", 1.0)).unwrap();
+  assert_eq!(machine_errors.len(), 1);
+  assert_eq!(machine_errors[0].0, 2);
+  assert_eq!(get(&core,"c"), f32(1.0));
+  assert_eq!(log.borrow().len(), 2);
+}
@@ -0,0 +1,49 @@
+use mech_core::*;
+use mech_core::function::math::*;
+use mech_core::function::table::CopyVV;
+
+mod common;
+use common::*;
+
+#[test]
+fn profile_covers_latest_transaction() {
+  let mut core = Core::new();
+  core.process_transaction(&new_f32_table("a")).unwrap();
+  core.load_blocks(&vec![binary_block(*MATH_ADD,"a","b"), binary_block(*MATH_MULTIPLY,"b","c")]);
+  core.enable_profiler().unwrap();
+  core.process_transaction(&set("a", 3.0)).unwrap();
+  let slowest = core.profile().slowest_blocks();
+  assert_eq!(slowest.len(), 2);
+  for block_profile in &slowest {
+    assert_eq!(block_profile.block.calls, 1);
+    assert!(!block_profile.steps.is_empty());
+  }
+  let table = core.get_table("mech/profile").unwrap();
+  {
+    let table_brrw = table.borrow();
+    assert_eq!(table_brrw.rows, 2 + slowest.iter().map(|block_profile| block_profile.steps.len()).sum::<usize>());
+    assert_eq!(table_brrw.get(&TableIndex::Index(1), &TableIndex::Alias(hash_str("calls"))).unwrap(), Value::U64(U64::new(1)));
+  }
+  // A transaction that solves nothing clears the profile.
+  core.process_transaction(&vec![]).unwrap();
+  assert_eq!(core.profile().slowest_blocks().len(), 0);
+  assert_eq!(table.borrow().rows, 0);
+  core.disable_profiler();
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  assert_eq!(core.profile().slowest_blocks().len(), 0);
+}
+
+#[test]
+fn kernels_are_named_by_type() {
+  let column = ColumnV::new(vec![F32::new(1.0)]);
+  let kernel = CopyVV{arg: (column.clone(), 0, 0), out: (column.clone(), 0, 0)};
+  assert_eq!(kernel.name(), "CopyVV");
+  let mut core = Core::new();
+  core.process_transaction(&new_f32_table("a")).unwrap();
+  core.load_blocks(&vec![binary_block(*MATH_ADD,"a","b")]);
+  for block_ref in core.blocks.values() {
+    for name in block_ref.borrow().plan.kernel_names() {
+      assert!(!name.is_empty() && !name.contains("::") && !name.contains('<'), "{}", name);
+    }
+  }
+}
@@ -0,0 +1,178 @@
+use mech_core::*;
+use mech_core::function::stats::*;
+use mech_core::function::table::*;
+
+mod common;
+use common::{dump, set};
+
+fn f32(value: f32) -> Value {
+  Value::F32(F32::new(value))
+}
+
+fn get(core: &Core, name: &str, row: usize, col: usize) -> Value {
+  core.get_table_by_id(hash_str(name)).unwrap().borrow().get_raw(row,col).unwrap()
+}
+
+fn rows(core: &Core) -> usize {
+  core.get_table_by_id(hash_str("t")).unwrap().borrow().rows
+}
+
+// #t is a column of 1, 2 and 3, with the last row aliased as c, and #s sums it.
+fn sum_core() -> Core {
+  let mut core = Core::new();
+  let t = hash_str("t");
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: t, rows: 3, columns: 1},
+    Change::ColumnKind{table_id: t, column_ix: 0, column_kind: ValueKind::F32},
+    Change::RowAlias{table_id: t, row_ix: 2, row_alias: hash_str("c")},
+    Change::Set((t, vec![(TableIndex::Index(1), TableIndex::Index(1), f32(1.0)), (TableIndex::Index(2), TableIndex::Index(1), f32(2.0)), (TableIndex::Index(3), TableIndex::Index(1), f32(3.0))])),
+  ]).unwrap();
+  let mut block = Block::new();
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Global(hash_str("s")), rows: 1, columns: 1}).unwrap();
+  block.add_tfm(Transformation::Function{name: *STATS_SUM, arguments: vec![(hash_str("column"), TableId::Global(t), vec![(TableIndex::All, TableIndex::All)])], out: (TableId::Global(hash_str("s")),TableIndex::All,TableIndex::All)}).ok();
+  let (_,errors) = core.load_blocks(&vec![block]);
+  assert!(errors.is_empty());
+  core
+}
+
+#[test]
+fn insert_remove_and_resize_rows() {
+  let mut core = sum_core();
+  let t = hash_str("t");
+  assert_eq!(get(&core,"s",0,0), f32(6.0));
+  core.process_transaction(&vec![
+    Change::InsertRows{table_id: t, row_ix: 1, rows: 2},
+    Change::Set((t, vec![(TableIndex::Index(2), TableIndex::Index(1), f32(10.0))])),
+  ]).unwrap();
+  assert_eq!(rows(&core), 5);
+  assert_eq!(get(&core,"t",4,0), f32(3.0));
+  assert_eq!(core.get_table_by_id(t).unwrap().borrow().get(&TableIndex::Alias(hash_str("c")), &TableIndex::Index(1)).unwrap(), f32(3.0));
+  assert_eq!(get(&core,"s",0,0), f32(16.0));
+  core.process_transaction(&vec![Change::RemoveRows{table_id: t, rows: RowSelection::Indices(vec![0,2])}]).unwrap();
+  assert_eq!(rows(&core), 3);
+  assert_eq!(get(&core,"s",0,0), f32(15.0));
+  core.process_transaction(&vec![Change::RemoveRows{table_id: t, rows: RowSelection::Mask(vec![true,false,false])}]).unwrap();
+  assert_eq!(get(&core,"s",0,0), f32(5.0));
+  core.process_transaction(&vec![Change::Resize{table_id: t, rows: 1, columns: 1}]).unwrap();
+  assert_eq!(get(&core,"s",0,0), f32(2.0));
+  assert!(core.process_transaction(&vec![
+    Change::InsertRows{table_id: t, row_ix: 0, rows: 1},
+    Change::RemoveRows{table_id: t, rows: RowSelection::Indices(vec![9])},
+  ]).is_err());
+  assert_eq!(rows(&core), 1);
+  assert_eq!(get(&core,"t",0,0), f32(2.0));
+}
+
+#[test]
+fn removed_tables_can_be_defined_again() {
+  let mut core = sum_core();
+  let t = hash_str("t");
+  core.process_transaction(&vec![Change::RemoveTable{table_id: t}]).unwrap();
+  assert!(core.get_table_by_id(t).is_err());
+  assert!(core.get_table_by_id(hash_str("s")).is_err());
+  assert!(core.process_transaction(&vec![Change::RemoveTable{table_id: t}]).is_err());
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: t, rows: 2, columns: 1},
+    Change::ColumnKind{table_id: t, column_ix: 0, column_kind: ValueKind::F32},
+    Change::Set((t, vec![(TableIndex::Index(1), TableIndex::Index(1), f32(4.0)), (TableIndex::Index(2), TableIndex::Index(1), f32(5.0))])),
+  ]).unwrap();
+  assert_eq!(get(&core,"s",0,0), f32(9.0));
+}
+
+// Blocks are recompiled when a table they read is reshaped, but only solved
+// once, so an append doesn't append twice.
+#[test]
+fn appends_from_a_reshaped_table_happen_once() {
+  let mut core = Core::new();
+  let src = hash_str("src");
+  let out = hash_str("out");
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: src, rows: 1, columns: 2},
+    Change::ColumnKind{table_id: src, column_ix: 0, column_kind: ValueKind::F32},
+    Change::ColumnKind{table_id: src, column_ix: 1, column_kind: ValueKind::F32},
+    Change::Set((src, vec![(TableIndex::Index(1), TableIndex::Index(1), f32(1.0)), (TableIndex::Index(1), TableIndex::Index(2), f32(2.0))])),
+    Change::NewTable{table_id: out, rows: 0, columns: 2},
+  ]).unwrap();
+  let mut block = Block::new();
+  block.add_tfm(Transformation::Function{name: *TABLE_APPEND, arguments: vec![(0, TableId::Global(src), vec![(TableIndex::All, TableIndex::All)])], out: (TableId::Global(out),TableIndex::All,TableIndex::All)}).ok();
+  let (_,errors) = core.load_blocks(&vec![block]);
+  assert!(errors.is_empty());
+  let rows = |core: &Core| core.get_table_by_id(out).unwrap().borrow().rows;
+  assert_eq!(rows(&core), 1);
+  core.process_transaction(&vec![
+    Change::InsertRows{table_id: src, row_ix: 1, rows: 1},
+    Change::Set((src, vec![(TableIndex::Index(2), TableIndex::Index(1), f32(3.0)), (TableIndex::Index(2), TableIndex::Index(2), f32(4.0))])),
+  ]).unwrap();
+  assert_eq!(rows(&core), 3);
+  assert_eq!(get(&core,"out",2,0), f32(3.0));
+  assert_eq!(get(&core,"out",2,1), f32(4.0));
+}
+
+#[test]
+fn reshaping_transactions_are_undone() {
+  let mut core = sum_core();
+  let t = hash_str("t");
+  core.history.capacity = 10;
+  let before = dump(&core);
+  core.process_transaction(&vec![Change::Resize{table_id: t, rows: 5, columns: 1}, Change::NewTable{table_id: hash_str("n"), rows: 2, columns: 2}]).unwrap();
+  let reshaped = dump(&core);
+  core.process_transaction(&vec![Change::Set((t, vec![(TableIndex::Index(5), TableIndex::Index(1), f32(9.0))]))]).unwrap();
+  assert_eq!(get(&core,"s",0,0), f32(15.0));
+  let set_again = dump(&core);
+  core.undo().unwrap();
+  assert_eq!(dump(&core), reshaped);
+  core.undo().unwrap();
+  assert_eq!(dump(&core), before);
+  core.redo().unwrap();
+  assert_eq!(dump(&core), reshaped);
+  core.redo().unwrap();
+  assert_eq!(dump(&core), set_again);
+}
+
+#[test]
+fn reshaping_diffs_replay_on_a_peer() {
+  let mut core = Core::new();
+  let mut a = Table::new(hash_str("a"),1,2);
+  a.set_col_kind(0,ValueKind::F32).unwrap();
+  a.set_col_kind(1,ValueKind::F32).unwrap();
+  core.insert_table(a).unwrap();
+  let mut peer = Core::new();
+  let init = core.get_table("a").unwrap().borrow().to_changes();
+  peer.process_transaction(&init).unwrap();
+  let (diff,_) = core.process_transaction_diff(&vec![
+    Change::NewTable{table_id: hash_str("c"), rows: 2, columns: 1},
+    Change::ColumnKind{table_id: hash_str("c"), column_ix: 0, column_kind: ValueKind::F32},
+    Change::Set((hash_str("c"), vec![(TableIndex::Index(2), TableIndex::Index(1), Value::F32(F32::new(7.0)))])),
+    Change::Resize{table_id: hash_str("a"), rows: 3, columns: 2},
+    Change::Set((hash_str("a"), vec![(TableIndex::Index(3), TableIndex::Index(1), Value::F32(F32::new(5.0)))])),
+  ]).unwrap();
+  peer.process_transaction(&diff).unwrap();
+  assert_eq!(dump(&core), dump(&peer));
+  let (diff,_) = core.process_transaction_diff(&vec![Change::RemoveRows{table_id: hash_str("a"), rows: RowSelection::Indices(vec![0])}]).unwrap();
+  peer.process_transaction(&diff).unwrap();
+  assert_eq!(dump(&core), dump(&peer));
+  let (diff,_) = core.process_transaction_diff(&set("c", 1.0)).unwrap();
+  assert_eq!(diff.len(), 1);
+  peer.process_transaction(&diff).unwrap();
+  assert_eq!(dump(&core), dump(&peer));
+}
+
+// A row alias past the end grows the table, and when that's rolled back the
+// columns are cut back with it, so rows can still be removed.
+#[test]
+fn rows_can_be_removed_after_a_growing_change_rolls_back() {
+  let mut core = Core::new();
+  let g = hash_str("g");
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: g, rows: 2, columns: 1},
+    Change::ColumnKind{table_id: g, column_ix: 0, column_kind: ValueKind::F32},
+  ]).unwrap();
+  assert!(core.process_transaction(&vec![
+    Change::RowAlias{table_id: g, row_ix: 5, row_alias: hash_str("far")},
+    Change::Set((hash_str("missing"), vec![])),
+  ]).is_err());
+  core.process_transaction(&vec![Change::RemoveRows{table_id: g, rows: RowSelection::Mask(vec![false,true])}]).unwrap();
+  let table = core.get_table_by_id(g).unwrap();
+  assert_eq!(table.borrow().rows, 1);
+  assert_eq!(table.borrow().get_col_raw(0).unwrap().len(), 1);
+}
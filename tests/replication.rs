@@ -0,0 +1,123 @@
+use mech_core::*;
+use hashbrown::HashSet;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::channel;
+
+mod common;
+use common::{set, pipe};
+
+fn dump(core: &Core, name: &str) -> String {
+  format!("{:?}", core.get_table_by_id(hash_str(name)).map(|table| table.borrow().to_changes()).ok())
+}
+
+#[test]
+fn replicate_over_tcp() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let address = listener.local_addr().unwrap();
+  let publisher = std::thread::spawn(move || {
+    let mut core = Core::new();
+    let mut table = Table::new(hash_str("a"),1,1);
+    table.set_col_kind(0,ValueKind::F32).unwrap();
+    core.insert_table(table).unwrap();
+    core.process_transaction(&set("a", 1.0)).unwrap();
+    let (stream,_) = listener.accept().unwrap();
+    let mut published = HashSet::new();
+    published.insert(hash_str("a"));
+    let mut publisher = Publisher::accept(stream, &core, &published).unwrap();
+    let (diff,_) = core.process_transaction_diff(&set("a", 7.0)).unwrap();
+    publisher.send(&diff).unwrap();
+  });
+  let mut core = Core::new();
+  let mut subscriber = Subscriber::connect(TcpStream::connect(address).unwrap(), vec![hash_str("a")]).unwrap();
+  subscriber.receive(&mut core).unwrap();
+  assert_eq!(core.get_table("a").unwrap().borrow().get_raw(0,0).unwrap(), Value::F32(F32::new(1.0)));
+  subscriber.receive(&mut core).unwrap();
+  assert_eq!(core.get_table("a").unwrap().borrow().get_raw(0,0).unwrap(), Value::F32(F32::new(7.0)));
+  publisher.join().unwrap();
+}
+
+#[test]
+fn every_change_to_a_published_table_is_forwarded() {
+  let (publisher_end,subscriber_end) = pipe();
+  let (dump_tx,dump_rx) = channel::<String>();
+  let publisher = std::thread::spawn(move || {
+    let mut core = Core::new();
+    core.process_transaction(&vec![
+      Change::NewTable{table_id: hash_str("a"), rows: 1, columns: 1},
+      Change::ColumnKind{table_id: hash_str("a"), column_ix: 0, column_kind: ValueKind::F32},
+      Change::NewTable{table_id: hash_str("hidden"), rows: 1, columns: 1},
+      Change::ColumnKind{table_id: hash_str("hidden"), column_ix: 0, column_kind: ValueKind::Bool},
+    ]).unwrap();
+    let mut published = HashSet::new();
+    published.insert(hash_str("a"));
+    let mut publisher = Publisher::accept(publisher_end, &core, &published).unwrap();
+    let steps = vec![
+      set("a", 7.0),
+      vec![Change::ColumnAlias{table_id: hash_str("a"), column_ix: 0, column_alias: hash_str("x")}, Change::Set((hash_str("hidden"), vec![(TableIndex::Index(1), TableIndex::Index(1), Value::Bool(true))]))],
+      set("a", 4.0),
+    ];
+    for txn in steps {
+      let (diff,_) = core.process_transaction_diff(&txn).unwrap();
+      publisher.send(&diff).unwrap();
+      dump_tx.send(dump(&core, "a")).unwrap();
+    }
+  });
+  let mut core = Core::new();
+  let mut subscriber = Subscriber::connect(subscriber_end, vec![hash_str("a")]).unwrap();
+  subscriber.receive(&mut core).unwrap();
+  for _ in 0..3 {
+    let txn = subscriber.receive(&mut core).unwrap();
+    assert!(txn.iter().all(|change| change.table_id() == hash_str("a")));
+    assert_eq!(dump(&core, "a"), dump_rx.recv().unwrap());
+  }
+  assert!(core.get_table_by_id(hash_str("hidden")).is_err());
+  publisher.join().unwrap();
+}
+
+#[test]
+fn publisher_rejects_other_versions() {
+  let (publisher_end,mut subscriber_end) = pipe();
+  let publisher = std::thread::spawn(move || {
+    let core = Core::new();
+    Publisher::accept(publisher_end, &core, &HashSet::new()).err().unwrap()
+  });
+  write_message(&mut subscriber_end, &ReplicationMessage::Hello{version: 99}).unwrap();
+  match read_message(&mut subscriber_end).unwrap() {
+    ReplicationMessage::Rejected(error) => assert!(matches!(error.kind, MechErrorKind::ProtocolVersionMismatch(99,REPLICATION_VERSION))),
+    message => panic!("{:?}", message),
+  }
+  assert!(matches!(publisher.join().unwrap().kind, MechErrorKind::ProtocolVersionMismatch(99,_)));
+}
+
+#[test]
+fn initial_state_replaces_existing_copy() {
+  let (publisher_end,subscriber_end) = pipe();
+  let publisher = std::thread::spawn(move || {
+    let mut core = Core::new();
+    let mut table = Table::new(hash_str("a"),2,1);
+    table.set_col_kind(0,ValueKind::F32).unwrap();
+    core.insert_table(table).unwrap();
+    core.process_transaction(&set("a", 3.0)).unwrap();
+    let mut published = HashSet::new();
+    published.insert(hash_str("a"));
+    Publisher::accept(publisher_end, &core, &published).unwrap();
+    dump(&core, "a")
+  });
+  // The subscriber still has a stale copy of #a, from an earlier connection.
+  let mut core = Core::new();
+  let mut table = Table::new(hash_str("a"),1,1);
+  table.set_col_kind(0,ValueKind::F32).unwrap();
+  core.insert_table(table).unwrap();
+  core.process_transaction(&set("a", 1.0)).unwrap();
+  let mut subscriber = Subscriber::connect(subscriber_end, vec![hash_str("a")]).unwrap();
+  subscriber.receive(&mut core).unwrap();
+  assert_eq!(dump(&core, "a"), publisher.join().unwrap());
+}
+
+#[test]
+fn oversized_messages_are_rejected() {
+  let mut bytes = ((MAX_MESSAGE_SIZE + 1) as u32).to_le_bytes().to_vec();
+  bytes.extend(vec![0;16]);
+  let error = read_message(&mut bytes.as_slice()).unwrap_err();
+  assert!(matches!(error.kind, MechErrorKind::MessageTooLarge(_,MAX_MESSAGE_SIZE)));
+}
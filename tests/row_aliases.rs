@@ -0,0 +1,89 @@
+use mech_core::*;
+use mech_core::function::math::*;
+
+fn f32(value: f32) -> Value {
+  Value::F32(F32::new(value))
+}
+
+fn set_config(row: &str, col: usize, value: f32) -> Transaction {
+  vec![Change::Set((hash_str("config"), vec![(TableIndex::Alias(hash_str(row)), TableIndex::Index(col), f32(value))]))]
+}
+
+fn get(core: &Core, name: &str, row: usize, col: usize) -> Value {
+  core.get_table_by_id(hash_str(name)).unwrap().borrow().get_raw(row,col).unwrap()
+}
+
+fn config_core() -> Core {
+  let mut core = Core::new();
+  let config = hash_str("config");
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: config, rows: 2, columns: 2},
+    Change::ColumnAlias{table_id: config, column_ix: 0, column_alias: hash_str("speed")},
+    Change::ColumnAlias{table_id: config, column_ix: 1, column_alias: hash_str("dir")},
+    Change::ColumnKind{table_id: config, column_ix: 0, column_kind: ValueKind::F32},
+    Change::ColumnKind{table_id: config, column_ix: 1, column_kind: ValueKind::F32},
+    Change::RowAlias{table_id: config, row_ix: 0, row_alias: hash_str("left-motor")},
+    Change::RowAlias{table_id: config, row_ix: 1, row_alias: hash_str("right-motor")},
+  ]).unwrap();
+  core.process_transaction(&set_config("left-motor", 1, 2.0)).unwrap();
+  core.process_transaction(&set_config("right-motor", 1, 5.0)).unwrap();
+  core
+}
+
+#[test]
+fn set_and_get_by_row_alias() {
+  let core = config_core();
+  assert_eq!(get(&core,"config",0,0), f32(2.0));
+  assert_eq!(get(&core,"config",1,0), f32(5.0));
+  let table = core.get_table_by_id(hash_str("config")).unwrap();
+  assert_eq!(table.borrow().get(&TableIndex::Alias(hash_str("right-motor")),&TableIndex::Alias(hash_str("speed"))).unwrap(), f32(5.0));
+  assert!(table.borrow().get(&TableIndex::Alias(hash_str("missing")),&TableIndex::Index(1)).is_err());
+}
+
+#[test]
+fn blocks_read_and_trigger_on_row_aliases() {
+  let mut core = config_core();
+  let left_speed = vec![(TableIndex::Alias(hash_str("left-motor")),TableIndex::Alias(hash_str("speed")))];
+  let mut block = Block::new();
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Global(hash_str("out")), rows: 1, columns: 1}).unwrap();
+  block.add_tfm(Transformation::Function{name: *MATH_ADD, arguments: vec![
+    (0,TableId::Global(hash_str("config")),left_speed.clone()),
+    (0,TableId::Global(hash_str("config")),left_speed)],
+    out: (TableId::Global(hash_str("out")),TableIndex::All,TableIndex::All)}).ok();
+  core.load_blocks(&vec![block]);
+  assert_eq!(get(&core,"out",0,0), f32(4.0));
+  core.process_transaction(&set_config("left-motor", 1, 3.0)).unwrap();
+  assert_eq!(get(&core,"out",0,0), f32(6.0));
+  let mut block = Block::new();
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Global(hash_str("row")), rows: 1, columns: 1}).unwrap();
+  block.add_tfm(Transformation::TableDefine{table_id: TableId::Global(hash_str("config")), indices: vec![(TableIndex::Alias(hash_str("right-motor")),TableIndex::None)], out: TableId::Global(hash_str("row"))}).ok();
+  core.load_blocks(&vec![block]);
+  let row = core.get_table_by_id(hash_str("row")).unwrap();
+  assert_eq!(row.borrow().rows, 1);
+  assert_eq!(row.borrow().cols, 2);
+  assert_eq!(get(&core,"row",0,0), f32(5.0));
+  core.process_transaction(&set_config("right-motor", 1, 7.0)).unwrap();
+  assert_eq!(get(&core,"row",0,0), f32(7.0));
+}
+
+#[test]
+fn growing_changes_roll_back() {
+  let mut core = Core::new();
+  let g = hash_str("g");
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: g, rows: 2, columns: 1},
+    Change::ColumnKind{table_id: g, column_ix: 0, column_kind: ValueKind::F32},
+  ]).unwrap();
+  assert!(core.process_transaction(&vec![
+    Change::RowAlias{table_id: g, row_ix: 5, row_alias: hash_str("far")},
+    Change::Set((hash_str("missing"), vec![])),
+  ]).is_err());
+  let table = core.get_table_by_id(g).unwrap();
+  assert_eq!(table.borrow().rows, 2);
+  assert_eq!(table.borrow().get_col_raw(0).unwrap().len(), 2);
+  assert!(core.process_transaction(&vec![
+    Change::ColumnKind{table_id: g, column_ix: 3, column_kind: ValueKind::F32},
+    Change::Set((hash_str("missing"), vec![])),
+  ]).is_err());
+  assert_eq!(table.borrow().cols, 1);
+}
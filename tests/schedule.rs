@@ -0,0 +1,103 @@
+use mech_core::*;
+use mech_core::sync::{Rc, RefCell};
+
+mod common;
+use common::{set, register};
+
+struct Log(Rc<RefCell<Vec<String>>>, String);
+impl MechFunction for Log {
+  fn solve(&self) { self.0.borrow_mut().push(self.1.clone()); }
+  fn to_string(&self) -> String { "log".to_string() }
+}
+
+fn logging_block(log: &Rc<RefCell<Vec<String>>>, name: &str, trigger: &str, output: &str) -> Block {
+  let mut block = Block::new();
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Local(hash_str(name)), rows: 1, columns: 1}).unwrap();
+  block.triggers.insert(register(trigger));
+  block.input.insert(register(trigger));
+  block.output.insert(register(output));
+  block.plan.push(Log(log.clone(), name.to_string()));
+  block
+}
+
+fn core_with(tables: &[&str]) -> Core {
+  let mut core = Core::new();
+  for name in tables {
+    let mut table = Table::new(hash_str(name),1,1);
+    table.set_col_kind(0,ValueKind::F32).unwrap();
+    core.insert_table(table).unwrap();
+  }
+  core
+}
+
+#[test]
+fn diamond_solves_each_block_once() {
+  let log = Rc::new(RefCell::new(vec![]));
+  let mut core = core_with(&["a","b","c","d","e"]);
+  let (_,errors) = core.load_blocks(&vec![
+    logging_block(&log,"E","d","e"),
+    logging_block(&log,"B","a","b"),
+    logging_block(&log,"C","a","c"),
+    logging_block(&log,"D1","b","d"),
+    logging_block(&log,"D2","c","d"),
+  ]);
+  assert!(errors.is_empty());
+  log.borrow_mut().clear();
+  core.process_transaction(&set("a", 5.0)).unwrap();
+  let log = log.borrow();
+  assert_eq!(log.len(), 5);
+  assert_eq!(log.iter().filter(|name| *name == "E").count(), 1);
+  assert_eq!(log.last().unwrap(), "E");
+}
+
+#[test]
+fn block_closing_cycle_is_left_unscheduled() {
+  let log = Rc::new(RefCell::new(vec![]));
+  let mut core = core_with(&["a","b","c"]);
+  let (_,errors) = core.load_blocks(&vec![logging_block(&log,"A","a","b"), logging_block(&log,"B","b","a")]);
+  assert!(errors.iter().any(|error| matches!(error.kind, MechErrorKind::CyclicDependency(_))));
+  log.borrow_mut().clear();
+  core.process_transaction(&set("a", 5.0)).unwrap();
+  assert_eq!(*log.borrow(), vec!["A"]);
+  let (_,block_refs) = core.full_errors.iter().find(|(error,_)| matches!(error.kind, MechErrorKind::CyclicDependency(_))).unwrap();
+  let cyclic_id = block_refs[0].borrow().id;
+  assert!(!core.blocks.contains_key(&cyclic_id));
+}
+
+#[test]
+fn solve_orders_follow_added_and_removed_blocks() {
+  let log = Rc::new(RefCell::new(vec![]));
+  let mut core = core_with(&["t0","t1","t2","t3","t4","t5"]);
+  // The end of the chain is loaded first, then the start.
+  let (_,errors) = core.load_blocks(&vec![logging_block(&log,"B4","t4","t5"), logging_block(&log,"B3","t3","t4")]);
+  assert!(errors.is_empty());
+  let (block_ids,errors) = core.load_blocks(&vec![logging_block(&log,"B0","t0","t1"), logging_block(&log,"B1","t1","t2"), logging_block(&log,"B2","t2","t3")]);
+  assert!(errors.is_empty());
+  log.borrow_mut().clear();
+  core.process_transaction(&set("t0", 5.0)).unwrap();
+  assert_eq!(*log.borrow(), vec!["B0","B1","B2","B3","B4"]);
+  core.remove_block(block_ids[2]).unwrap();
+  log.borrow_mut().clear();
+  core.process_transaction(&set("t0", 6.0)).unwrap();
+  assert_eq!(*log.borrow(), vec!["B0","B1"]);
+  core.load_blocks(&vec![logging_block(&log,"B2","t2","t3")]);
+  log.borrow_mut().clear();
+  core.process_transaction(&set("t0", 7.0)).unwrap();
+  assert_eq!(*log.borrow(), vec!["B0","B1","B2","B3","B4"]);
+}
+
+#[test]
+fn block_closing_longer_cycle_is_left_unscheduled() {
+  let log = Rc::new(RefCell::new(vec![]));
+  let mut core = core_with(&["a","b","c"]);
+  let (_,errors) = core.load_blocks(&vec![logging_block(&log,"A","a","b"), logging_block(&log,"C","c","a"), logging_block(&log,"B","b","c")]);
+  let cycle = errors.iter().find_map(|error| match &error.kind {
+    MechErrorKind::CyclicDependency(cycle) => Some(cycle.clone()),
+    _ => None,
+  }).unwrap();
+  assert_eq!(cycle.len(), 4);
+  assert_eq!(cycle.first(), cycle.last());
+  log.borrow_mut().clear();
+  core.process_transaction(&set("a", 5.0)).unwrap();
+  assert_eq!(*log.borrow(), vec!["A"]);
+}
@@ -0,0 +1,41 @@
+use mech_core::*;
+use mech_core::function::math::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn restored_core_keeps_tables_and_blocks() {
+  let mut core = Core::new();
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: hash_str("a"), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str("a"), column_ix: 0, column_kind: ValueKind::F32},
+  ]).unwrap();
+  core.process_transaction(&set("a", 2.0)).unwrap();
+  let mut block = Block::new();
+  block.add_tfm(Transformation::Identifier{name: "a".chars().collect(), id: hash_str("a")}).unwrap();
+  block.add_tfm(Transformation::Identifier{name: "b".chars().collect(), id: hash_str("b")}).unwrap();
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Global(hash_str("b")), rows: 1, columns: 1}).unwrap();
+  block.add_tfm(Transformation::Function{name: *MATH_ADD, arguments: vec![
+    (0,TableId::Global(hash_str("a")),vec![(TableIndex::All,TableIndex::All)]),
+    (0,TableId::Global(hash_str("a")),vec![(TableIndex::All,TableIndex::All)])],
+    out: (TableId::Global(hash_str("b")),TableIndex::All,TableIndex::All)}).ok();
+  let (_,errors) = core.load_blocks(&vec![block]);
+  assert!(errors.is_empty());
+  core.process_transaction(&set("a", 3.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(6.0));
+  let bytes = core.snapshot().unwrap();
+  let mut restored = Core::restore(&bytes).unwrap();
+  assert_eq!(get(&restored,"a"), f32(3.0));
+  assert_eq!(get(&restored,"b"), f32(6.0));
+  restored.process_transaction(&set("a", 5.0)).unwrap();
+  assert_eq!(get(&restored,"b"), f32(10.0));
+}
+
+#[test]
+fn restore_rejects_other_versions() {
+  let core = Core::new();
+  let mut bytes = core.snapshot().unwrap();
+  bytes[4] = 9;
+  assert!(matches!(Core::restore(&bytes).err().unwrap().kind, MechErrorKind::SnapshotVersionMismatch(9,1)));
+}
@@ -0,0 +1,70 @@
+use mech_core::*;
+use mech_core::function::math::*;
+
+mod common;
+use common::*;
+
+fn string(value: &str) -> Option<Value> {
+  Some(Value::String(MechString::from_str(value)))
+}
+
+fn global(name: &str) -> TableId {
+  TableId::Global(hash_str(name))
+}
+
+fn all() -> Vec<(TableIndex,TableIndex)> {
+  vec![(TableIndex::All,TableIndex::All)]
+}
+
+fn identifier(block: &mut Block, name: &str) {
+  block.add_tfm(Transformation::Identifier{name: name.chars().collect(), id: hash_str(name)}).unwrap();
+}
+
+// An entry action for a state of #robot, which doubles #arg into #out.
+fn entry_action(state: &str, arg: &str, out: &str) -> Block {
+  let mut block = Block::new();
+  block.add_tfm(Transformation::OnEnter{table_id: global("robot"), state: hash_str(state)}).ok();
+  block.add_tfm(Transformation::NewTable{table_id: global(out), rows: 1, columns: 1}).ok();
+  block.add_tfm(Transformation::Function{name: *MATH_ADD, arguments: vec![(0,global(arg),all()), (0,global(arg),all())], out: (global(out),TableIndex::All,TableIndex::All)}).ok();
+  block
+}
+
+#[test]
+fn machine_takes_one_transition_per_step() {
+  let mut core = Core::new();
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: hash_str("a"), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str("a"), column_ix: 0, column_kind: ValueKind::F32},
+    Change::NewTable{table_id: hash_str("go"), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str("go"), column_ix: 0, column_kind: ValueKind::Bool},
+    Change::NewTable{table_id: hash_str("halt"), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str("halt"), column_ix: 0, column_kind: ValueKind::Bool},
+  ]).unwrap();
+  core.process_transaction(&set("a", 3.0)).unwrap();
+  let mut machine = Block::new();
+  for name in &["robot","idle","moving","stopped"] {
+    identifier(&mut machine, name);
+  }
+  machine.add_tfm(Transformation::NewTable{table_id: global("robot"), rows: 1, columns: 1}).unwrap();
+  machine.add_tfm(Transformation::StateMachine{table_id: global("robot"), initial: hash_str("idle")}).unwrap();
+  machine.add_tfm(Transformation::StateTransition{table_id: global("robot"), from: hash_str("idle"), to: hash_str("moving"), guard: global("go"), indices: all()}).ok();
+  machine.add_tfm(Transformation::StateTransition{table_id: global("robot"), from: hash_str("moving"), to: hash_str("stopped"), guard: global("halt"), indices: all()}).ok();
+  machine.add_tfm(Transformation::StateTransition{table_id: global("robot"), from: hash_str("stopped"), to: hash_str("idle"), guard: global("go"), indices: all()}).ok();
+  core.load_blocks(&vec![entry_action("moving","a","b"), machine, entry_action("idle","a","c")]);
+  assert_eq!(get(&core,"robot"), string("idle"));
+  assert_eq!(get(&core,"b"), f32(0.0));
+  assert_eq!(get(&core,"c"), f32(6.0));
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(0.0));
+  core.process_transaction(&set_bool("go", true)).unwrap();
+  assert_eq!(get(&core,"robot"), string("moving"));
+  assert_eq!(get(&core,"b"), f32(8.0));
+  core.process_transaction(&set_bool("halt", true)).unwrap();
+  assert_eq!(get(&core,"robot"), string("stopped"));
+  // Go still holds, so the machine keeps moving, one state per step.
+  core.process_transaction(&vec![]).unwrap();
+  assert_eq!(get(&core,"robot"), string("idle"));
+  assert_eq!(get(&core,"c"), f32(8.0));
+  core.process_transaction(&vec![]).unwrap();
+  assert_eq!(get(&core,"robot"), string("moving"));
+}
@@ -0,0 +1,226 @@
+use mech_core::*;
+use std::io::Write;
+use std::path::PathBuf;
+
+mod common;
+use common::{set_value, doubling_block};
+
+fn f32(value: f32) -> Value {
+  Value::F32(F32::new(value))
+}
+
+fn string(value: &str) -> Value {
+  Value::String(MechString::from_str(value))
+}
+
+fn get(core: &Core, name: &str, row: usize, col: usize) -> Value {
+  core.get_table_by_id(hash_str(name)).unwrap().borrow().get_raw(row,col).unwrap()
+}
+
+fn rows(core: &Core, name: &str) -> usize {
+  core.get_table_by_id(hash_str(name)).unwrap().borrow().rows
+}
+
+fn storage_dir(name: &str) -> PathBuf {
+  let path = std::env::temp_dir().join(format!("mech-storage-{}-{}", name, std::process::id()));
+  std::fs::remove_dir_all(&path).ok();
+  path
+}
+
+// #pose is persistent once the test asks for it, and #tmp never is.
+fn new_tables(core: &mut Core) {
+  let pose = hash_str("pose");
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: pose, rows: 1, columns: 2},
+    Change::ColumnAlias{table_id: pose, column_ix: 0, column_alias: hash_str("x")},
+    Change::ColumnKind{table_id: pose, column_ix: 0, column_kind: ValueKind::F32},
+    Change::ColumnKind{table_id: pose, column_ix: 1, column_kind: ValueKind::String},
+    Change::NewTable{table_id: hash_str("tmp"), rows: 1, columns: 1},
+    Change::Set((pose, vec![(TableIndex::Index(1), TableIndex::Index(1), f32(1.0)), (TableIndex::Index(1), TableIndex::Index(2), string("a"))])),
+  ]).unwrap();
+}
+
+#[test]
+fn persistent_tables_survive_reopening() {
+  let dir = storage_dir("reopen");
+  let path = dir.to_str().unwrap();
+  {
+    let mut core = Core::open(path).unwrap();
+    new_tables(&mut core);
+    core.persist(hash_str("pose")).unwrap();
+    core.persist(hash_str("later")).unwrap();
+    core.process_transaction(&set_value("pose", f32(2.0))).unwrap();
+    core.process_transaction(&vec![Change::Append{table_id: hash_str("pose"), rows: vec![vec![f32(3.0), string("b")]]}]).unwrap();
+    core.process_transaction(&vec![
+      Change::NewTable{table_id: hash_str("later"), rows: 1, columns: 1},
+      Change::ColumnKind{table_id: hash_str("later"), column_ix: 0, column_kind: ValueKind::F32},
+      Change::Set((hash_str("later"), vec![(TableIndex::Index(1), TableIndex::Index(1), f32(9.0))])),
+    ]).unwrap();
+    assert!(core.process_transaction(&set_value("pose", string("bad"))).is_err());
+  }
+  let mut core = Core::open(path).unwrap();
+  assert!(core.get_table_by_id(hash_str("tmp")).is_err());
+  assert_eq!(rows(&core,"pose"), 2);
+  assert_eq!(get(&core,"pose",0,0), f32(2.0));
+  assert_eq!(get(&core,"pose",1,1), string("b"));
+  assert_eq!(get(&core,"later",0,0), f32(9.0));
+  core.process_transaction(&vec![Change::Set((hash_str("pose"), vec![(TableIndex::Index(2), TableIndex::Alias(hash_str("x")), f32(5.0))]))]).unwrap();
+  core.process_transaction(&vec![Change::RemoveTable{table_id: hash_str("later")}]).unwrap();
+  drop(core);
+  let core = Core::open(path).unwrap();
+  assert_eq!(get(&core,"pose",1,0), f32(5.0));
+  assert!(core.get_table_by_id(hash_str("later")).is_err());
+  std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn truncated_log_tail_is_dropped() {
+  let dir = storage_dir("tail");
+  let path = dir.to_str().unwrap();
+  {
+    let mut core = Core::open(path).unwrap();
+    new_tables(&mut core);
+    core.persist(hash_str("pose")).unwrap();
+    core.process_transaction(&set_value("pose", f32(6.0))).unwrap();
+  }
+  // A record whose length runs past the end of the log, as a crash mid-write
+  // would leave it.
+  let mut wal = std::fs::OpenOptions::new().append(true).open(dir.join("wal")).unwrap();
+  wal.write_all(&[200,0,0,0,1,2,3,4,5,6,7,8,9,9]).unwrap();
+  drop(wal);
+  {
+    let mut core = Core::open(path).unwrap();
+    assert_eq!(get(&core,"pose",0,0), f32(6.0));
+    core.process_transaction(&set_value("pose", f32(42.0))).unwrap();
+  }
+  let core = Core::open(path).unwrap();
+  assert_eq!(get(&core,"pose",0,0), f32(42.0));
+  assert_eq!(get(&core,"pose",0,1), string("a"));
+  std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn compaction_starts_a_new_generation() {
+  let dir = storage_dir("compaction");
+  let path = dir.to_str().unwrap();
+  {
+    let mut core = Core::open(path).unwrap();
+    new_tables(&mut core);
+    core.persist(hash_str("pose")).unwrap();
+    core.storage.as_mut().unwrap().snapshot_interval = 3;
+    for i in 0..7 {
+      core.process_transaction(&set_value("pose", f32(i as f32))).unwrap();
+    }
+    assert!(core.storage.as_ref().unwrap().generation >= 2);
+  }
+  let core = Core::open(path).unwrap();
+  assert_eq!(get(&core,"pose",0,0), f32(6.0));
+  assert_eq!(get(&core,"pose",0,1), string("a"));
+  drop(core);
+  // A log left over from before a compaction isn't replayed over the snapshot.
+  let stale_wal = std::fs::read(dir.join("wal")).unwrap();
+  {
+    let mut core = Core::open(path).unwrap();
+    core.process_transaction(&set_value("pose", f32(7.0))).unwrap();
+    core.compact_storage().unwrap();
+  }
+  std::fs::write(dir.join("wal"), stale_wal).unwrap();
+  let core = Core::open(path).unwrap();
+  assert_eq!(get(&core,"pose",0,0), f32(7.0));
+  std::fs::remove_dir_all(&dir).ok();
+}
+
+fn new_a(core: &mut Core, value: f32) {
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: hash_str("a"), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str("a"), column_ix: 0, column_kind: ValueKind::F32},
+    Change::Set((hash_str("a"), vec![(TableIndex::Index(1), TableIndex::Index(1), f32(value))])),
+  ]).unwrap();
+}
+
+#[test]
+fn blocks_loaded_after_open_write_persistent_tables() {
+  let dir = storage_dir("blocks");
+  let path = dir.to_str().unwrap();
+  {
+    let mut core = Core::open(path).unwrap();
+    core.persist(hash_str("doubled")).unwrap();
+    new_a(&mut core, 3.0);
+    let (_,errors) = core.load_blocks(&vec![doubling_block(vec![], "a", "doubled")]);
+    assert!(errors.is_empty());
+    assert_eq!(get(&core,"doubled",0,0), f32(6.0));
+  }
+  {
+    let mut core = Core::open(path).unwrap();
+    assert_eq!(get(&core,"doubled",0,0), f32(6.0));
+    new_a(&mut core, 4.0);
+    let (_,errors) = core.load_blocks(&vec![doubling_block(vec![], "a", "doubled")]);
+    assert!(errors.is_empty());
+    assert_eq!(get(&core,"doubled",0,0), f32(8.0));
+  }
+  {
+    let mut core = Core::open(path).unwrap();
+    assert_eq!(get(&core,"doubled",0,0), f32(8.0));
+    new_a(&mut core, 5.0);
+    core.load_blocks(&vec![doubling_block(vec![], "a", "doubled")]);
+    core.process_transaction(&set_value("a", f32(6.0))).unwrap();
+    assert_eq!(get(&core,"doubled",0,0), f32(12.0));
+  }
+  let core = Core::open(path).unwrap();
+  assert_eq!(get(&core,"doubled",0,0), f32(12.0));
+  std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn enabled_blocks_write_persistent_tables() {
+  let dir = storage_dir("enable");
+  let path = dir.to_str().unwrap();
+  {
+    let mut core = Core::open(path).unwrap();
+    core.persist(hash_str("doubled")).unwrap();
+    new_a(&mut core, 3.0);
+    let (block_ids,_) = core.load_blocks(&vec![doubling_block(vec![], "a", "doubled")]);
+    core.disable_block(block_ids[0]).unwrap();
+    core.process_transaction(&set_value("a", f32(5.0))).unwrap();
+    core.enable_block(block_ids[0]).unwrap();
+    assert_eq!(get(&core,"doubled",0,0), f32(10.0));
+  }
+  let core = Core::open(path).unwrap();
+  assert_eq!(get(&core,"doubled",0,0), f32(10.0));
+  std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn moving_through_the_history_is_stored() {
+  let dir = storage_dir("history");
+  let path = dir.to_str().unwrap();
+  {
+    let mut core = Core::open(path).unwrap();
+    core.history.capacity = 10;
+    new_tables(&mut core);
+    core.persist(hash_str("pose")).unwrap();
+    core.process_transaction(&set_value("pose", f32(2.0))).unwrap();
+    core.process_transaction(&set_value("pose", f32(3.0))).unwrap();
+    core.undo().unwrap();
+    assert_eq!(get(&core,"pose",0,0), f32(2.0));
+  }
+  {
+    let mut core = Core::open(path).unwrap();
+    assert_eq!(get(&core,"pose",0,0), f32(2.0));
+    core.history.capacity = 10;
+    core.process_transaction(&set_value("pose", f32(4.0))).unwrap();
+    core.process_transaction(&set_value("pose", f32(5.0))).unwrap();
+    core.rewind_to(0).unwrap();
+    core.redo().unwrap();
+    assert_eq!(get(&core,"pose",0,0), f32(4.0));
+  }
+  let core = Core::open(path).unwrap();
+  assert_eq!(get(&core,"pose",0,0), f32(4.0));
+  std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn persist_needs_storage() {
+  let mut core = Core::new();
+  assert!(core.persist(hash_str("pose")).is_err());
+}
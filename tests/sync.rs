@@ -0,0 +1,49 @@
+#![cfg(feature = "sync")]
+
+use mech_core::*;
+use mech_core::function::math::*;
+use mech_core::sync::RefCell;
+
+mod common;
+use common::*;
+
+#[test]
+fn core_runs_on_another_thread() {
+  let mut core = Core::new();
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: hash_str("a"), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str("a"), column_ix: 0, column_kind: ValueKind::F32},
+  ]).unwrap();
+  let mut blocks = vec![];
+  for i in 0..50 {
+    blocks.push(binary_block(*MATH_ADD,"a",&format!("b{}",i)));
+    blocks.push(binary_block(*MATH_MULTIPLY,&format!("b{}",i),&format!("c{}",i)));
+  }
+  core.load_blocks(&blocks);
+  let core = std::thread::spawn(move || {
+    for value in 1..20 {
+      core.process_transaction(&set("a", value as f32)).unwrap();
+    }
+    core
+  }).join().unwrap();
+  for i in 0..50 {
+    assert_eq!(get(&core,&format!("b{}",i)), Some(Value::F32(F32::new(38.0))));
+    assert_eq!(get(&core,&format!("c{}",i)), Some(Value::F32(F32::new(38.0*38.0))));
+  }
+}
+
+#[test]
+#[should_panic(expected = "already mutably borrowed")]
+fn borrow_while_mutably_borrowed_panics() {
+  let cell = RefCell::new(1);
+  let _write = cell.borrow_mut();
+  let _read = cell.borrow();
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn mutable_borrow_while_borrowed_panics() {
+  let cell = RefCell::new(1);
+  let _read = cell.borrow();
+  let _write = cell.borrow_mut();
+}
@@ -0,0 +1,119 @@
+use mech_core::*;
+use mech_core::sync::{Rc, RefCell};
+
+mod common;
+use common::*;
+
+struct Counter(Rc<RefCell<usize>>);
+impl Machine for Counter {
+  fn name(&self) -> String { "counter".to_string() }
+  fn id(&self) -> u64 { 1 }
+  fn on_change(&mut self, _table: &Table) -> Result<(),MechError> {
+    *self.0.borrow_mut() += 1;
+    Ok(())
+  }
+}
+
+fn core_with_clock() -> (Core,ManualClock) {
+  let mut core = Core::new();
+  let clock = ManualClock::new();
+  core.set_time_source(Box::new(clock.clone()));
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: hash_str("a"), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str("a"), column_ix: 0, column_kind: ValueKind::F32},
+    Change::NewTable{table_id: hash_str("ready"), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str("ready"), column_ix: 0, column_kind: ValueKind::Bool},
+  ]).unwrap();
+  core.process_transaction(&set("a", 3.0)).unwrap();
+  (core,clock)
+}
+
+#[test]
+fn wait_delays_the_first_solve() {
+  let (mut core,clock) = core_with_clock();
+  core.load_blocks(&vec![doubling_block(vec![Transformation::Wait{duration: 3}],"a","b")]);
+  assert_eq!(get(&core,"b"), f32(0.0));
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(0.0));
+  clock.advance(2);
+  core.tick().unwrap();
+  assert_eq!(get(&core,"b"), f32(0.0));
+  clock.advance(1);
+  core.tick().unwrap();
+  assert_eq!(get(&core,"b"), f32(8.0));
+  core.process_transaction(&set("a", 5.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(10.0));
+}
+
+#[test]
+fn ticks_are_undone() {
+  let (mut core,clock) = core_with_clock();
+  core.history.capacity = 10;
+  core.load_blocks(&vec![doubling_block(vec![Transformation::Wait{duration: 5}],"a","c")]);
+  let loaded = dump(&core);
+  let step = core.history.step;
+  clock.advance(5);
+  core.tick().unwrap();
+  assert_eq!(core.history.step, step + 1);
+  assert_eq!(get(&core,"c"), f32(6.0));
+  let ticked = dump(&core);
+  // A tick that changes nothing isn't recorded.
+  core.tick().unwrap();
+  assert_eq!(core.history.step, step + 1);
+  core.process_transaction(&set("a", 9.0)).unwrap();
+  let set_again = dump(&core);
+  core.undo().unwrap();
+  assert_eq!(dump(&core), ticked);
+  core.undo().unwrap();
+  assert_eq!(dump(&core), loaded);
+  core.redo().unwrap();
+  assert_eq!(dump(&core), ticked);
+  core.redo().unwrap();
+  assert_eq!(dump(&core), set_again);
+  assert_eq!(core.history.transactions().len(), 1);
+}
+
+#[test]
+fn ticks_are_profiled() {
+  let (mut core,clock) = core_with_clock();
+  core.load_blocks(&vec![doubling_block(vec![],"a","b"), doubling_block(vec![Transformation::Wait{duration: 5}],"a","c")]);
+  core.enable_profiler().unwrap();
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  clock.advance(5);
+  core.tick().unwrap();
+  assert_eq!(core.profile().slowest_blocks().len(), 1);
+  assert!(core.get_table("mech/profile").unwrap().borrow().rows > 0);
+}
+
+#[test]
+fn wait_for_holds_until_condition() {
+  let (mut core,_clock) = core_with_clock();
+  core.load_blocks(&vec![doubling_block(vec![Transformation::WaitFor{table_id: TableId::Global(hash_str("ready")), indices: vec![(TableIndex::All,TableIndex::All)]}],"a","b")]);
+  assert_eq!(get(&core,"b"), f32(0.0));
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(0.0));
+  core.process_transaction(&set_bool("ready", true)).unwrap();
+  assert_eq!(get(&core,"b"), f32(8.0));
+}
+
+#[test]
+fn until_solves_every_tick_until_condition() {
+  let (mut core,clock) = core_with_clock();
+  let count = Rc::new(RefCell::new(0));
+  core.load_blocks(&vec![doubling_block(vec![Transformation::Until{table_id: TableId::Global(hash_str("ready")), indices: vec![(TableIndex::All,TableIndex::All)]}],"a","b")]);
+  assert_eq!(get(&core,"b"), f32(6.0));
+  core.register_machine(Box::new(Counter(count.clone())), &vec![hash_str("b")]).unwrap();
+  for _ in 0..3 {
+    clock.advance(1);
+    core.tick().unwrap();
+  }
+  assert_eq!(*count.borrow(), 3);
+  core.process_transaction(&set_bool("ready", true)).unwrap();
+  let before = *count.borrow();
+  for _ in 0..3 {
+    core.tick().unwrap();
+  }
+  assert_eq!(*count.borrow(), before);
+  core.process_transaction(&set("a", 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(6.0));
+}
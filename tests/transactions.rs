@@ -0,0 +1,25 @@
+use mech_core::*;
+
+mod common;
+use common::dump_database;
+
+#[test]
+fn failed_change_rolls_back_transaction() {
+  let mut core = Core::new();
+  let mut table = Table::new(hash_str("a"),2,1);
+  table.set_col_kind(0,ValueKind::F32).unwrap();
+  core.insert_table(table).unwrap();
+  let before = dump_database(&core);
+  let txn = vec![
+    Change::Set((hash_str("a"), vec![(TableIndex::Index(1), TableIndex::Index(1), Value::F32(F32::new(5.0)))])),
+    Change::NewTable{table_id: hash_str("b"), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str("a"), column_ix: 1, column_kind: ValueKind::Bool},
+    Change::Set((hash_str("a"), vec![(TableIndex::Index(3), TableIndex::Index(1), Value::F32(F32::new(5.0)))])),
+  ];
+  assert!(matches!(core.process_transaction(&txn).unwrap_err().kind, MechErrorKind::InvalidChange(3,_)));
+  let a = core.get_table("a").unwrap();
+  assert_eq!(a.borrow().get_raw(0,0).unwrap(), Value::F32(F32::new(0.0)));
+  assert_eq!(a.borrow().cols, 1);
+  assert!(core.get_table("b").is_err());
+  assert_eq!(dump_database(&core), before);
+}
@@ -0,0 +1,42 @@
+use mech_core::*;
+use mech_core::sync::{Rc, RefCell};
+
+struct Count(Rc<RefCell<usize>>);
+impl MechFunction for Count {
+  fn solve(&self) { *self.0.borrow_mut() += 1; }
+  fn to_string(&self) -> String { "count".to_string() }
+}
+
+fn counting_block(name: &str, trigger: (TableId,RegisterIndex,RegisterIndex)) -> (Block,Rc<RefCell<usize>>) {
+  let count = Rc::new(RefCell::new(0));
+  let mut block = Block::new();
+  block.add_tfm(Transformation::NewTable{table_id: TableId::Local(hash_str(name)), rows: 1, columns: 1}).unwrap();
+  block.triggers.insert(trigger);
+  block.plan.push(Count(count.clone()));
+  (block,count)
+}
+
+#[test]
+fn set_triggers_only_blocks_on_changed_columns() {
+  let mut core = Core::new();
+  let mut table = Table::new(hash_str("robot"),1,2);
+  table.set_col_kind(0,ValueKind::F32).unwrap();
+  table.set_col_kind(1,ValueKind::F32).unwrap();
+  table.set_col_alias(0,hash_str("x")).unwrap();
+  table.set_col_alias(1,hash_str("battery")).unwrap();
+  core.insert_table(table).unwrap();
+  let robot = TableId::Global(hash_str("robot"));
+  let (x_block,x_count) = counting_block("x",(robot,RegisterIndex::All,RegisterIndex::Alias(hash_str("x"))));
+  let (battery_block,battery_count) = counting_block("battery",(robot,RegisterIndex::All,RegisterIndex::Alias(hash_str("battery"))));
+  let (robot_block,robot_count) = counting_block("robot",(robot,RegisterIndex::All,RegisterIndex::All));
+  core.load_blocks(&vec![x_block,battery_block,robot_block]);
+  *x_count.borrow_mut() = 0;
+  *battery_count.borrow_mut() = 0;
+  *robot_count.borrow_mut() = 0;
+  core.process_transaction(&vec![Change::Set((hash_str("robot"), vec![
+    (TableIndex::Index(1), TableIndex::Index(1), Value::F32(F32::new(5.0))),
+    (TableIndex::Index(1), TableIndex::Alias(hash_str("x")), Value::F32(F32::new(6.0)))]))]).unwrap();
+  assert_eq!(*x_count.borrow(), 1);
+  assert_eq!(*battery_count.borrow(), 0);
+  assert_eq!(*robot_count.borrow(), 1);
+}
@@ -0,0 +1,81 @@
+use mech_core::*;
+
+mod common;
+use common::*;
+
+fn set_row(name: &str, row: usize, value: f32) -> Transaction {
+  vec![Change::Set((hash_str(name), vec![(TableIndex::Index(row), TableIndex::Index(1), Value::F32(F32::new(value)))]))]
+}
+
+fn sensor_core() -> Core {
+  let mut core = Core::new();
+  core.process_transaction(&vec![
+    Change::NewTable{table_id: hash_str("a"), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str("a"), column_ix: 0, column_kind: ValueKind::F32},
+    Change::NewTable{table_id: hash_str("sensors"), rows: 3, columns: 2},
+    Change::ColumnAlias{table_id: hash_str("sensors"), column_ix: 1, column_alias: hash_str("temp")},
+    Change::ColumnKind{table_id: hash_str("sensors"), column_ix: 0, column_kind: ValueKind::F32},
+    Change::ColumnKind{table_id: hash_str("sensors"), column_ix: 1, column_kind: ValueKind::F32},
+    Change::NewTable{table_id: hash_str("ready"), rows: 1, columns: 1},
+    Change::ColumnKind{table_id: hash_str("ready"), column_ix: 0, column_kind: ValueKind::Bool},
+  ]).unwrap();
+  core.process_transaction(&set_row("a", 1, 3.0)).unwrap();
+  core
+}
+
+#[test]
+fn whenever_row_fires_on_that_row() {
+  let mut core = sensor_core();
+  core.load_blocks(&vec![doubling_block(vec![Transformation::Whenever{table_id: TableId::Global(hash_str("sensors")), indices: vec![(TableIndex::Index(2),TableIndex::None)]}],"a","b")]);
+  assert_eq!(get(&core,"b"), f32(6.0));
+  core.process_transaction(&set_row("a", 1, 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(6.0));
+  core.process_transaction(&set_row("sensors", 1, 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(6.0));
+  core.process_transaction(&set_row("sensors", 2, 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(8.0));
+}
+
+#[test]
+fn whenever_column_fires_on_that_column() {
+  let mut core = sensor_core();
+  core.load_blocks(&vec![doubling_block(vec![Transformation::Whenever{table_id: TableId::Global(hash_str("sensors")), indices: vec![(TableIndex::All,TableIndex::Alias(hash_str("temp")))]}],"a","b")]);
+  core.process_transaction(&set_row("a", 1, 4.0)).unwrap();
+  core.process_transaction(&set_row("sensors", 1, 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(6.0));
+  core.process_transaction(&vec![Change::Set((hash_str("sensors"), vec![(TableIndex::Index(3), TableIndex::Index(2), Value::F32(F32::new(1.0)))]))]).unwrap();
+  assert_eq!(get(&core,"b"), f32(8.0));
+  core.process_transaction(&set_row("a", 1, 5.0)).unwrap();
+  core.process_transaction(&vec![Change::Set((hash_str("sensors"), vec![(TableIndex::Index(1), TableIndex::Alias(hash_str("temp")), Value::F32(F32::new(1.0)))]))]).unwrap();
+  assert_eq!(get(&core,"b"), f32(10.0));
+}
+
+#[test]
+fn whenever_after_the_function_still_replaces_its_triggers() {
+  let mut core = sensor_core();
+  let mut block = doubling_block(vec![],"a","b");
+  block.add_tfm(Transformation::Whenever{table_id: TableId::Global(hash_str("sensors")), indices: vec![(TableIndex::Index(2),TableIndex::None)]}).ok();
+  core.load_blocks(&vec![block]);
+  core.process_transaction(&set_row("a", 1, 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(6.0));
+  core.process_transaction(&set_row("sensors", 2, 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(8.0));
+}
+
+#[test]
+fn whenever_rising_fires_on_rising_edge() {
+  let mut core = sensor_core();
+  core.load_blocks(&vec![doubling_block(vec![Transformation::WheneverRising{table_id: TableId::Global(hash_str("ready")), indices: vec![(TableIndex::All,TableIndex::All)]}],"a","b")]);
+  assert_eq!(get(&core,"b"), f32(0.0));
+  core.process_transaction(&set_row("a", 1, 4.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(0.0));
+  core.process_transaction(&set_bool("ready", true)).unwrap();
+  assert_eq!(get(&core,"b"), f32(8.0));
+  core.process_transaction(&set_row("a", 1, 5.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(8.0));
+  core.process_transaction(&set_bool("ready", false)).unwrap();
+  core.process_transaction(&set_row("a", 1, 6.0)).unwrap();
+  assert_eq!(get(&core,"b"), f32(8.0));
+  core.process_transaction(&set_bool("ready", true)).unwrap();
+  assert_eq!(get(&core,"b"), f32(12.0));
+}